@@ -13,21 +13,21 @@ use tauri_plugin_opener::OpenerExt;
 use tauri_specta::Event;
 use tokio::time::sleep;
 use tracing::instrument;
-use walkdir::WalkDir;
 
 use crate::{
     config::Config,
     errors::{CommandError, CommandResult},
     events::UpdateDownloadedComicsEvent,
     export,
-    extensions::{AppHandleExt, EyreReportToMessage, WalkDirEntryExt},
-    logger,
+    extensions::{AppHandleExt, EyreReportToMessage},
+    library_dedup, library_migration, library_watcher, logger,
     responses::{
         ChapterInGetChaptersRespData, GetChapterRespData, LoginRespData, UserProfileRespData,
     },
     types::{
-        ChapterInfo, Comic, ComicInFavorite, ComicInSearch, GetFavoriteOrdering, GetFavoriteResult,
-        LogMetadata, SearchResult,
+        CbzComicInfoUpdateReport, ChapterInfo, Comic, ComicInFavorite, ComicInSearch,
+        DuplicateComicsReport, FmtKind, FmtPreview, GetFavoriteOrdering, GetFavoriteResult,
+        LibraryMigrationReport, LogMetadata, SearchResult,
     },
     utils,
 };
@@ -60,6 +60,9 @@ pub fn save_config(app: AppHandle, config: Config) -> CommandResult<()> {
         .enable_file_logger
         .ne(&enable_file_logger);
 
+    let download_dir = config.download_dir.clone();
+    let download_dir_changed = config_state.read().download_dir.ne(&download_dir);
+
     {
         // 包裹在大括号中，以便自动释放写锁
         let mut config_state = config_state.write();
@@ -80,6 +83,11 @@ pub fn save_config(app: AppHandle, config: Config) -> CommandResult<()> {
         }
     }
 
+    if download_dir_changed {
+        library_watcher::watch_download_dir(download_dir)
+            .map_err(|err| CommandError::from("监听新的下载目录失败", err))?;
+    }
+
     Ok(())
 }
 
@@ -234,11 +242,17 @@ pub async fn get_favorite(
     skip_all,
     fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name)
 )]
-pub fn save_metadata(comic: Comic) -> CommandResult<()> {
+pub fn save_metadata(app: AppHandle, comic: Comic) -> CommandResult<()> {
     comic
         .save_metadata()
         .map_err(|err| CommandError::from("保存元数据失败", err))?;
 
+    if let Some(comic_download_dir) = &comic.comic_download_dir {
+        app.get_library_index()
+            .update_comic_metadata(comic_download_dir)
+            .map_err(|err| CommandError::from("保存元数据失败", err))?;
+    }
+
     Ok(())
 }
 
@@ -247,65 +261,21 @@ pub fn save_metadata(comic: Comic) -> CommandResult<()> {
 #[allow(clippy::needless_pass_by_value)]
 #[instrument(level = "error", skip_all)]
 pub fn get_downloaded_comics(app: AppHandle) -> Vec<Comic> {
-    let config = app.get_config();
-
-    let download_dir = config.read().download_dir.clone();
-    // 遍历下载目录，获取所有元数据文件的路径和修改时间
-    let mut metadata_path_and_modify_time_pairs = Vec::new();
-    for entry in WalkDir::new(&download_dir)
-        .into_iter()
-        .filter_map(Result::ok)
+    let download_dir = app.get_config().read().download_dir.clone();
+    // 从索引中获取，已经按照元数据文件的修改时间排序，最新的排在最前面
+    let library_index = app.get_library_index();
+    let downloaded_comics = match library_index
+        .ensure_built(&download_dir)
+        .and_then(|()| library_index.get_downloaded_comics())
     {
-        let path = entry.path();
-
-        if !entry.is_comic_metadata() {
-            continue;
-        }
-
-        let metadata = match path
-            .metadata()
-            .map_err(eyre::Report::from)
-            .wrap_err(format!("获取`{}`的metadata失败", path.display()))
-        {
-            Ok(metadata) => metadata,
-            Err(err) => {
-                let err_title = "获取已下载漫画的过程中遇到错误，已跳过";
-                let message = err.to_message();
-                tracing::error!(err_title, message);
-                continue;
-            }
-        };
-
-        let modify_time = match metadata
-            .modified()
-            .map_err(eyre::Report::from)
-            .wrap_err(format!("获取`{}`的修改时间失败", path.display()))
-        {
-            Ok(modify_time) => modify_time,
-            Err(err) => {
-                let err_title = "获取已下载漫画的过程中遇到错误，已跳过";
-                let message = err.to_message();
-                tracing::error!(err_title, message);
-                continue;
-            }
-        };
-
-        metadata_path_and_modify_time_pairs.push((path.to_path_buf(), modify_time));
-    }
-    // 按照文件修改时间排序，最新的排在最前面
-    metadata_path_and_modify_time_pairs.sort_by(|(_, a), (_, b)| b.cmp(a));
-
-    let mut downloaded_comics = Vec::new();
-    for (metadata_path, _) in metadata_path_and_modify_time_pairs {
-        match Comic::from_metadata(&metadata_path) {
-            Ok(comic) => downloaded_comics.push(comic),
-            Err(err) => {
-                let err_title = "获取已下载漫画的过程中遇到错误，已跳过";
-                let message = err.to_message();
-                tracing::error!(err_title, message);
-            }
+        Ok(downloaded_comics) => downloaded_comics,
+        Err(err) => {
+            let err_title = "获取已下载漫画失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+            return Vec::new();
         }
-    }
+    };
 
     // 按照漫画ID分组，以方便去重
     let mut comics_by_path_word: IndexMap<String, Vec<Comic>> = IndexMap::new();
@@ -329,8 +299,9 @@ pub fn get_downloaded_comics(app: AppHandle) -> Vec<Comic> {
             continue;
         }
 
-        // 选第一个作为保留的漫画
-        let chosen_download_dir = comic_download_dirs[0];
+        // 选下载章节最多的版本作为保留的漫画
+        let chosen_index = library_dedup::choose_kept_copy(&comics);
+        let chosen_download_dir = comic_download_dirs[chosen_index];
 
         if comics.len() > 1 {
             let dir_paths_string = comic_download_dirs
@@ -347,13 +318,12 @@ pub fn get_downloaded_comics(app: AppHandle) -> Vec<Comic> {
                     chosen_download_dir.display()
                 ))
                 .wrap_err(format!(
-                    "漫画`{comic_title}`在下载目录里有多个版本，请手动处理，只保留一个版本"
+                    "漫画`{comic_title}`在下载目录里有多个版本，请合并重复的版本，或手动处理只保留一个版本"
                 ))
                 .to_message();
             tracing::error!(err_title, message);
         }
-        // 取第一个作为保留的漫画
-        let chosen_comic = comics.remove(0);
+        let chosen_comic = comics.swap_remove(chosen_index);
         unique_comics.push(chosen_comic);
     }
 
@@ -393,6 +363,33 @@ pub fn export_cbz_chapters(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn update_cbz_comic_info(
+    app: AppHandle,
+    comic: Comic,
+) -> CommandResult<CbzComicInfoUpdateReport> {
+    let report = export::update_cbz_comic_info(&app, &comic)
+        .map_err(|err| CommandError::from("更新cbz的ComicInfo失败", err))?;
+    Ok(report)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn estimate_pdf_size(
+    app: AppHandle,
+    comic: Comic,
+    chapter_uuids: Vec<String>,
+) -> CommandResult<u64> {
+    let size = export::estimate_pdf_size(&app, &comic, &chapter_uuids)
+        .map_err(|err| CommandError::from("估算pdf大小失败", err))?;
+    Ok(size)
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 #[allow(clippy::needless_pass_by_value)]
@@ -408,6 +405,104 @@ pub fn export_pdf_chapters(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn export_long_img(app: AppHandle, comic: Comic) -> CommandResult<()> {
+    export::long_img(&app, &comic).map_err(|err| CommandError::from("漫画导出长图失败", err))?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_long_img_chapters(
+    app: AppHandle,
+    comic: Comic,
+    chapter_uuids: Vec<String>,
+) -> CommandResult<()> {
+    let comic_title = comic.comic.name.clone();
+    export::long_img_chapters(&app, &comic, chapter_uuids)
+        .context(format!("漫画`{comic_title}`导出指定章节长图失败"))
+        .map_err(|err| CommandError::from("漫画导出指定章节长图失败", err))?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn export_epub(app: AppHandle, comic: Comic) -> CommandResult<()> {
+    export::epub(&app, &comic).map_err(|err| CommandError::from("漫画导出epub失败", err))?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_epub_chapters(
+    app: AppHandle,
+    comic: Comic,
+    chapter_uuids: Vec<String>,
+) -> CommandResult<()> {
+    let comic_title = comic.comic.name.clone();
+    export::epub_chapters(&app, &comic, chapter_uuids)
+        .context(format!("漫画`{comic_title}`导出指定章节epub失败"))
+        .map_err(|err| CommandError::from("漫画导出指定章节epub失败", err))?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn preview_fmt(
+    app: AppHandle,
+    comic: Comic,
+    fmt_kind: FmtKind,
+    fmt: String,
+    sample_count: u32,
+) -> CommandResult<FmtPreview> {
+    let sample_count = usize::try_from(sample_count).unwrap_or(usize::MAX);
+    let preview = export::preview_fmt(&app, &comic, fmt_kind, &fmt, sample_count)
+        .map_err(|err| CommandError::from("预览模板失败", err))?;
+    Ok(preview)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(dry_run = dry_run))]
+pub fn migrate_library(app: AppHandle, dry_run: bool) -> CommandResult<LibraryMigrationReport> {
+    let report = library_migration::migrate_library(&app, dry_run)
+        .map_err(|err| CommandError::from("迁移已下载的漫画失败", err))?;
+    Ok(report)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(level = "error", skip_all, fields(dry_run = dry_run))]
+pub fn merge_duplicate_comics(
+    app: AppHandle,
+    dry_run: bool,
+) -> CommandResult<DuplicateComicsReport> {
+    let report = library_dedup::merge_duplicate_comics(&app, dry_run)
+        .map_err(|err| CommandError::from("合并重复的漫画版本失败", err))?;
+    Ok(report)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+#[instrument(level = "error", skip_all, fields(comic_path_word = comic_path_word))]
+pub fn cancel_export(app: AppHandle, comic_path_word: String) -> u32 {
+    let export_queue = app.get_export_queue();
+    // 返回被取消的导出任务数，包括正在执行的和排队中的
+    u32::try_from(export_queue.cancel(&comic_path_word)).unwrap_or(u32::MAX)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
@@ -516,6 +611,12 @@ pub async fn update_downloaded_comics(app: AppHandle) -> CommandResult<()> {
             sleep(Duration::from_secs(interval_sec)).await;
             continue;
         }
+        // 更新库存时顺便刷新封面，封面刷新失败不影响更新
+        if let Err(err) = comic.save_cover(&app).await {
+            let err_title = format!("更新库存过程中，刷新漫画`{comic_title}`的封面失败");
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
 
         let downloaded_groups: HashMap<&String, &Vec<ChapterInfo>> = comic
             .comic
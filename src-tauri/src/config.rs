@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::{AppHandle, Manager};
 
-use crate::types::DownloadFormat;
+use crate::{
+    types::{DownloadFormat, LongImgFormat},
+    zh_convert,
+};
 
 const DEFAULT_API_DOMAIN: &str = "api.2025copy.com";
 
@@ -29,8 +32,40 @@ pub struct Config {
     pub merge_pdf_fmt: String,
     pub create_pdf_concurrency: usize,
     pub enable_merge_pdf: bool,
+    /// 合并PDF时每多少个章节拆分为一个PDF，0表示不按章节数拆分
+    pub merge_pdf_chapter_count: usize,
+    /// 合并PDF的最大大小，单位为MB，超过则拆分，0表示不按大小拆分
+    pub merge_pdf_max_size_mb: u64,
+    /// 合并PDF时把所有分组合并为一个PDF，书签按分组嵌套，此时使用`merge_comic_pdf_fmt`
+    pub merge_pdf_all_groups: bool,
+    /// 所有分组合并为一个PDF时的路径模板，只能使用漫画字段
+    pub merge_comic_pdf_fmt: String,
+    /// 所有目录名和文件名模板中的文字字段统一进行的转换，单个字段还可以用`s2t`、`t2s`、`pinyin`过滤器单独转换
+    pub fmt_text_conversion: TextConversion,
     /// 导出跳过模式
     pub export_skip_mode: ExportSkipMode,
+    /// 导出时是否把封面作为第一页
+    pub export_with_cover: bool,
+    /// 图片处理
+    pub img_process: ImgProcessConfig,
+    /// 条漫(长条漫画)处理
+    pub webtoon: WebtoonConfig,
+    /// 导出长图的格式
+    pub long_img_format: LongImgFormat,
+    /// 同时创建长图的章节数，拼接长图很吃内存，不宜太大
+    pub create_long_img_concurrency: usize,
+    pub merge_epub_fmt: String,
+    pub enable_merge_epub: bool,
+    /// EPUB是否从右往左翻页
+    pub epub_rtl: bool,
+    pub merge_cbz_fmt: String,
+    pub enable_merge_cbz: bool,
+    /// 合并CBZ时每多少个章节拆分为一个CBZ，0表示不拆分
+    pub merge_cbz_chapter_count: usize,
+    /// CBZ的压缩方式
+    pub cbz: CbzConfig,
+    /// PDF的元数据和阅读方式
+    pub pdf: PdfConfig,
 }
 
 impl Config {
@@ -109,7 +144,25 @@ impl Config {
             merge_pdf_fmt: "{comic_title}/pdf/{group_title}".to_string(),
             create_pdf_concurrency: cpu_core_num,
             enable_merge_pdf: true,
+            merge_pdf_chapter_count: 0,
+            merge_pdf_max_size_mb: 0,
+            merge_pdf_all_groups: false,
+            merge_comic_pdf_fmt: "{comic_title}/pdf/{comic_title}".to_string(),
+            fmt_text_conversion: TextConversion::default(),
             export_skip_mode: ExportSkipMode::default(),
+            export_with_cover: true,
+            img_process: ImgProcessConfig::default(),
+            webtoon: WebtoonConfig::default(),
+            long_img_format: LongImgFormat::default(),
+            create_long_img_concurrency: cpu_core_num.min(2),
+            merge_epub_fmt: "{comic_title}/epub/{group_title}".to_string(),
+            enable_merge_epub: false,
+            epub_rtl: true,
+            merge_cbz_fmt: "{comic_title}/cbz/{group_title}".to_string(),
+            enable_merge_cbz: false,
+            merge_cbz_chapter_count: 0,
+            cbz: CbzConfig::default(),
+            pdf: PdfConfig::default(),
         }
     }
 
@@ -144,3 +197,263 @@ pub enum ExportSkipMode {
     /// 跳过曾导出过的章节（即使本地文件已删除）
     SkipExported,
 }
+
+/// 模板中文字字段的转换方式，只转换标题、作者这类文字字段，不转换ID、`path_word`和数字
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum TextConversion {
+    #[default]
+    None,
+    /// 繁体转简体，只转换内置对照表中的约990个常用字
+    Simplified,
+    /// 简体转繁体，只转换内置对照表中的约990个常用字
+    Traditional,
+    /// 转为拼音，结果只包含ASCII字符
+    Pinyin,
+}
+
+impl TextConversion {
+    pub fn convert(self, s: &str) -> String {
+        match self {
+            TextConversion::None => s.to_string(),
+            TextConversion::Simplified => zh_convert::to_simplified(s),
+            TextConversion::Traditional => zh_convert::to_traditional(s),
+            TextConversion::Pinyin => zh_convert::to_pinyin(s),
+        }
+    }
+}
+
+/// 图片处理配置，用于电子书阅读器等场景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ImgProcessConfig {
+    /// 导出时处理图片
+    pub enable_on_export: bool,
+    /// 下载时处理图片，处理后的图片直接保存到下载目录
+    pub enable_on_download: bool,
+    /// 自动裁剪四周的纯色边框
+    pub auto_crop: bool,
+    /// 自动裁剪时，亮度与边框颜色的差值不超过这个值的像素视为边框
+    pub auto_crop_tolerance: u8,
+    /// 将横向的跨页拆分为两页，按从右到左的顺序排列
+    pub split_spread: bool,
+    /// 转为灰度图
+    pub grayscale: bool,
+    /// 对比度调整，0表示不调整，正数增加对比度，负数降低对比度
+    pub contrast: f32,
+    /// gamma校正，1.0表示不调整
+    pub gamma: f32,
+}
+
+impl Default for ImgProcessConfig {
+    fn default() -> Self {
+        ImgProcessConfig {
+            enable_on_export: false,
+            enable_on_download: false,
+            auto_crop: true,
+            auto_crop_tolerance: 16,
+            split_spread: true,
+            grayscale: false,
+            contrast: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ImgProcessConfig {
+    /// 去掉`applied`中已经做过的处理，避免重复裁剪、拆分跨页，以及对比度和gamma的叠加
+    pub fn without_applied(&self, applied: &ImgProcessConfig) -> ImgProcessConfig {
+        let mut config = self.clone();
+        config.auto_crop &= !applied.auto_crop;
+        config.split_spread &= !applied.split_spread;
+        config.grayscale &= !applied.grayscale;
+        if applied.adjusts_contrast() {
+            config.contrast = 0.0;
+        }
+        if applied.adjusts_gamma() {
+            config.gamma = 1.0;
+        }
+        config
+    }
+
+    /// 是否有需要做的处理，没有时不需要解码图片
+    pub fn has_any_step(&self) -> bool {
+        self.auto_crop
+            || self.split_spread
+            || self.grayscale
+            || self.adjusts_contrast()
+            || self.adjusts_gamma()
+    }
+
+    pub fn adjusts_contrast(&self) -> bool {
+        self.contrast.abs() >= f32::EPSILON
+    }
+
+    pub fn adjusts_gamma(&self) -> bool {
+        (self.gamma - 1.0).abs() >= f32::EPSILON && self.gamma > 0.0
+    }
+}
+
+/// 条漫模式
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum WebtoonMode {
+    /// 不处理，每张图片都是单独的一页
+    Off,
+    /// 只处理`is_long`为`true`的章节
+    #[default]
+    Auto,
+    /// 处理所有章节
+    Always,
+}
+
+impl WebtoonMode {
+    pub fn should_restitch(self, is_long: bool) -> bool {
+        match self {
+            WebtoonMode::Off => false,
+            WebtoonMode::Auto => is_long,
+            WebtoonMode::Always => true,
+        }
+    }
+}
+
+/// 条漫配置
+///
+/// 条漫的图片是一条条竖长的切片，导出时会把切片拼接起来，再按`page_aspect_ratio`重新切分成页
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct WebtoonConfig {
+    pub mode: WebtoonMode,
+    /// 每页的高宽比，比如A4纸为1.414
+    pub page_aspect_ratio: f32,
+    /// 一行像素的最大亮度与最小亮度之差不超过这个值时，视为可以切分的空白行
+    pub whitespace_tolerance: u8,
+}
+
+impl Default for WebtoonConfig {
+    fn default() -> Self {
+        WebtoonConfig {
+            mode: WebtoonMode::default(),
+            page_aspect_ratio: 1.414,
+            whitespace_tolerance: 8,
+        }
+    }
+}
+
+/// CBZ配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct CbzConfig {
+    pub compression: CbzCompression,
+    /// 压缩等级，`Deflate`为0~9，`Zstd`为1~22，`Stored`时忽略
+    pub compression_level: i64,
+    /// JPEG和WebP本身已经压缩过，再压缩几乎没有收益，为`true`时这些图片始终不压缩
+    pub store_compressed_imgs: bool,
+}
+
+impl Default for CbzConfig {
+    fn default() -> Self {
+        CbzConfig {
+            compression: CbzCompression::default(),
+            compression_level: 6,
+            store_compressed_imgs: true,
+        }
+    }
+}
+
+/// CBZ中文件的压缩方式
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum CbzCompression {
+    /// 不压缩
+    #[default]
+    Stored,
+    Deflate,
+    /// 压缩率更高，但有些阅读器不支持
+    Zstd,
+}
+
+/// PDF配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PdfConfig {
+    /// 是否从右往左翻页
+    pub rtl: bool,
+    /// 是否以双页显示，封面单独占一页
+    pub two_page_layout: bool,
+    /// 是否嵌入XMP元数据，有些文献管理软件只读取XMP
+    pub embed_xmp: bool,
+    /// 页面尺寸
+    pub page_size: PdfPageSize,
+    /// 自定义页面的宽度，单位为毫米
+    pub custom_page_width_mm: f32,
+    /// 自定义页面的高度，单位为毫米
+    pub custom_page_height_mm: f32,
+    /// 页面尺寸为`Original`时，按这个DPI把图片的像素换算为页面尺寸，72表示1像素对应1点
+    pub dpi: u32,
+    /// 页边距，单位为毫米
+    pub margin_mm: f32,
+    /// 是否把图片重新压缩为JPEG
+    pub recompress_img: bool,
+    /// 重新压缩时的JPEG质量，1~100
+    pub jpeg_quality: u8,
+    /// 重新压缩时图片的最大宽度，超过则等比缩小，0表示不限制
+    pub max_img_width: u32,
+    /// 合并PDF时是否生成封面页，包含漫画封面、标题和章节范围
+    pub merged_cover_page: bool,
+    /// 合并PDF时是否生成目录页，点击章节标题可以跳转
+    pub merged_toc_page: bool,
+    /// 合并PDF时是否设置页码标签，阅读器会把页码显示为`Ch.12 p.3`
+    pub merged_page_labels: bool,
+}
+
+impl Default for PdfConfig {
+    fn default() -> Self {
+        PdfConfig {
+            rtl: true,
+            two_page_layout: false,
+            embed_xmp: false,
+            page_size: PdfPageSize::default(),
+            custom_page_width_mm: 210.0,
+            custom_page_height_mm: 297.0,
+            dpi: 72,
+            margin_mm: 0.0,
+            recompress_img: false,
+            jpeg_quality: 85,
+            max_img_width: 0,
+            merged_cover_page: false,
+            merged_toc_page: false,
+            merged_page_labels: true,
+        }
+    }
+}
+
+/// PDF的页面尺寸
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum PdfPageSize {
+    /// 按图片尺寸和DPI决定页面尺寸
+    #[default]
+    Original,
+    A4,
+    A5,
+    B5,
+    Custom,
+}
+
+impl PdfPageSize {
+    /// 页面的宽高，单位为毫米，`Original`返回`None`
+    pub fn size_mm(self, config: &PdfConfig) -> Option<(f32, f32)> {
+        match self {
+            PdfPageSize::Original => None,
+            PdfPageSize::A4 => Some((210.0, 297.0)),
+            PdfPageSize::A5 => Some((148.0, 210.0)),
+            PdfPageSize::B5 => Some((176.0, 250.0)),
+            PdfPageSize::Custom => {
+                Some((config.custom_page_width_mm, config.custom_page_height_mm))
+            }
+        }
+    }
+}
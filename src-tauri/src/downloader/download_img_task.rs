@@ -6,7 +6,7 @@ use std::{
 };
 
 use bytes::Bytes;
-use eyre::{eyre, WrapErr};
+use eyre::{eyre, OptionExt, WrapErr};
 use image::ImageFormat;
 use tauri::AppHandle;
 use tokio::{
@@ -16,8 +16,10 @@ use tokio::{
 use tracing::instrument;
 
 use crate::{
+    config::ImgProcessConfig,
     downloader::{download_task::DownloadTask, download_task_state::DownloadTaskState},
     extensions::{AppHandleExt, EyreReportToMessage},
+    img_process,
 };
 
 pub struct DownloadImgTask {
@@ -93,6 +95,7 @@ impl DownloadImgTask {
         let url = &self.url;
 
         let download_format = self.app.get_config().read().download_format;
+        let img_process_config = self.download_task.img_process_config.clone();
         let extension = download_format.extension();
         let save_path = self
             .temp_download_dir
@@ -127,7 +130,15 @@ impl DownloadImgTask {
 
         // 保存图片
         let target_format = download_format.to_image_format();
-        if let Err(err) = save_img(&save_path, target_format, img_data, img_format).await {
+        if let Err(err) = save_img(
+            &save_path,
+            target_format,
+            img_data,
+            img_format,
+            img_process_config,
+        )
+        .await
+        {
             let err_title = "保存图片失败";
             let message = err.to_message();
             tracing::error!(err_title, message);
@@ -222,8 +233,9 @@ async fn save_img(
     target_format: ImageFormat,
     src_img_data: Bytes,
     src_format: ImageFormat,
+    img_process_config: Option<ImgProcessConfig>,
 ) -> eyre::Result<()> {
-    if target_format == src_format {
+    if target_format == src_format && img_process_config.is_none() {
         // 如果target_format与src_format匹配，则直接保存
         std::fs::write(save_path, &src_img_data)?;
         tracing::trace!("图片成功保存到磁盘");
@@ -235,24 +247,51 @@ async fn save_img(
     // 图像处理的闭包
     let process_img = move || -> eyre::Result<()> {
         let _enter = current_span.enter();
-        // 如果target_format与src_format不匹配，则需要转换格式
-        let img = image::load_from_memory(&src_img_data)?;
-
-        let mut converted_data = Vec::new();
-
-        match target_format {
-            ImageFormat::WebP => img
-                .to_rgba8()
-                .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::WebP),
-            ImageFormat::Jpeg => img
-                .to_rgb8()
-                .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::Jpeg),
-            _ => return Err(eyre!("不支持的图片格式: {:?}", target_format)),
-        }
-        .wrap_err("转换图片格式失败")?;
+        let Some(img_process_config) = img_process_config else {
+            // 如果target_format与src_format不匹配，则需要转换格式
+            let img = image::load_from_memory(&src_img_data)?;
+
+            let mut converted_data = Vec::new();
+
+            match target_format {
+                ImageFormat::WebP => img
+                    .to_rgba8()
+                    .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::WebP),
+                ImageFormat::Jpeg => img
+                    .to_rgb8()
+                    .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::Jpeg),
+                _ => return Err(eyre!("不支持的图片格式: {:?}", target_format)),
+            }
+            .wrap_err("转换图片格式失败")?;
 
-        std::fs::write(&save_path, &converted_data)?;
-        tracing::trace!("图片成功保存到磁盘");
+            std::fs::write(&save_path, &converted_data)?;
+            tracing::trace!("图片成功保存到磁盘");
+
+            return Ok(());
+        };
+
+        // 处理图片，处理的同时完成格式转换
+        let processed_data =
+            img_process::process_img_data(&src_img_data, target_format, &img_process_config)
+                .wrap_err("处理图片失败")?;
+        // 跨页被拆分时，第一页保存到`save_path`，后面的页依次保存为`{stem}_2.{ext}`、`{stem}_3.{ext}`...
+        for (i, data) in processed_data.iter().enumerate() {
+            let path = if i == 0 {
+                save_path.clone()
+            } else {
+                let stem = save_path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_eyre(format!("获取`{}`的文件名失败", save_path.display()))?;
+                let ext = save_path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .ok_or_eyre(format!("获取`{}`的扩展名失败", save_path.display()))?;
+                save_path.with_file_name(format!("{stem}_{}.{ext}", i + 1))
+            };
+            std::fs::write(&path, data).wrap_err(format!("保存`{}`失败", path.display()))?;
+        }
+        tracing::trace!("图片成功处理并保存到磁盘");
 
         Ok(())
     };
@@ -1,7 +1,7 @@
 use std::{
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
     time::Duration,
@@ -18,6 +18,7 @@ use tokio::{
 use tracing::instrument;
 
 use crate::{
+    config::ImgProcessConfig,
     downloader::{download_img_task::DownloadImgTask, download_task_state::DownloadTaskState},
     errors::RiskControlError,
     events::DownloadEvent,
@@ -34,6 +35,10 @@ pub struct DownloadTask {
     pub delete_sender: watch::Sender<()>,
     pub downloaded_img_count: Arc<AtomicU32>,
     pub total_img_count: Arc<AtomicU32>,
+    /// 是否为条漫，获取章节信息后才能知道
+    pub is_long: Arc<AtomicBool>,
+    /// 下载时处理图片的配置，创建任务时确定，保证同一章节的所有图片按同样的配置处理
+    pub img_process_config: Option<ImgProcessConfig>,
 }
 
 impl DownloadTask {
@@ -58,6 +63,16 @@ impl DownloadTask {
             .cloned()
             .ok_or_eyre("未找到章节ID对应的章节信息")?;
 
+        let is_long = chapter_info.is_long;
+        // 只有启用了下载时处理，才需要处理图片
+        let img_process_config = {
+            let config = app.get_config();
+            let config = config.read();
+            config
+                .img_process
+                .enable_on_download
+                .then(|| config.img_process.clone())
+        };
         let (state_sender, _) = watch::channel(DownloadTaskState::Pending);
         let (delete_sender, _) = watch::channel(());
 
@@ -69,6 +84,8 @@ impl DownloadTask {
             delete_sender,
             downloaded_img_count: Arc::new(AtomicU32::new(0)),
             total_img_count: Arc::new(AtomicU32::new(0)),
+            is_long: Arc::new(AtomicBool::new(is_long)),
+            img_process_config,
         });
 
         tauri::async_runtime::spawn(task.clone().process());
@@ -144,6 +161,22 @@ impl DownloadTask {
 
             return;
         }
+        if let Some(comic_download_dir) = &self.comic.comic_download_dir {
+            let library_index = self.app.get_library_index();
+            if let Err(err) = library_index.update_comic_metadata(comic_download_dir) {
+                let err_title = "更新已下载漫画索引失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
+        // 第一次下载这个漫画时保存封面，封面下载失败不影响章节下载
+        if self.comic.get_cover_path().is_none() {
+            if let Err(err) = self.comic.save_cover(&self.app).await {
+                let err_title = "保存封面失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
         // 获取章节图片URL列表
         let Some(url_and_index_pairs) = self.get_url_and_index_pairs().await else {
             return;
@@ -198,11 +231,24 @@ impl DownloadTask {
             return;
         }
 
-        if let Err(err) = self.chapter_info.save_metadata() {
+        let mut chapter_info = self.chapter_info.as_ref().clone();
+        chapter_info.is_long = self.is_long.load(Ordering::Relaxed);
+        chapter_info
+            .download_img_process
+            .clone_from(&self.img_process_config);
+        if let Err(err) = chapter_info.save_metadata() {
             let err_title = "保存章节元数据失败";
             let message = err.to_message();
             tracing::error!(err_title, message);
         }
+        if let Some(chapter_download_dir) = &chapter_info.chapter_download_dir {
+            let library_index = self.app.get_library_index();
+            if let Err(err) = library_index.update_chapter_metadata(chapter_download_dir) {
+                let err_title = "更新已下载漫画索引失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
 
         tracing::info!("章节下载成功");
         self.sleep_between_chapter().await;
@@ -227,6 +273,9 @@ impl DownloadTask {
             }
         };
 
+        self.is_long
+            .store(chapter_resp_data.chapter.is_long, Ordering::Relaxed);
+
         let urls: Vec<String> = chapter_resp_data
             .chapter
             .contents
@@ -54,6 +54,66 @@ pub enum ExportCbzEvent {
     #[serde(rename_all = "camelCase")]
     Start {
         uuid: String,
+        comic_path_word: String,
+        comic_title: String,
+        group_title: String,
+        total: u32,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Progress { uuid: String, current: u32 },
+
+    #[serde(rename_all = "camelCase")]
+    ValidationError {
+        uuid: String,
+        chapter_uuid: String,
+        chapter_title: String,
+        err_msg: String,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Error { uuid: String },
+
+    #[serde(rename_all = "camelCase")]
+    End {
+        uuid: String,
+        comic_path_word: String,
+        export_dir: PathBuf,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MergeStart {
+        uuid: String,
+        comic_path_word: String,
+        comic_title: String,
+        group_title: String,
+        total: u32,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MergeProgress { uuid: String, current: u32 },
+
+    #[serde(rename_all = "camelCase")]
+    MergeError { uuid: String },
+
+    #[serde(rename_all = "camelCase")]
+    MergeEnd {
+        uuid: String,
+        comic_path_word: String,
+        export_dir: PathBuf,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Cancelled { uuid: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(tag = "event", content = "data")]
+pub enum ExportLongImgEvent {
+    #[serde(rename_all = "camelCase")]
+    Start {
+        uuid: String,
+        comic_path_word: String,
         comic_title: String,
         group_title: String,
         total: u32,
@@ -71,6 +131,9 @@ pub enum ExportCbzEvent {
         comic_path_word: String,
         export_dir: PathBuf,
     },
+
+    #[serde(rename_all = "camelCase")]
+    Cancelled { uuid: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
@@ -79,6 +142,58 @@ pub enum ExportPdfEvent {
     #[serde(rename_all = "camelCase")]
     CreateStart {
         uuid: String,
+        comic_path_word: String,
+        comic_title: String,
+        group_title: String,
+        total: u32,
+    },
+    #[serde(rename_all = "camelCase")]
+    CreateProgress { uuid: String, current: u32 },
+    #[serde(rename_all = "camelCase")]
+    CreateValidationError {
+        uuid: String,
+        chapter_uuid: String,
+        chapter_title: String,
+        err_msg: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    CreateError { uuid: String },
+    #[serde(rename_all = "camelCase")]
+    CreateEnd {
+        uuid: String,
+        comic_path_word: String,
+        export_dir: PathBuf,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MergeStart {
+        uuid: String,
+        comic_path_word: String,
+        comic_title: String,
+        group_title: String,
+        total: u32,
+    },
+    #[serde(rename_all = "camelCase")]
+    MergeProgress { uuid: String, current: u32 },
+    #[serde(rename_all = "camelCase")]
+    MergeError { uuid: String },
+    #[serde(rename_all = "camelCase")]
+    MergeEnd {
+        uuid: String,
+        comic_path_word: String,
+        export_dir: PathBuf,
+    },
+    #[serde(rename_all = "camelCase")]
+    Cancelled { uuid: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(tag = "event", content = "data")]
+pub enum ExportEpubEvent {
+    #[serde(rename_all = "camelCase")]
+    CreateStart {
+        uuid: String,
+        comic_path_word: String,
         comic_title: String,
         group_title: String,
         total: u32,
@@ -97,6 +212,7 @@ pub enum ExportPdfEvent {
     #[serde(rename_all = "camelCase")]
     MergeStart {
         uuid: String,
+        comic_path_word: String,
         comic_title: String,
         group_title: String,
         total: u32,
@@ -109,6 +225,8 @@ pub enum ExportPdfEvent {
         comic_path_word: String,
         export_dir: PathBuf,
     },
+    #[serde(rename_all = "camelCase")]
+    Cancelled { uuid: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
@@ -138,3 +256,10 @@ pub enum UpdateDownloadedComicsEvent {
 pub struct LogEvent {
     pub json_raw: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryChangedEvent {
+    /// 下载目录里有变化的漫画的`path_word`
+    pub comic_path_words: Vec<String>,
+}
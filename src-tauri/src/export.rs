@@ -1,72 +1,203 @@
 mod cbz;
+mod epub;
+mod fmt_preview;
+mod long_img;
 mod pdf;
+mod pdf_front_matter;
+mod pdf_manifest;
+mod pdf_metadata;
+mod pdf_writer;
+mod webtoon;
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
+    io::Write,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-pub use cbz::{cbz, cbz_chapters};
+pub use cbz::{cbz, cbz_chapters, update_cbz_comic_info};
+pub use epub::{epub, epub_chapters};
 use eyre::{eyre, OptionExt, WrapErr};
-use parking_lot::Mutex;
-pub use pdf::{pdf, pdf_chapters};
+pub use fmt_preview::preview_fmt;
+pub use long_img::{long_img, long_img_chapters};
+use parking_lot::{Condvar, Mutex};
+pub use pdf::{estimate_pdf_size, pdf, pdf_chapters};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 use serde::Serialize;
 use tauri::AppHandle;
 use tracing::instrument;
 
 use crate::{
+    config::{ImgProcessConfig, TextConversion, WebtoonConfig},
     extensions::{AppHandleExt, PathIsImg},
-    types::{ChapterInfo, Comic},
+    fmt_template, img_process,
+    types::{
+        disambiguate_chapter_paths, ChapterDirFmtParams, ChapterInfo, Comic, ComicDirFmtParams,
+        LongImgFormat,
+    },
     utils,
 };
 
-/// 导出互斥锁管理器，确保同一漫画的导出操作串行执行
+/// 导出任务队列，同一漫画的导出任务按提交顺序串行执行，不同漫画的导出任务互不影响
 #[derive(Debug, Clone, Default)]
-pub struct ComicExportLock {
-    /// 正在导出的漫画`path_word`集合
-    locked_comic_path_words: Arc<Mutex<HashSet<String>>>,
+pub struct ExportQueue {
+    /// 漫画`path_word` -> 该漫画正在执行和排队中的任务，队首为正在执行的任务
+    comic_jobs: Arc<Mutex<HashMap<String, VecDeque<ExportCancelToken>>>>,
+    /// 有任务结束或被取消时通知排队中的任务
+    job_changed: Arc<Condvar>,
 }
 
-impl ComicExportLock {
+impl ExportQueue {
     pub fn new() -> Self {
-        Self {
-            locked_comic_path_words: Arc::new(Mutex::new(HashSet::new())),
+        Self::default()
+    }
+
+    /// 把导出任务加入`comic_path_word`的队列，等前面的任务都结束后执行`export`
+    ///
+    /// 任务在排队时或执行中被取消都返回`Ok`
+    pub fn run<T: Default>(
+        &self,
+        comic_path_word: &str,
+        export: impl FnOnce(&ExportCancelToken) -> eyre::Result<T>,
+    ) -> eyre::Result<T> {
+        let cancel_token = ExportCancelToken::default();
+        self.comic_jobs
+            .lock()
+            .entry(comic_path_word.to_string())
+            .or_default()
+            .push_back(cancel_token.clone());
+        // 无论是否执行，结束时都要把任务移出队列
+        let _guard = ExportJobGuard {
+            queue: self.clone(),
+            comic_path_word: comic_path_word.to_string(),
+            cancel_token: cancel_token.clone(),
+        };
+
+        if !self.wait_turn(comic_path_word, &cancel_token) {
+            return Ok(T::default());
+        }
+
+        match export(&cancel_token) {
+            Err(err) if is_cancelled_err(&err) => Ok(T::default()),
+            result => result,
         }
     }
 
-    /// 尝试获取漫画导出锁，返回是否成功（如果该漫画正在导出则返回 false）
-    pub fn try_acquire(&self, comic_path_word: &str) -> bool {
-        let mut locked = self.locked_comic_path_words.lock();
-        if locked.contains(comic_path_word) {
-            return false;
+    /// 取消`comic_path_word`正在执行和排队中的所有任务，返回取消的任务数
+    pub fn cancel(&self, comic_path_word: &str) -> usize {
+        let comic_jobs = self.comic_jobs.lock();
+        let Some(jobs) = comic_jobs.get(comic_path_word) else {
+            return 0;
+        };
+
+        for cancel_token in jobs {
+            cancel_token.cancel();
         }
-        locked.insert(comic_path_word.to_string());
-        true
+        self.job_changed.notify_all();
+
+        jobs.len()
     }
 
-    /// 释放漫画导出锁
-    pub fn release(&self, comic_path_word: &str) {
-        self.locked_comic_path_words.lock().remove(comic_path_word);
+    /// 阻塞直到`cancel_token`对应的任务排到队首，返回`false`表示排队时被取消
+    fn wait_turn(&self, comic_path_word: &str, cancel_token: &ExportCancelToken) -> bool {
+        let mut comic_jobs = self.comic_jobs.lock();
+        loop {
+            if cancel_token.is_cancelled() {
+                return false;
+            }
+
+            let is_first = comic_jobs
+                .get(comic_path_word)
+                .and_then(VecDeque::front)
+                .is_some_and(|first| first.is_same_job(cancel_token));
+            if is_first {
+                return true;
+            }
+
+            self.job_changed.wait(&mut comic_jobs);
+        }
+    }
+
+    fn remove(&self, comic_path_word: &str, cancel_token: &ExportCancelToken) {
+        let mut comic_jobs = self.comic_jobs.lock();
+        if let Some(jobs) = comic_jobs.get_mut(comic_path_word) {
+            jobs.retain(|job| !job.is_same_job(cancel_token));
+            if jobs.is_empty() {
+                comic_jobs.remove(comic_path_word);
+            }
+        }
+        self.job_changed.notify_all();
     }
 }
 
-struct ComicExportLockGuard {
-    lock: ComicExportLock,
-    path_word: String,
+struct ExportJobGuard {
+    queue: ExportQueue,
+    comic_path_word: String,
+    cancel_token: ExportCancelToken,
 }
 
-impl Drop for ComicExportLockGuard {
+impl Drop for ExportJobGuard {
     fn drop(&mut self) {
-        self.lock.release(&self.path_word);
+        self.queue.remove(&self.comic_path_word, &self.cancel_token);
     }
 }
 
+/// 导出任务的取消标记，导出过程中的循环每处理一项前都要检查
+#[derive(Debug, Clone, Default)]
+pub struct ExportCancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ExportCancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// 已取消时返回`ExportCancelled`错误，用`?`提前结束导出
+    pub fn check(&self) -> eyre::Result<()> {
+        if self.is_cancelled() {
+            return Err(ExportCancelled.into());
+        }
+        Ok(())
+    }
+
+    fn is_same_job(&self, other: &ExportCancelToken) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+/// 导出被取消
+#[derive(Debug)]
+pub struct ExportCancelled;
+
+impl std::fmt::Display for ExportCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "导出已取消")
+    }
+}
+
+impl std::error::Error for ExportCancelled {}
+
+/// `err`是否由取消导出引起，`ExportCancelled`可能被`wrap_err`包装过
+fn is_cancelled_err(err: &eyre::Report) -> bool {
+    err.chain().any(|cause| cause.is::<ExportCancelled>())
+}
+
 /// 导出格式
 #[derive(Debug, Copy, Clone)]
 enum ExportFormat {
     Pdf,
     Cbz,
+    Epub,
+    LongImg(LongImgFormat),
 }
 
 impl ExportFormat {
@@ -74,6 +205,8 @@ impl ExportFormat {
         match self {
             ExportFormat::Pdf => "pdf",
             ExportFormat::Cbz => "cbz",
+            ExportFormat::Epub => "epub",
+            ExportFormat::LongImg(format) => format.extension(),
         }
     }
 }
@@ -116,34 +249,216 @@ fn get_image_paths(images_dir: &Path) -> eyre::Result<Vec<PathBuf>> {
     Ok(image_paths)
 }
 
+/// 准备写入导出文件的图片
+enum ExportImg {
+    /// 未经处理的图片，导出时直接读取文件
+    File { filename: String, path: PathBuf },
+    /// 经过处理后的图片，已在内存中
+    Memory { filename: String, data: Vec<u8> },
+}
+
+impl ExportImg {
+    fn filename(&self) -> &str {
+        match self {
+            ExportImg::File { filename, .. } | ExportImg::Memory { filename, .. } => filename,
+        }
+    }
+
+    /// 给文件名加上前缀，合并多个章节时用来避免文件名冲突
+    fn with_filename_prefix(self, prefix: &str) -> Self {
+        match self {
+            ExportImg::File { filename, path } => ExportImg::File {
+                filename: format!("{prefix}{filename}"),
+                path,
+            },
+            ExportImg::Memory { filename, data } => ExportImg::Memory {
+                filename: format!("{prefix}{filename}"),
+                data,
+            },
+        }
+    }
+
+    /// 读取图片数据
+    fn into_data(self) -> eyre::Result<Vec<u8>> {
+        match self {
+            ExportImg::File { path, .. } => {
+                std::fs::read(&path).wrap_err(format!("读取`{}`失败", path.display()))
+            }
+            ExportImg::Memory { data, .. } => Ok(data),
+        }
+    }
+
+    /// 图片数据的大小，单位为字节
+    fn data_len(&self) -> eyre::Result<u64> {
+        match self {
+            ExportImg::File { path, .. } => {
+                let metadata = std::fs::metadata(path)
+                    .wrap_err(format!("获取`{}`的大小失败", path.display()))?;
+                Ok(metadata.len())
+            }
+            ExportImg::Memory { data, .. } => Ok(data.len() as u64),
+        }
+    }
+
+    /// 获取图片的宽高，只读取图片头，不解码整张图片
+    fn dimensions(&self) -> eyre::Result<(u32, u32)> {
+        match self {
+            ExportImg::File { path, .. } => image::image_dimensions(path)
+                .wrap_err(format!("获取`{}`的尺寸失败", path.display())),
+            ExportImg::Memory { filename, data } => {
+                utils::get_dimensions(data).wrap_err(format!("获取`{filename}`的尺寸失败"))
+            }
+        }
+    }
+
+    /// 将图片数据写入`writer`
+    fn write_to<W: Write>(&self, writer: &mut W) -> eyre::Result<()> {
+        match self {
+            ExportImg::File { path, .. } => {
+                let mut file =
+                    std::fs::File::open(path).wrap_err(format!("打开`{}`失败", path.display()))?;
+                std::io::copy(&mut file, writer)
+                    .wrap_err(format!("写入`{}`失败", path.display()))?;
+            }
+            ExportImg::Memory { filename, data } => {
+                writer
+                    .write_all(data)
+                    .wrap_err(format!("写入`{filename}`失败"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 获取章节要导出的图片
+///
+/// - 如果`cover_path`不为`None`，封面会作为第一页，命名为`000`，排在`001`之前
+/// - 如果`webtoon_config`不为`None`，会把章节图片拼接后重新切分成页，封面不参与拼接
+/// - 如果启用了导出时处理图片，会在rayon线程池中并发处理所有图片，
+///   被拆分的跨页会命名为`001.webp`、`001_2.webp`，保证按文件名排序后顺序不变
+/// - `download_img_process`为章节图片下载时已经做过的处理，章节图片不会重复这些处理，封面仍按完整的配置处理
+#[instrument(level = "error", skip_all)]
+fn get_export_imgs(
+    cover_path: Option<&Path>,
+    image_paths: Vec<PathBuf>,
+    img_process_config: &ImgProcessConfig,
+    download_img_process: Option<&ImgProcessConfig>,
+    webtoon_config: Option<&WebtoonConfig>,
+) -> eyre::Result<Vec<ExportImg>> {
+    let mut imgs = Vec::with_capacity(image_paths.len() + 1);
+
+    if let Some(cover_path) = cover_path {
+        let ext = cover_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_eyre(format!("获取`{}`的扩展名失败", cover_path.display()))?;
+        imgs.push(ExportImg::File {
+            filename: format!("000.{ext}"),
+            path: cover_path.to_path_buf(),
+        });
+    }
+
+    let mut chapter_imgs = Vec::with_capacity(image_paths.len());
+    for path in image_paths {
+        let filename = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_eyre(format!("获取`{}`的文件名失败", path.display()))?
+            .to_string();
+        chapter_imgs.push(ExportImg::File { filename, path });
+    }
+
+    match webtoon_config {
+        Some(webtoon_config) => imgs.extend(restitch_webtoon_imgs(chapter_imgs, webtoon_config)?),
+        None => imgs.extend(chapter_imgs),
+    }
+
+    if !img_process_config.enable_on_export {
+        return Ok(imgs);
+    }
+
+    let chapter_img_process_config = match download_img_process {
+        Some(applied) => img_process_config.without_applied(applied),
+        None => img_process_config.clone(),
+    };
+    let has_cover = cover_path.is_some();
+
+    let current_span = tracing::Span::current();
+    let processed_imgs: Vec<Vec<ExportImg>> = imgs
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, img)| -> eyre::Result<Vec<ExportImg>> {
+            let _enter = current_span.enter();
+
+            let img_process_config = if has_cover && i == 0 {
+                img_process_config
+            } else {
+                &chapter_img_process_config
+            };
+            if !img_process_config.has_any_step() {
+                return Ok(vec![img]);
+            }
+
+            let filename = img.filename().to_string();
+            let (stem, ext) = filename
+                .rsplit_once('.')
+                .ok_or_eyre(format!("`{filename}`没有扩展名"))?;
+            let format = img_process::format_from_extension(ext)
+                .ok_or_eyre(format!("`{filename}`的格式不支持处理"))?;
+
+            let img_data = img.into_data()?;
+            let processed_data =
+                img_process::process_img_data(&img_data, format, img_process_config)
+                    .wrap_err(format!("处理`{filename}`失败"))?;
+
+            let processed_imgs = processed_data
+                .into_iter()
+                .enumerate()
+                .map(|(i, data)| {
+                    let filename = if i == 0 {
+                        format!("{stem}.{ext}")
+                    } else {
+                        format!("{stem}_{}.{ext}", i + 1)
+                    };
+                    ExportImg::Memory { filename, data }
+                })
+                .collect();
+
+            Ok(processed_imgs)
+        })
+        .collect::<eyre::Result<_>>()?;
+
+    Ok(processed_imgs.into_iter().flatten().collect())
+}
+
 #[derive(Debug, Clone)]
 struct ExportTarget {
     chapter_info: ChapterInfo,
     export_path: PathBuf,
 }
 
-const EXPORT_FMT_COMMON_FIELDS: [&str; 5] = [
+const EXPORT_FMT_COMMON_FIELDS: [&str; 10] = [
     "comic_uuid",
     "comic_path_word",
     "comic_title",
+    "alias",
     "author",
+    "publish_year",
+    "region",
+    "status",
+    "theme",
     "export_format",
 ];
-const EXPORT_FMT_GROUP_FIELDS: [&str; 2] = ["group_path_word", "group_title"];
-const EXPORT_FMT_CHAPTER_FIELDS: [&str; 3] = ["chapter_uuid", "chapter_title", "order"];
+/// 合并模板中至少要有一个这样的字段，否则不同分组的合并文件会重名
+const MERGE_FMT_GROUP_ID_FIELDS: [&str; 2] = ["group_path_word", "group_title"];
+/// 合并文件被拆分时的部分序号，所有合并模板都能使用
+const MERGE_FMT_PART_FIELDS: [&str; 1] = ["part"];
 
 #[derive(Debug, Clone, Serialize)]
 struct ChapterExportFmtParams {
-    comic_uuid: String,
-    comic_path_word: String,
-    comic_title: String,
-    author: String,
+    #[serde(flatten)]
+    chapter: ChapterDirFmtParams,
     export_format: String,
-    group_path_word: String,
-    group_title: String,
-    chapter_uuid: String,
-    chapter_title: String,
-    order: f64,
 }
 
 impl ChapterExportFmtParams {
@@ -151,14 +466,14 @@ impl ChapterExportFmtParams {
         level = "error",
         skip_all,
         fields(
-            comic_uuid = self.comic_uuid,
-            comic_path_word = self.comic_path_word,
-            comic_title = self.comic_title,
-            group_path_word = self.group_path_word,
-            group_title = self.group_title,
-            chapter_uuid = self.chapter_uuid,
-            chapter_title = self.chapter_title,
-            order = self.order,
+            comic_uuid = self.chapter.comic.comic_uuid,
+            comic_path_word = self.chapter.comic.comic_path_word,
+            comic_title = self.chapter.comic.comic_title,
+            group_path_word = self.chapter.group_path_word,
+            group_title = self.chapter.group_title,
+            chapter_uuid = self.chapter.chapter_uuid,
+            chapter_title = self.chapter.chapter_title,
+            order = self.chapter.order,
             export_format = self.export_format
         )
     )]
@@ -168,39 +483,10 @@ impl ChapterExportFmtParams {
         export_dir_fmt: &str,
         format: ExportFormat,
     ) -> eyre::Result<PathBuf> {
-        use strfmt::strfmt;
-
-        let json_value = serde_json::to_value(self)
-            .wrap_err("将ChapterExportFmtParams转为serde_json::Value失败")?;
-
-        let json_map = json_value
-            .as_object()
-            .ok_or_eyre("ChapterExportFmtParams不是JSON对象")?;
-
-        let vars: HashMap<String, String> = json_map
-            .iter()
-            .map(|(key, value)| {
-                let value = match value {
-                    serde_json::Value::String(s) => s.clone(),
-                    _ => value.to_string(),
-                };
-                (key.clone(), value)
-            })
-            .collect();
-
-        let mut export_dir_fmt = export_dir_fmt.to_string();
-        utils::preprocess_order_placeholder(&mut export_dir_fmt, &vars)?;
-
-        let dir_fmt_parts: Vec<&str> = export_dir_fmt.split('/').collect();
-
-        let mut dir_names = Vec::new();
-        for fmt_part in dir_fmt_parts {
-            let dir_name = strfmt(fmt_part, &vars).wrap_err("格式化导出目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
-            if !dir_name.is_empty() {
-                dir_names.push(dir_name);
-            }
-        }
+        let vars = fmt_template::to_vars(self)?;
+
+        let mut dir_names =
+            fmt_template::render_path(export_dir_fmt, &vars).wrap_err("格式化导出目录名失败")?;
 
         let Some(filename) = dir_names.pop() else {
             let err_msg =
@@ -214,11 +500,33 @@ impl ChapterExportFmtParams {
         }
 
         let ext = format.extension();
+        let filename =
+            utils::truncate_filename(&filename, MAX_EXPORT_FILENAME_BYTES - ext.len() - 1);
 
         Ok(export_path.join(format!("{filename}.{ext}")))
     }
 }
 
+/// 导出文件名的最大字节数，要给`get_temp_export_path`加的`.tmp`留出位置
+const MAX_EXPORT_FILENAME_BYTES: usize = utils::MAX_FILENAME_BYTES - ".tmp".len();
+
+/// 导出文件先写到这个临时文件，校验通过后再用`persist_temp_export_file`重命名为`export_path`
+///
+/// 直接写到`export_path`的话，中途崩溃会留下不完整的文件，`SkipExisting`会把它当成已经导出过
+fn get_temp_export_path(export_path: &Path) -> PathBuf {
+    let mut temp_path = export_path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+fn persist_temp_export_file(temp_path: &Path, export_path: &Path) -> eyre::Result<()> {
+    std::fs::rename(temp_path, export_path).wrap_err(format!(
+        "将`{}`重命名为`{}`失败",
+        temp_path.display(),
+        export_path.display()
+    ))
+}
+
 #[instrument(
     level = "error",
     skip_all,
@@ -230,39 +538,38 @@ pub fn build_grouped_export_targets(
     chapter_infos: Vec<ChapterInfo>,
     export_format: ExportFormat,
 ) -> eyre::Result<HashMap<String, Vec<ExportTarget>>> {
-    let (export_dir, export_dir_fmt) = {
+    let (export_dir, export_dir_fmt, text_conversion) = {
         let config = app.get_config();
         let config = config.read();
-        (config.export_dir.clone(), config.export_dir_fmt.clone())
+        (
+            config.export_dir.clone(),
+            config.export_dir_fmt.clone(),
+            config.fmt_text_conversion,
+        )
     };
 
     validate_export_dir_fmt(&export_dir_fmt)?;
 
-    let author = comic
-        .comic
-        .author
-        .iter()
-        .map(|author| author.name.clone())
-        .collect::<Vec<_>>()
-        .join(", ");
+    let mut export_paths: HashMap<String, PathBuf> = get_chapter_export_paths(
+        comic,
+        &export_dir,
+        &export_dir_fmt,
+        export_format,
+        text_conversion,
+    )?
+    .into_iter()
+    .map(|(chapter_info, export_path)| (chapter_info.chapter_uuid.clone(), export_path))
+    .collect();
 
     let mut grouped_export_targets: HashMap<String, Vec<ExportTarget>> = HashMap::new();
 
     for chapter_info in chapter_infos {
-        let fmt_params = ChapterExportFmtParams {
-            comic_uuid: comic.comic.uuid.clone(),
-            comic_path_word: comic.comic.path_word.clone(),
-            comic_title: comic.comic.name.clone(),
-            author: author.clone(),
-            export_format: export_format.extension().to_string(),
-            group_path_word: chapter_info.group_path_word.clone(),
-            group_title: chapter_info.group_name.clone(),
-            chapter_uuid: chapter_info.chapter_uuid.clone(),
-            chapter_title: chapter_info.chapter_title.clone(),
-            order: chapter_info.order,
-        };
-
-        let export_path = fmt_params.to_export_path(&export_dir, &export_dir_fmt, export_format)?;
+        let export_path = export_paths
+            .remove(&chapter_info.chapter_uuid)
+            .ok_or_eyre(format!(
+                "章节`{}`不在漫画`{}`的章节列表中",
+                chapter_info.chapter_title, comic.comic.name
+            ))?;
 
         grouped_export_targets
             .entry(chapter_info.group_path_word.clone())
@@ -276,6 +583,64 @@ pub fn build_grouped_export_targets(
     Ok(grouped_export_targets)
 }
 
+/// 用`export_dir_fmt`生成漫画所有章节的导出路径，路径冲突的章节会用`disambiguate_chapter_paths`区分
+///
+/// 必须用所有章节来检查冲突，而不只是这次要导出的章节，否则分几次导出同名的章节时它们会得到同一个路径
+fn get_chapter_export_paths<'a>(
+    comic: &'a Comic,
+    export_dir: &Path,
+    export_dir_fmt: &str,
+    format: ExportFormat,
+    text_conversion: TextConversion,
+) -> eyre::Result<Vec<(&'a ChapterInfo, PathBuf)>> {
+    let (chapter_infos, mut export_paths): (Vec<&ChapterInfo>, Vec<PathBuf>) =
+        get_raw_chapter_export_paths(comic, export_dir, export_dir_fmt, format, text_conversion)?
+            .into_iter()
+            .unzip();
+
+    disambiguate_chapter_paths(
+        &chapter_infos,
+        &mut export_paths,
+        format.extension(),
+        MAX_EXPORT_FILENAME_BYTES,
+    );
+
+    Ok(chapter_infos.into_iter().zip(export_paths).collect())
+}
+
+/// 与`get_chapter_export_paths`相同，但不区分冲突的路径
+fn get_raw_chapter_export_paths<'a>(
+    comic: &'a Comic,
+    export_dir: &Path,
+    export_dir_fmt: &str,
+    format: ExportFormat,
+    text_conversion: TextConversion,
+) -> eyre::Result<Vec<(&'a ChapterInfo, PathBuf)>> {
+    let comic_fmt_params = ComicDirFmtParams::new(&comic.comic, text_conversion);
+    let chapter_indexes = comic.comic.get_chapter_indexes();
+
+    let mut export_paths = Vec::new();
+    for chapter_info in comic.comic.groups.values().flatten() {
+        let chapter_index = chapter_indexes
+            .get(&chapter_info.chapter_uuid)
+            .cloned()
+            .unwrap_or_default();
+        let fmt_params = ChapterExportFmtParams {
+            chapter: ChapterDirFmtParams::new(
+                comic_fmt_params.clone(),
+                chapter_info,
+                chapter_index,
+                text_conversion,
+            ),
+            export_format: format.extension().to_string(),
+        };
+        let export_path = fmt_params.to_export_path(export_dir, export_dir_fmt, format)?;
+        export_paths.push((chapter_info, export_path));
+    }
+
+    Ok(export_paths)
+}
+
 fn validate_export_dir_fmt(export_dir_fmt: &str) -> eyre::Result<()> {
     let mut fmt_parts: Vec<&str> = export_dir_fmt.split('/').collect();
 
@@ -283,34 +648,206 @@ fn validate_export_dir_fmt(export_dir_fmt: &str) -> eyre::Result<()> {
         return Err(eyre!("`导出目录格式`不能为空"));
     };
 
-    if !contains_any_field(last_fmt_part, &EXPORT_FMT_CHAPTER_FIELDS) {
+    if !fmt_template::contains_any_field(last_fmt_part, &fmt_template::CHAPTER_FIELDS)? {
         return Err(eyre!(
             "`导出目录格式`不合法，最后一层必须至少包含一个章节字段 {:?}",
-            EXPORT_FMT_CHAPTER_FIELDS
+            fmt_template::CHAPTER_FIELDS
         ));
     }
 
     for fmt_part in fmt_parts {
-        if let Some(field) = find_used_field(fmt_part, &EXPORT_FMT_CHAPTER_FIELDS) {
+        if let Some(field) = fmt_template::find_used_field(fmt_part, &fmt_template::CHAPTER_FIELDS)?
+        {
             return Err(eyre!(
                 "`导出目录格式`不合法，章节字段只能出现在最后一层，你当前在非最后一层使用了`{field}`。非最后一层只允许使用 {:?} 和 {:?}",
                 EXPORT_FMT_COMMON_FIELDS,
-                EXPORT_FMT_GROUP_FIELDS,
+                fmt_template::GROUP_FIELDS,
             ));
         }
     }
 
+    let fields = [
+        EXPORT_FMT_COMMON_FIELDS.as_slice(),
+        &fmt_template::GROUP_FIELDS,
+        &fmt_template::CHAPTER_FIELDS,
+    ]
+    .concat();
+    fmt_template::validate_fields(export_dir_fmt, &fields).wrap_err("`导出目录格式`不合法")?;
+
     Ok(())
 }
 
-fn contains_any_field(fmt_part: &str, fields: &[&str]) -> bool {
-    find_used_field(fmt_part, fields).is_some()
+/// 合并导出文件的路径模板参数，只有漫画和分组级别的字段
+#[derive(Debug, Clone, Serialize)]
+struct MergeFmtParams {
+    #[serde(flatten)]
+    comic: ComicDirFmtParams,
+    group_path_word: String,
+    group_title: String,
+    group_size: i64,
+    /// 合并后的文件被拆分时，当前是第几部分，从1开始
+    part: u32,
 }
 
-fn find_used_field<'a>(fmt_part: &str, fields: &'a [&'a str]) -> Option<&'a str> {
-    fields.iter().copied().find(|field| {
-        let placeholder = format!("{{{field}}}");
-        let format_prefix = format!("{{{field}:");
-        fmt_part.contains(&placeholder) || fmt_part.contains(&format_prefix)
-    })
+impl MergeFmtParams {
+    /// `group_title`会用`text_conversion`转换
+    fn new(
+        comic: &Comic,
+        group_path_word: String,
+        group_title: &str,
+        text_conversion: TextConversion,
+    ) -> Self {
+        let group_size = comic
+            .comic
+            .groups
+            .get(&group_path_word)
+            .and_then(|chapter_infos| chapter_infos.first())
+            .map_or(0, |chapter_info| chapter_info.group_size);
+        Self {
+            comic: ComicDirFmtParams::new(&comic.comic, text_conversion),
+            group_path_word,
+            group_title: text_conversion.convert(group_title),
+            group_size,
+            part: 1,
+        }
+    }
+
+    #[instrument(
+        level = "error",
+        skip_all,
+        fields(
+            comic_uuid = self.comic.comic_uuid,
+            comic_path_word = self.comic.comic_path_word,
+            comic_title = self.comic.comic_title,
+            group_path_word = self.group_path_word,
+            group_title = self.group_title,
+            format = ?format
+        )
+    )]
+    fn to_merge_path(
+        &self,
+        export_dir: &Path,
+        merge_fmt: &str,
+        format: ExportFormat,
+    ) -> eyre::Result<PathBuf> {
+        let vars = fmt_template::to_vars(self)?;
+
+        let ext = format.extension();
+
+        let mut dir_names = fmt_template::render_path(merge_fmt, &vars)
+            .wrap_err(format!("格式化合并{ext}目录名失败"))?;
+
+        let Some(filename) = dir_names.pop() else {
+            return Err(eyre!(
+                "配置中的合并{ext}目录格式至少要有1个层级，例如这个例子是3个层级：{{comic_title}}/{ext}/{{group_title}}"
+            ));
+        };
+
+        let mut output_path = export_dir.to_path_buf();
+        for dir_name in dir_names {
+            output_path = output_path.join(dir_name);
+        }
+
+        let filename =
+            utils::truncate_filename(&filename, MAX_EXPORT_FILENAME_BYTES - ext.len() - 1);
+
+        Ok(output_path.join(format!("{filename}.{ext}")))
+    }
+}
+
+/// 检查合并导出文件的路径模板，`fmt_name`是模板在配置中的名字，用于错误提示
+fn validate_merge_fmt(merge_fmt: &str, fmt_name: &str) -> eyre::Result<()> {
+    if !fmt_template::contains_any_field(merge_fmt, &MERGE_FMT_GROUP_ID_FIELDS)? {
+        return Err(eyre!(
+            "`{fmt_name}`不合法，整个模板中必须至少包含一个分组字段 {:?}",
+            MERGE_FMT_GROUP_ID_FIELDS
+        ));
+    }
+
+    if let Some(field) = fmt_template::find_used_field(merge_fmt, &fmt_template::CHAPTER_FIELDS)? {
+        return Err(eyre!(
+            "`{fmt_name}`不合法，合并文件不能使用章节字段`{field}`，只能使用 {:?} 和 {:?}",
+            fmt_template::COMIC_FIELDS,
+            fmt_template::GROUP_FIELDS,
+        ));
+    }
+
+    let fields = [
+        fmt_template::COMIC_FIELDS.as_slice(),
+        &fmt_template::GROUP_FIELDS,
+        &MERGE_FMT_PART_FIELDS,
+    ]
+    .concat();
+    fmt_template::validate_fields(merge_fmt, &fields).wrap_err(format!("`{fmt_name}`不合法"))?;
+
+    Ok(())
+}
+
+/// 检查所有分组合并为一个文件时的路径模板，此时没有分组字段可用
+fn validate_merge_comic_fmt(merge_fmt: &str, fmt_name: &str) -> eyre::Result<()> {
+    let forbidden_fields = [
+        fmt_template::GROUP_FIELDS.as_slice(),
+        &fmt_template::CHAPTER_FIELDS,
+    ]
+    .concat();
+    if let Some(field) = fmt_template::find_used_field(merge_fmt, &forbidden_fields)? {
+        return Err(eyre!(
+            "`{fmt_name}`不合法，所有分组合并为一个文件时不能使用分组字段和章节字段，你使用了`{field}`，只能使用 {:?}",
+            fmt_template::COMIC_FIELDS
+        ));
+    }
+
+    let fields = [
+        fmt_template::COMIC_FIELDS.as_slice(),
+        &MERGE_FMT_PART_FIELDS,
+    ]
+    .concat();
+    fmt_template::validate_fields(merge_fmt, &fields).wrap_err(format!("`{fmt_name}`不合法"))?;
+
+    Ok(())
+}
+
+/// 把条漫的切片`imgs`拼接起来，再按`webtoon_config`重新切分成页
+///
+/// 切分后的页按`001`、`002`...重新命名，格式与第一张切片相同
+#[instrument(level = "error", skip_all)]
+fn restitch_webtoon_imgs(
+    imgs: Vec<ExportImg>,
+    webtoon_config: &WebtoonConfig,
+) -> eyre::Result<Vec<ExportImg>> {
+    let Some(first_img) = imgs.first() else {
+        return Ok(imgs);
+    };
+    let first_filename = first_img.filename();
+    let (_, ext) = first_filename
+        .rsplit_once('.')
+        .ok_or_eyre(format!("`{first_filename}`没有扩展名"))?;
+    let ext = ext.to_string();
+    let format = img_process::format_from_extension(&ext)
+        .ok_or_eyre(format!("`{first_filename}`的格式不支持拼接"))?;
+
+    let mut stitcher = webtoon::StripStitcher::new(webtoon_config);
+    let mut pages = vec![];
+    // 每切出一页就立即编码，避免整个章节的页同时以未压缩的形式放在内存中
+    let mut encode_page = |page: &image::DynamicImage| -> eyre::Result<()> {
+        let filename = format!("{:03}.{ext}", pages.len() + 1);
+        let data =
+            img_process::encode_img(page, format).wrap_err(format!("编码`{filename}`失败"))?;
+        pages.push(ExportImg::Memory { filename, data });
+        Ok(())
+    };
+
+    for img in imgs {
+        let filename = img.filename().to_string();
+        let img_data = img.into_data()?;
+        let strip = image::load_from_memory(&img_data).wrap_err(format!("解码`{filename}`失败"))?;
+        for page in stitcher.push(strip) {
+            encode_page(&page)?;
+        }
+    }
+    if let Some(page) = stitcher.finish() {
+        encode_page(&page)?;
+    }
+
+    Ok(pages)
 }
@@ -1,30 +1,40 @@
 use std::{
-    io::Write,
+    ffi::OsStr,
+    io::{Read, Write},
     path::{Path, PathBuf},
     sync::{atomic::AtomicU32, Arc},
 };
 
 use eyre::{eyre, OptionExt, WrapErr};
+use float_ord::FloatOrd;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tauri::AppHandle;
 use tauri_specta::Event;
 use tracing::instrument;
-use zip::{write::SimpleFileOptions, ZipWriter};
+use zip::{
+    result::ZipError, write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter,
+    ZIP64_BYTES_THR,
+};
 
 use crate::{
-    config::ExportSkipMode,
+    config::{CbzCompression, CbzConfig, ExportSkipMode, ImgProcessConfig, WebtoonConfig},
     events::ExportCbzEvent,
     export::{
         build_grouped_export_targets, get_downloaded_chapters, get_downloaded_chapters_by_uuids,
-        get_image_paths, ComicExportLockGuard, ExportFormat, ExportTarget,
+        get_export_imgs, get_image_paths, get_temp_export_path, persist_temp_export_file,
+        validate_merge_fmt, ExportCancelToken, ExportFormat, ExportImg, ExportTarget,
+        MergeFmtParams,
+    },
+    extensions::{AppHandleExt, EyreReportToMessage},
+    types::{
+        CbzComicInfoUpdateReport, ChapterInfo, Comic, ComicInfo, ComicPageInfo, MissingCbzChapter,
     },
-    extensions::AppHandleExt,
-    types::{ChapterInfo, Comic, ComicInfo},
 };
 
 struct CbzErrorEventGuard {
     uuid: String,
     app: AppHandle,
+    cancel_token: ExportCancelToken,
     success: bool,
 }
 
@@ -35,35 +45,63 @@ impl Drop for CbzErrorEventGuard {
         }
 
         let uuid = self.uuid.clone();
-        let _ = ExportCbzEvent::Error { uuid }.emit(&self.app);
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportCbzEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportCbzEvent::Error { uuid }.emit(&self.app);
+        }
     }
 }
 
-/// 公开接口：导出全部已下载章节为CBZ
-#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
-pub fn cbz(app: &AppHandle, comic: &Comic) -> eyre::Result<()> {
-    let comic_path_word = &comic.comic.path_word;
-    let comic_title = &comic.comic.name;
-    let export_lock = app.get_export_lock().inner().clone();
-
-    // 检查导出锁
-    if !export_lock.try_acquire(comic_path_word) {
-        return Err(eyre!("漫画`{comic_title}`正在导出，请稍后再试"));
-    }
+struct CbzMergeErrorEventGuard {
+    uuid: String,
+    app: AppHandle,
+    cancel_token: ExportCancelToken,
+    success: bool,
+}
 
-    let _guard = ComicExportLockGuard {
-        lock: export_lock.clone(),
-        path_word: comic_path_word.clone(),
-    };
+impl Drop for CbzMergeErrorEventGuard {
+    fn drop(&mut self) {
+        if self.success {
+            return;
+        }
 
-    // 获取配置
-    let skip_mode = app.get_config().read().export_skip_mode;
+        let uuid = self.uuid.clone();
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportCbzEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportCbzEvent::MergeError { uuid }.emit(&self.app);
+        }
+    }
+}
 
-    // 获取已下载章节
-    let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+/// 公开接口：导出全部已下载章节为CBZ
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn cbz(app: &AppHandle, comic: &Comic) -> eyre::Result<()> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取配置
+        let (skip_mode, enable_merge) = {
+            let config = app.get_config().inner().read();
+            (config.export_skip_mode, config.enable_merge_cbz)
+        };
 
-    // 调用内部实现
-    export_cbz_internal(app, comic, downloaded_chapters, skip_mode)
+        // 获取已下载章节
+        let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+
+        // 调用内部实现
+        export_cbz_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            skip_mode,
+            enable_merge,
+            cancel_token,
+        )
+    })
 }
 
 /// 公开接口：导出指定已下载章节为CBZ
@@ -73,30 +111,122 @@ pub fn cbz_chapters(
     comic: &Comic,
     chapter_uuids: Vec<String>,
 ) -> eyre::Result<()> {
-    let comic_path_word = &comic.comic.path_word;
-    let comic_title = &comic.comic.name;
-    let export_lock = app.get_export_lock().inner().clone();
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取指定章节（用户主动选择，不跳过，不合并）
+        let downloaded_chapters =
+            get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
+
+        // 调用内部实现
+        export_cbz_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            ExportSkipMode::None, // 用户主动选择，不跳过
+            false,                // 选择性导出，不合并
+            cancel_token,
+        )
+    })
+}
 
-    // 检查导出锁
-    if !export_lock.try_acquire(comic_path_word) {
-        return Err(eyre!("漫画`{comic_title}`正在导出，请稍后再试"));
-    }
+/// 公开接口：用漫画最新的元数据重写已导出CBZ中的`ComicInfo.xml`，返回有变化的CBZ和找不到CBZ的章节
+///
+/// 只替换`ComicInfo.xml`，图片原样复制，不会重新压缩
+///
+/// CBZ的路径按当前的导出格式生成，修改过导出格式后，用旧格式导出的CBZ找不到，只能报告给用户
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn update_cbz_comic_info(
+    app: &AppHandle,
+    comic: &Comic,
+) -> eyre::Result<CbzComicInfoUpdateReport> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        let (export_dir, merge_cbz_fmt, merge_cbz_chapter_count, cbz_config, text_conversion) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.export_dir.clone(),
+                config.merge_cbz_fmt.clone(),
+                config.merge_cbz_chapter_count,
+                config.cbz.clone(),
+                config.fmt_text_conversion,
+            )
+        };
+        validate_merge_fmt(&merge_cbz_fmt, "合并cbz目录格式")?;
+
+        let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+        let grouped_export_targets =
+            build_grouped_export_targets(app, comic, downloaded_chapters, ExportFormat::Cbz)?;
+
+        // 收集所有可能存在的CBZ，包括章节CBZ和合并CBZ
+        let mut cbz_paths_and_comic_infos = Vec::new();
+        let mut report = CbzComicInfoUpdateReport::default();
+        for (group_path_word, export_targets) in grouped_export_targets {
+            if export_targets.is_empty() {
+                continue;
+            }
 
-    let _guard = ComicExportLockGuard {
-        lock: export_lock.clone(),
-        path_word: comic_path_word.clone(),
-    };
+            for target in &export_targets {
+                let chapter_info = &target.chapter_info;
+                if !chapter_info.is_cbz_exported {
+                    continue;
+                }
+                if !target.export_path.exists() {
+                    report.missing_chapters.push(MissingCbzChapter {
+                        chapter_uuid: chapter_info.chapter_uuid.clone(),
+                        chapter_title: chapter_info.chapter_title.clone(),
+                        expected_cbz_path: target.export_path.clone(),
+                    });
+                    continue;
+                }
+                let comic_info = ComicInfo::from(comic, chapter_info);
+                cbz_paths_and_comic_infos.push((target.export_path.clone(), comic_info));
+            }
+
+            let group_title = &export_targets[0].chapter_info.group_name;
+            let fmt_params =
+                MergeFmtParams::new(comic, group_path_word, group_title, text_conversion);
+            let chapter_infos: Vec<ChapterInfo> = export_targets
+                .into_iter()
+                .map(|target| target.chapter_info)
+                .collect();
+            let parts = split_merged_cbz(
+                &export_dir,
+                &merge_cbz_fmt,
+                fmt_params,
+                chapter_infos,
+                merge_cbz_chapter_count,
+            )?;
+            for part in parts {
+                let comic_info = part.comic_info(comic);
+                cbz_paths_and_comic_infos.push((part.cbz_path, comic_info));
+            }
+        }
+
+        for (cbz_path, comic_info) in cbz_paths_and_comic_infos {
+            cancel_token.check()?;
 
-    // 获取指定章节（用户主动选择，不跳过）
-    let downloaded_chapters = get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
+            // 合并CBZ不一定导出过，不存在时直接跳过
+            if !cbz_path.exists() {
+                continue;
+            }
 
-    // 调用内部实现
-    export_cbz_internal(
-        app,
-        comic,
-        downloaded_chapters,
-        ExportSkipMode::None, // 用户主动选择，不跳过
-    )
+            if rewrite_comic_info(&cbz_path, comic_info, &cbz_config)? {
+                report.updated_cbz_paths.push(cbz_path);
+            }
+        }
+
+        if !report.missing_chapters.is_empty() {
+            tracing::warn!(
+                missing_chapter_count = report.missing_chapters.len(),
+                "有已导出的章节找不到CBZ，可能修改过导出格式"
+            );
+        }
+
+        Ok(report)
+    })
 }
 
 /// 内部实现：导出CBZ
@@ -106,6 +236,8 @@ fn export_cbz_internal(
     comic: &Comic,
     downloaded_chapters: Vec<ChapterInfo>,
     skip_mode: ExportSkipMode,
+    enable_merge: bool,
+    cancel_token: &ExportCancelToken,
 ) -> eyre::Result<()> {
     let grouped_export_targets =
         build_grouped_export_targets(app, comic, downloaded_chapters, ExportFormat::Cbz)?;
@@ -113,29 +245,74 @@ fn export_cbz_internal(
         return Ok(());
     }
 
+    let grouped_export_targets_for_merge = if enable_merge {
+        Some(grouped_export_targets.clone())
+    } else {
+        None
+    };
+
     for (_group_path_word, export_targets) in grouped_export_targets {
         if export_targets.is_empty() {
             continue;
         }
 
-        create_group_cbz_files(app, comic, export_targets, skip_mode)?;
+        create_group_cbz_files(app, comic, export_targets, skip_mode, cancel_token)?;
+    }
+
+    // 合并CBZ
+    if let Some(grouped_export_targets) = grouped_export_targets_for_merge {
+        let (export_dir, merge_cbz_fmt, text_conversion) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.export_dir.clone(),
+                config.merge_cbz_fmt.clone(),
+                config.fmt_text_conversion,
+            )
+        };
+
+        validate_merge_fmt(&merge_cbz_fmt, "合并cbz目录格式")?;
+
+        for (group_path_word, export_targets) in grouped_export_targets {
+            if export_targets.is_empty() {
+                continue;
+            }
+
+            let group_title = &export_targets[0].chapter_info.group_name;
+            let fmt_params =
+                MergeFmtParams::new(comic, group_path_word, group_title, text_conversion);
+
+            merge_group_cbz_files(
+                app,
+                comic,
+                &export_dir,
+                &merge_cbz_fmt,
+                fmt_params,
+                export_targets,
+                skip_mode,
+                cancel_token,
+            )?;
+        }
     }
 
     Ok(())
 }
 
 #[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
 #[instrument(level = "error", skip_all)]
 fn create_group_cbz_files(
     app: &AppHandle,
     comic: &Comic,
     export_targets: Vec<ExportTarget>,
     skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
 ) -> eyre::Result<()> {
     let create_event_uuid = uuid::Uuid::new_v4().to_string();
     // 发送开始创建cbz事件
     let _ = ExportCbzEvent::Start {
         uuid: create_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
         comic_title: comic.comic.name.clone(),
         group_title: export_targets[0].chapter_info.group_name.clone(),
         total: export_targets.len() as u32,
@@ -145,6 +322,7 @@ fn create_group_cbz_files(
     let mut create_error_event_guard = CbzErrorEventGuard {
         uuid: create_event_uuid.clone(),
         app: app.clone(),
+        cancel_token: cancel_token.clone(),
         success: false,
     };
 
@@ -159,9 +337,21 @@ fn create_group_cbz_files(
     std::fs::create_dir_all(&export_dir)
         .wrap_err(format!("创建目录`{}`失败", export_dir.display()))?;
 
-    let cfg = yaserde::ser::Config {
-        perform_indent: true,
-        ..Default::default()
+    let (export_with_cover, img_process_config, webtoon_config, cbz_config) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.export_with_cover,
+            config.img_process.clone(),
+            config.webtoon.clone(),
+            config.cbz.clone(),
+        )
+    };
+    // 如果启用了封面，则把封面作为每个cbz的第一页
+    let cover_path = if export_with_cover {
+        comic.get_cover_path()
+    } else {
+        None
     };
 
     // 用来记录创建cbz的进度
@@ -170,6 +360,8 @@ fn create_group_cbz_files(
     let current_span = tracing::Span::current();
     let export_targets = export_targets.into_par_iter();
     export_targets.try_for_each(|target| -> eyre::Result<()> {
+        cancel_token.check()?;
+
         let mut chapter_info = target.chapter_info;
         let cbz_path = target.export_path;
 
@@ -206,22 +398,69 @@ fn create_group_cbz_files(
             return Ok(());
         }
 
-        // 生成ComicInfo
-        let comic_info = ComicInfo::from(comic, &chapter_info);
-        // 序列化ComicInfo为xml
-        let comic_info_xml = yaserde::ser::to_string_with_config(&comic_info, &cfg)
-            .map_err(|err_msg| eyre!("序列化`ComicInfo.xml`失败: {err_msg}"))?;
-
         let image_paths = get_image_paths(chapter_download_dir).wrap_err(format!(
             "获取`{}`中的图片失败",
             chapter_download_dir.display()
         ))?;
 
-        create_cbz_file(image_paths, &comic_info_xml, &cbz_path)?;
+        // 条漫需要拼接后重新切分成页
+        let chapter_webtoon_config = webtoon_config
+            .mode
+            .should_restitch(chapter_info.is_long)
+            .then_some(&webtoon_config);
+        let imgs = get_export_imgs(
+            cover_path.as_deref(),
+            image_paths,
+            &img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            chapter_webtoon_config,
+        )?;
+
+        // 生成ComicInfo
+        let mut comic_info = ComicInfo::from(comic, &chapter_info);
+        // 封面、跨页拆分和条漫重新切分都会改变页数，以实际导出的图片为准
+        let pages = imgs
+            .iter()
+            .enumerate()
+            .map(|(i, img)| {
+                let is_cover = i == 0 && cover_path.is_some();
+                Ok(ComicPageInfo::new(i as u32, img.dimensions()?, is_cover))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        comic_info.set_pages(pages);
+        let comic_info_xml = comic_info.to_xml()?;
+        let entry_options = CbzEntryOptions::new(&cbz_config, &comic_info);
+
+        // 先写到临时文件，校验通过后再重命名，避免留下不完整的cbz
+        let expected_entry_count = imgs.len() + 1; // 所有图片加上ComicInfo.xml
+        let temp_path = get_temp_export_path(&cbz_path);
+        if let Err(err) = create_cbz_file(imgs, &comic_info_xml, &entry_options, &temp_path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        if let Err(err) = validate_cbz_file(&temp_path, expected_entry_count) {
+            let _ = std::fs::remove_file(&temp_path);
+            // 发送校验失败事件，让前端知道是哪个章节有问题
+            let _ = ExportCbzEvent::ValidationError {
+                uuid: create_event_uuid.clone(),
+                chapter_uuid: chapter_info.chapter_uuid.clone(),
+                chapter_title: chapter_info.chapter_title.clone(),
+                err_msg: format!("{err:#}"),
+            }
+            .emit(app);
+            return Err(err);
+        }
+        persist_temp_export_file(&temp_path, &cbz_path)?;
 
         // 更新章节导出状态
         chapter_info.is_cbz_exported = true;
         chapter_info.save_metadata()?;
+        let library_index = app.get_library_index();
+        if let Err(err) = library_index.update_chapter_metadata(chapter_download_dir) {
+            let err_title = "更新已下载漫画索引失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
 
         // 更新创建cbz的进度
         let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
@@ -249,39 +488,333 @@ fn create_group_cbz_files(
     Ok(())
 }
 
+/// 把分组中的章节合并为CBZ，如果配置了`merge_cbz_chapter_count`，则每这么多个章节拆分为一个CBZ
+///
+/// 直接读取章节的下载目录，而不是拆开章节CBZ，所以合并不依赖章节CBZ是否存在
+///
+/// `skip_mode`为`SkipExported`时，只重建包含新导出章节或还不存在的合并CBZ
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "error", skip_all)]
+fn merge_group_cbz_files(
+    app: &AppHandle,
+    comic: &Comic,
+    export_dir: &Path,
+    merge_cbz_fmt: &str,
+    fmt_params: MergeFmtParams,
+    export_targets: Vec<ExportTarget>,
+    skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let group_title = export_targets[0].chapter_info.group_name.clone();
+
+    let (merge_config, merge_cbz_chapter_count) = {
+        let config = app.get_config();
+        let config = config.read();
+        let cover_path = if config.export_with_cover {
+            comic.get_cover_path()
+        } else {
+            None
+        };
+        let merge_config = CbzMergeConfig {
+            cover_path,
+            img_process_config: config.img_process.clone(),
+            webtoon_config: config.webtoon.clone(),
+            cbz_config: config.cbz.clone(),
+        };
+        (merge_config, config.merge_cbz_chapter_count)
+    };
+
+    let chapter_infos: Vec<ChapterInfo> = export_targets
+        .into_iter()
+        .map(|target| target.chapter_info)
+        .collect();
+    let parts = split_merged_cbz(
+        export_dir,
+        merge_cbz_fmt,
+        fmt_params,
+        chapter_infos,
+        merge_cbz_chapter_count,
+    )?;
+
+    let merge_event_uuid = uuid::Uuid::new_v4().to_string();
+    // 发送开始合并cbz事件
+    let _ = ExportCbzEvent::MergeStart {
+        uuid: merge_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        group_title,
+        total: parts.len() as u32,
+    }
+    .emit(app);
+    // 如果success为false，drop时发送MergeError事件
+    let mut merge_error_event_guard = CbzMergeErrorEventGuard {
+        uuid: merge_event_uuid.clone(),
+        app: app.clone(),
+        cancel_token: cancel_token.clone(),
+        success: false,
+    };
+
+    // `{part}`在目录层级中时，拆分后的各部分在不同的目录
+    let mut merge_cbz_dirs: Vec<PathBuf> = Vec::new();
+    for part in &parts {
+        let merge_cbz_dir = part
+            .cbz_path
+            .parent()
+            .ok_or_eyre(format!("获取`{}`的父目录失败", part.cbz_path.display()))?;
+        std::fs::create_dir_all(merge_cbz_dir)
+            .wrap_err(format!("创建目录`{}`失败", merge_cbz_dir.display()))?;
+        merge_cbz_dirs.push(merge_cbz_dir.to_path_buf());
+    }
+
+    // 合并很吃内存，为了减少爆内存的发生，不使用并发处理，而是逐个合并
+    for (i, part) in parts.iter().enumerate() {
+        cancel_token.check()?;
+
+        // `chapter_infos`是导出前的状态，这次新导出的章节`is_cbz_exported`为`false`
+        let is_up_to_date = skip_mode == ExportSkipMode::SkipExported
+            && part.cbz_path.exists()
+            && part
+                .chapter_infos
+                .iter()
+                .all(|chapter_info| chapter_info.is_cbz_exported);
+        if !is_up_to_date {
+            let comic_info = part.comic_info(comic);
+            // 先写到临时文件，校验通过后再重命名，避免留下不完整的合并cbz
+            let temp_path = get_temp_export_path(&part.cbz_path);
+            let entry_count = match create_merged_cbz_file(
+                &part.chapter_infos,
+                comic_info,
+                &merge_config,
+                &temp_path,
+            ) {
+                Ok(entry_count) => entry_count,
+                Err(err) => {
+                    let _ = std::fs::remove_file(&temp_path);
+                    return Err(err);
+                }
+            };
+            if let Err(err) = validate_cbz_file(&temp_path, entry_count) {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(err);
+            }
+            persist_temp_export_file(&temp_path, &part.cbz_path)?;
+        }
+
+        let _ = ExportCbzEvent::MergeProgress {
+            uuid: merge_event_uuid.clone(),
+            current: i as u32 + 1,
+        }
+        .emit(app);
+    }
+
+    // 标记为成功，后面drop时就不会发送MergeError事件
+    merge_error_event_guard.success = true;
+    // 发送合并cbz完成事件
+    let _ = ExportCbzEvent::MergeEnd {
+        uuid: merge_event_uuid,
+        comic_path_word: comic.comic.path_word.clone(),
+        export_dir: merge_cbz_dirs
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| export_dir.to_path_buf()),
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+/// 合并CBZ拆分后的一部分，没有拆分时只有一部分
+struct MergedCbzPart {
+    cbz_path: PathBuf,
+    /// 按`order`排好序的章节
+    chapter_infos: Vec<ChapterInfo>,
+}
+
+impl MergedCbzPart {
+    fn comic_info(&self, comic: &Comic) -> ComicInfo {
+        match (self.chapter_infos.first(), self.chapter_infos.last()) {
+            (Some(first_chapter_info), Some(last_chapter_info)) => {
+                ComicInfo::from_merged(comic, first_chapter_info, last_chapter_info)
+            }
+            _ => ComicInfo::default(),
+        }
+    }
+}
+
+/// 按`merge_cbz_chapter_count`把章节拆分为多个合并CBZ，为`0`时不拆分
+///
+/// 拆分后的文件名由合并模板中的`{part}`字段决定，模板中没有`{part}`时会在文件名后面加上章节序号的范围，
+/// 比如`xxx (1-10).cbz`，序号是章节在分组中按`order`排序后的位置，从1开始
+fn split_merged_cbz(
+    export_dir: &Path,
+    merge_cbz_fmt: &str,
+    mut fmt_params: MergeFmtParams,
+    mut chapter_infos: Vec<ChapterInfo>,
+    merge_cbz_chapter_count: usize,
+) -> eyre::Result<Vec<MergedCbzPart>> {
+    chapter_infos.sort_by_key(|chapter_info| FloatOrd(chapter_info.order));
+    let chunk_size = if merge_cbz_chapter_count == 0 {
+        chapter_infos.len().max(1)
+    } else {
+        merge_cbz_chapter_count
+    };
+
+    let split = chapter_infos.len() > chunk_size;
+    let has_part_field = merge_cbz_fmt.contains("{part");
+
+    let mut parts = Vec::new();
+    for (i, chunk) in chapter_infos.chunks(chunk_size).enumerate() {
+        fmt_params.part = i as u32 + 1;
+        let part_fmt = if split && !has_part_field {
+            let first_index = i * chunk_size + 1;
+            let last_index = first_index + chunk.len() - 1;
+            format!("{merge_cbz_fmt} ({first_index}-{last_index})")
+        } else {
+            merge_cbz_fmt.to_string()
+        };
+        let cbz_path = fmt_params.to_merge_path(export_dir, &part_fmt, ExportFormat::Cbz)?;
+
+        parts.push(MergedCbzPart {
+            cbz_path,
+            chapter_infos: chunk.to_vec(),
+        });
+    }
+
+    Ok(parts)
+}
+
+/// 合并CBZ时需要的配置
+struct CbzMergeConfig {
+    cover_path: Option<PathBuf>,
+    img_process_config: ImgProcessConfig,
+    webtoon_config: WebtoonConfig,
+    cbz_config: CbzConfig,
+}
+
+/// 把`chapter_infos`中的章节合并为一个CBZ，保存到`cbz_path`，返回写入的文件数
+///
+/// 每个章节的图片都加上章节在CBZ中的序号作为前缀，比如`0001_001.webp`，封面为`0000_000.webp`，
+/// 这样阅读器按文件名排序时就能得到正确的顺序
+///
+/// 合并CBZ可能超过4GB或有超过65535个文件，这时zip会自动改用zip64的中央目录
+#[allow(clippy::cast_possible_truncation)]
+#[instrument(level = "error", skip_all, fields(cbz_path = %cbz_path.display()))]
+fn create_merged_cbz_file(
+    chapter_infos: &[ChapterInfo],
+    mut comic_info: ComicInfo,
+    merge_config: &CbzMergeConfig,
+    cbz_path: &Path,
+) -> eyre::Result<usize> {
+    let cbz_file = std::fs::File::create(cbz_path)
+        .wrap_err(format!("创建文件`{}`失败", cbz_path.display()))?;
+    let mut zip_writer = ZipWriter::new(cbz_file);
+    let mut pages = vec![];
+    // 页的信息不影响日期，所以可以先创建
+    let entry_options = CbzEntryOptions::new(&merge_config.cbz_config, &comic_info);
+
+    let mut write_imgs = |imgs: Vec<ExportImg>, prefix: &str, is_cover: bool| -> eyre::Result<()> {
+        for img in imgs {
+            let img = img.with_filename_prefix(prefix);
+            let filename = img.filename();
+            let page_info = ComicPageInfo::new(pages.len() as u32, img.dimensions()?, is_cover);
+            let file_options = entry_options.file_options(filename, img.data_len()?);
+
+            zip_writer
+                .start_file(filename, file_options)
+                .wrap_err(format!("在`{}`创建`{filename}`失败", cbz_path.display()))?;
+            img.write_to(&mut zip_writer)
+                .wrap_err(format!("将`{filename}`写入`{}`失败", cbz_path.display()))?;
+            pages.push(page_info);
+        }
+        Ok(())
+    };
+
+    // 封面只放在最前面
+    let cover_imgs = get_export_imgs(
+        merge_config.cover_path.as_deref(),
+        vec![],
+        &merge_config.img_process_config,
+        None,
+        None,
+    )?;
+    write_imgs(cover_imgs, "0000_", true)?;
+
+    // 逐个章节写入，内存中最多只有一个章节的图片
+    for (i, chapter_info) in chapter_infos.iter().enumerate() {
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+        let image_paths = get_image_paths(chapter_download_dir).wrap_err(format!(
+            "获取`{}`中的图片失败",
+            chapter_download_dir.display()
+        ))?;
+        // 条漫需要拼接后重新切分成页
+        let webtoon_config = merge_config
+            .webtoon_config
+            .mode
+            .should_restitch(chapter_info.is_long)
+            .then_some(&merge_config.webtoon_config);
+        let imgs = get_export_imgs(
+            None,
+            image_paths,
+            &merge_config.img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            webtoon_config,
+        )?;
+
+        write_imgs(imgs, &format!("{:04}_", i + 1), false)?;
+    }
+
+    // 所有图片加上ComicInfo.xml
+    let entry_count = pages.len() + 1;
+    // 所有图片都写入后才知道总页数，所以ComicInfo放在最后写入
+    comic_info.set_pages(pages);
+    let comic_info_xml = comic_info.to_xml()?;
+    let file_options = entry_options.file_options("ComicInfo.xml", comic_info_xml.len() as u64);
+    zip_writer
+        .start_file("ComicInfo.xml", file_options)
+        .wrap_err(format!("在`{}`创建`ComicInfo.xml`失败", cbz_path.display()))?;
+    zip_writer
+        .write_all(comic_info_xml.as_bytes())
+        .wrap_err("写入`ComicInfo.xml`失败")?;
+
+    zip_writer
+        .finish()
+        .wrap_err(format!("关闭`{}`失败", cbz_path.display()))?;
+
+    Ok(entry_count)
+}
+
 #[instrument(level = "error", skip_all, fields(cbz_path = %cbz_path.display()))]
 fn create_cbz_file(
-    image_paths: Vec<PathBuf>,
+    imgs: Vec<ExportImg>,
     comic_info_xml: &str,
+    entry_options: &CbzEntryOptions,
     cbz_path: &Path,
 ) -> eyre::Result<()> {
     let cbz_file = std::fs::File::create(cbz_path)
         .wrap_err(format!("创建文件`{}`失败", cbz_path.display()))?;
     let mut zip_writer = ZipWriter::new(cbz_file);
 
+    let file_options = entry_options.file_options("ComicInfo.xml", comic_info_xml.len() as u64);
     zip_writer
-        .start_file("ComicInfo.xml", SimpleFileOptions::default())
+        .start_file("ComicInfo.xml", file_options)
         .wrap_err(format!("在`{}`创建`ComicInfo.xml`失败", cbz_path.display()))?;
     zip_writer
         .write_all(comic_info_xml.as_bytes())
         .wrap_err("写入`ComicInfo.xml`失败")?;
 
-    for image_path in image_paths {
-        let filename = image_path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .ok_or_eyre(format!("获取`{}`的文件名失败", image_path.display()))?;
+    for img in imgs {
+        let filename = img.filename();
+        let file_options = entry_options.file_options(filename, img.data_len()?);
 
         zip_writer
-            .start_file(filename, SimpleFileOptions::default())
+            .start_file(filename, file_options)
             .wrap_err(format!("在`{}`创建`{filename}`失败", cbz_path.display()))?;
-        let mut file = std::fs::File::open(&image_path)
-            .wrap_err(format!("打开`{}`失败", image_path.display()))?;
-        std::io::copy(&mut file, &mut zip_writer).wrap_err(format!(
-            "将`{}`写入`{}`失败",
-            image_path.display(),
-            cbz_path.display()
-        ))?;
+        img.write_to(&mut zip_writer)
+            .wrap_err(format!("将`{filename}`写入`{}`失败", cbz_path.display()))?;
     }
 
     zip_writer
@@ -290,3 +823,177 @@ fn create_cbz_file(
 
     Ok(())
 }
+
+/// 写入CBZ中每个文件时的选项
+///
+/// 所有文件的修改时间都取`ComicInfo`中的日期，没有日期时为`1980-01-01`，
+/// 这样同样的内容每次导出的CBZ都完全相同，同步工具可以跳过没有变化的文件
+struct CbzEntryOptions {
+    compression_method: CompressionMethod,
+    compression_level: Option<i64>,
+    store_compressed_imgs: bool,
+    last_modified_time: DateTime,
+}
+
+impl CbzEntryOptions {
+    fn new(cbz_config: &CbzConfig, comic_info: &ComicInfo) -> Self {
+        let (compression_method, compression_level) = match cbz_config.compression {
+            CbzCompression::Stored => (CompressionMethod::Stored, None),
+            CbzCompression::Deflate => (
+                CompressionMethod::Deflated,
+                Some(cbz_config.compression_level),
+            ),
+            CbzCompression::Zstd => (CompressionMethod::Zstd, Some(cbz_config.compression_level)),
+        };
+
+        CbzEntryOptions {
+            compression_method,
+            compression_level,
+            store_compressed_imgs: cbz_config.store_compressed_imgs,
+            last_modified_time: get_zip_date_time(comic_info).unwrap_or_default(),
+        }
+    }
+
+    /// `filename`的选项，`size`为文件大小，超过4GB时要启用zip64
+    fn file_options(&self, filename: &str, size: u64) -> SimpleFileOptions {
+        let options = SimpleFileOptions::default()
+            .last_modified_time(self.last_modified_time)
+            .large_file(size >= ZIP64_BYTES_THR);
+
+        let is_compressed_img = Path::new(filename)
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| {
+                let ext = ext.to_lowercase();
+                ext == "jpg" || ext == "jpeg" || ext == "webp"
+            });
+        if self.store_compressed_imgs && is_compressed_img {
+            return options.compression_method(CompressionMethod::Stored);
+        }
+
+        options
+            .compression_method(self.compression_method)
+            .compression_level(self.compression_level)
+    }
+}
+
+/// 把`ComicInfo`中的日期转为zip的时间，日期不完整或超出zip能表示的范围时返回`None`
+fn get_zip_date_time(comic_info: &ComicInfo) -> Option<DateTime> {
+    let year = u16::try_from(comic_info.year?).ok()?;
+    let month = u8::try_from(comic_info.month?).ok()?;
+    let day = u8::try_from(comic_info.day?).ok()?;
+    DateTime::from_date_and_time(year, month, day, 0, 0, 0).ok()
+}
+
+/// 校验`cbz_path`的中央目录能否读取，并且其中有`expected_entry_count`个文件
+fn validate_cbz_file(cbz_path: &Path, expected_entry_count: usize) -> eyre::Result<()> {
+    let cbz_file =
+        std::fs::File::open(cbz_path).wrap_err(format!("打开`{}`失败", cbz_path.display()))?;
+    let archive = ZipArchive::new(cbz_file)
+        .wrap_err(format!("读取`{}`的中央目录失败", cbz_path.display()))?;
+
+    let entry_count = archive.len();
+    if entry_count != expected_entry_count {
+        return Err(eyre!(
+            "`{}`中应该有{expected_entry_count}个文件，实际有{entry_count}个",
+            cbz_path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// 用`comic_info`替换`cbz_path`中的`ComicInfo.xml`，内容没有变化时返回`false`
+///
+/// 图片没有变化，所以页的信息沿用原来的`ComicInfo.xml`
+#[allow(clippy::cast_possible_wrap)]
+#[instrument(level = "error", skip_all, fields(cbz_path = %cbz_path.display()))]
+fn rewrite_comic_info(
+    cbz_path: &Path,
+    mut comic_info: ComicInfo,
+    cbz_config: &CbzConfig,
+) -> eyre::Result<bool> {
+    let cbz_file =
+        std::fs::File::open(cbz_path).wrap_err(format!("打开`{}`失败", cbz_path.display()))?;
+    let mut archive =
+        ZipArchive::new(cbz_file).wrap_err(format!("读取`{}`失败", cbz_path.display()))?;
+
+    let old_comic_info_xml = match archive.by_name("ComicInfo.xml") {
+        Ok(mut file) => {
+            let mut xml = String::new();
+            file.read_to_string(&mut xml).wrap_err(format!(
+                "读取`{}`中的`ComicInfo.xml`失败",
+                cbz_path.display()
+            ))?;
+            Some(xml)
+        }
+        Err(ZipError::FileNotFound) => None,
+        Err(err) => {
+            return Err(err).wrap_err(format!(
+                "在`{}`中查找`ComicInfo.xml`失败",
+                cbz_path.display()
+            ))
+        }
+    };
+
+    if let Some(old_comic_info_xml) = &old_comic_info_xml {
+        let old_comic_info = ComicInfo::from_xml(old_comic_info_xml)?;
+        comic_info.page_count = old_comic_info.page_count;
+        comic_info.pages = old_comic_info.pages;
+    } else {
+        // 没有`ComicInfo.xml`，压缩包里的文件就都是图片
+        comic_info.page_count = archive.len() as i64;
+    }
+
+    let comic_info_xml = comic_info.to_xml()?;
+    if old_comic_info_xml.as_deref() == Some(comic_info_xml.as_str()) {
+        return Ok(false);
+    }
+
+    // 先写到临时文件，成功后再替换原文件，避免中途出错导致原文件损坏
+    let temp_path = cbz_path.with_extension("cbz.tmp");
+    let temp_file = std::fs::File::create(&temp_path)
+        .wrap_err(format!("创建文件`{}`失败", temp_path.display()))?;
+    let mut zip_writer = ZipWriter::new(temp_file);
+
+    let entry_options = CbzEntryOptions::new(cbz_config, &comic_info);
+    let file_options = entry_options.file_options("ComicInfo.xml", comic_info_xml.len() as u64);
+    zip_writer
+        .start_file("ComicInfo.xml", file_options)
+        .wrap_err(format!(
+            "在`{}`创建`ComicInfo.xml`失败",
+            temp_path.display()
+        ))?;
+    zip_writer
+        .write_all(comic_info_xml.as_bytes())
+        .wrap_err("写入`ComicInfo.xml`失败")?;
+
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index_raw(i)
+            .wrap_err(format!("读取`{}`中的第{i}个文件失败", cbz_path.display()))?;
+        if file.name() == "ComicInfo.xml" {
+            continue;
+        }
+
+        let filename = file.name().to_string();
+        // 直接复制压缩后的数据，不重新压缩
+        zip_writer
+            .raw_copy_file(file)
+            .wrap_err(format!("复制`{filename}`到`{}`失败", temp_path.display()))?;
+    }
+
+    zip_writer
+        .finish()
+        .wrap_err(format!("关闭`{}`失败", temp_path.display()))?;
+    // 关闭原文件后才能替换它
+    drop(archive);
+
+    std::fs::rename(&temp_path, cbz_path).wrap_err(format!(
+        "将`{}`重命名为`{}`失败",
+        temp_path.display(),
+        cbz_path.display()
+    ))?;
+
+    Ok(true)
+}
@@ -0,0 +1,839 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicU32, Arc},
+};
+
+use eyre::{eyre, OptionExt, WrapErr};
+use float_ord::FloatOrd;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tauri::AppHandle;
+use tauri_specta::Event;
+use tracing::instrument;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    config::{ExportSkipMode, ImgProcessConfig, WebtoonConfig},
+    events::ExportEpubEvent,
+    export::{
+        build_grouped_export_targets, get_downloaded_chapters, get_downloaded_chapters_by_uuids,
+        get_export_imgs, get_image_paths, validate_merge_fmt, ExportCancelToken, ExportFormat,
+        ExportImg, ExportTarget, MergeFmtParams,
+    },
+    extensions::{AppHandleExt, EyreReportToMessage},
+    types::{ChapterInfo, Comic},
+    utils::escape_xml,
+};
+
+struct EpubCreateErrorEventGuard {
+    uuid: String,
+    app: AppHandle,
+    cancel_token: ExportCancelToken,
+    success: bool,
+}
+
+impl Drop for EpubCreateErrorEventGuard {
+    fn drop(&mut self) {
+        if self.success {
+            return;
+        }
+
+        let uuid = self.uuid.clone();
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportEpubEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportEpubEvent::CreateError { uuid }.emit(&self.app);
+        }
+    }
+}
+
+struct EpubMergeErrorEventGuard {
+    uuid: String,
+    app: AppHandle,
+    cancel_token: ExportCancelToken,
+    success: bool,
+}
+
+impl Drop for EpubMergeErrorEventGuard {
+    fn drop(&mut self) {
+        if self.success {
+            return;
+        }
+
+        let uuid = self.uuid.clone();
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportEpubEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportEpubEvent::MergeError { uuid }.emit(&self.app);
+        }
+    }
+}
+
+/// 公开接口：导出全部已下载章节为EPUB
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn epub(app: &AppHandle, comic: &Comic) -> eyre::Result<()> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取配置
+        let (skip_mode, enable_merge) = {
+            let config = app.get_config().inner().read();
+
+            let skip_mode = config.export_skip_mode;
+            let enable_merge = if skip_mode == ExportSkipMode::SkipExported {
+                false
+            } else {
+                config.enable_merge_epub
+            };
+
+            (skip_mode, enable_merge)
+        };
+
+        // 获取已下载章节
+        let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+
+        // 调用内部实现
+        export_epub_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            skip_mode,
+            enable_merge,
+            cancel_token,
+        )
+    })
+}
+
+/// 公开接口：导出指定已下载章节为EPUB
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn epub_chapters(
+    app: &AppHandle,
+    comic: &Comic,
+    chapter_uuids: Vec<String>,
+) -> eyre::Result<()> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取指定章节（用户主动选择，不跳过，不合并）
+        let downloaded_chapters =
+            get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
+
+        // 调用内部实现
+        export_epub_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            ExportSkipMode::None, // 用户主动选择，不跳过
+            false,                // 选择性导出，不合并
+            cancel_token,
+        )
+    })
+}
+
+/// 内部实现：导出EPUB
+#[instrument(level = "error", skip_all, fields(skip_mode = ?skip_mode))]
+fn export_epub_internal(
+    app: &AppHandle,
+    comic: &Comic,
+    downloaded_chapters: Vec<ChapterInfo>,
+    skip_mode: ExportSkipMode,
+    enable_merge: bool,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let grouped_export_targets =
+        build_grouped_export_targets(app, comic, downloaded_chapters, ExportFormat::Epub)?;
+    if grouped_export_targets.is_empty() {
+        return Ok(());
+    }
+
+    let grouped_export_targets_for_merge = if enable_merge {
+        Some(grouped_export_targets.clone())
+    } else {
+        None
+    };
+
+    for (_group_path_word, export_targets) in grouped_export_targets {
+        if export_targets.is_empty() {
+            continue;
+        }
+
+        create_group_epub_files(app, comic, export_targets, skip_mode, cancel_token)?;
+    }
+
+    // 合并EPUB
+    if let Some(grouped_export_targets) = grouped_export_targets_for_merge {
+        let (export_dir, merge_epub_fmt, text_conversion) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.export_dir.clone(),
+                config.merge_epub_fmt.clone(),
+                config.fmt_text_conversion,
+            )
+        };
+
+        validate_merge_fmt(&merge_epub_fmt, "合并epub目录格式")?;
+
+        for (group_path_word, export_targets) in grouped_export_targets {
+            if export_targets.is_empty() {
+                continue;
+            }
+
+            let group_title = &export_targets[0].chapter_info.group_name;
+            let fmt_params =
+                MergeFmtParams::new(comic, group_path_word, group_title, text_conversion);
+
+            let merge_epub_path =
+                fmt_params.to_merge_path(&export_dir, &merge_epub_fmt, ExportFormat::Epub)?;
+
+            merge_group_epub_files(app, comic, &merge_epub_path, export_targets, cancel_token)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// EPUB导出时需要的配置
+struct EpubExportConfig {
+    cover_path: Option<PathBuf>,
+    export_with_cover: bool,
+    rtl: bool,
+    img_process_config: ImgProcessConfig,
+    webtoon_config: WebtoonConfig,
+}
+
+impl EpubExportConfig {
+    fn new(app: &AppHandle, comic: &Comic) -> Self {
+        let config = app.get_config();
+        let config = config.read();
+
+        Self {
+            cover_path: comic.get_cover_path(),
+            export_with_cover: config.export_with_cover,
+            rtl: config.epub_rtl,
+            img_process_config: config.img_process.clone(),
+            webtoon_config: config.webtoon.clone(),
+        }
+    }
+
+    /// 获取章节要写入EPUB的图片，封面由`EpubWriter::add_cover`单独处理
+    fn get_chapter_imgs(&self, chapter_info: &ChapterInfo) -> eyre::Result<Vec<ExportImg>> {
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+
+        let image_paths = get_image_paths(chapter_download_dir).wrap_err(format!(
+            "获取`{}`中的图片失败",
+            chapter_download_dir.display()
+        ))?;
+        // 条漫需要拼接后重新切分成页
+        let webtoon_config = self
+            .webtoon_config
+            .mode
+            .should_restitch(chapter_info.is_long)
+            .then_some(&self.webtoon_config);
+
+        get_export_imgs(
+            None,
+            image_paths,
+            &self.img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            webtoon_config,
+        )
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[instrument(level = "error", skip_all)]
+fn create_group_epub_files(
+    app: &AppHandle,
+    comic: &Comic,
+    export_targets: Vec<ExportTarget>,
+    skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let create_event_uuid = uuid::Uuid::new_v4().to_string();
+    // 发送开始创建epub事件
+    let _ = ExportEpubEvent::CreateStart {
+        uuid: create_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        group_title: export_targets[0].chapter_info.group_name.clone(),
+        total: export_targets.len() as u32,
+    }
+    .emit(app);
+    // 如果success为false，drop时发送CreateError事件
+    let mut create_error_event_guard = EpubCreateErrorEventGuard {
+        uuid: create_event_uuid.clone(),
+        app: app.clone(),
+        cancel_token: cancel_token.clone(),
+        success: false,
+    };
+
+    let export_dir = {
+        let first_export_path = &export_targets[0].export_path;
+        first_export_path
+            .parent()
+            .ok_or_eyre(format!("获取`{}`的父目录失败", first_export_path.display()))?
+            .to_path_buf()
+    };
+    // 保证导出目录存在
+    std::fs::create_dir_all(&export_dir)
+        .wrap_err(format!("创建目录`{}`失败", export_dir.display()))?;
+
+    let export_config = EpubExportConfig::new(app, comic);
+
+    // 用来记录创建epub的进度
+    let created_count = Arc::new(AtomicU32::new(0));
+    // 并发处理
+    let current_span = tracing::Span::current();
+    let export_targets = export_targets.into_par_iter();
+    export_targets.try_for_each(|target| -> eyre::Result<()> {
+        cancel_token.check()?;
+
+        let mut chapter_info = target.chapter_info;
+        let epub_path = target.export_path;
+
+        let _enter = current_span.enter();
+
+        let span = tracing::error_span!(
+            "export_epub_rayon",
+            group_name = chapter_info.group_name,
+            chapter_title = chapter_info.chapter_title
+        );
+        let _enter = span.enter();
+
+        // 跳过逻辑
+        let should_skip = match skip_mode {
+            ExportSkipMode::SkipExported if chapter_info.is_epub_exported => true,
+            ExportSkipMode::SkipExisting if epub_path.exists() => true,
+            _ => false,
+        };
+
+        if should_skip {
+            // 更新进度
+            let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            let _ = ExportEpubEvent::CreateProgress {
+                uuid: create_event_uuid.clone(),
+                current,
+            }
+            .emit(app);
+            return Ok(());
+        }
+
+        let imgs = export_config.get_chapter_imgs(&chapter_info)?;
+
+        let metadata = EpubMetadata::new(
+            comic,
+            format!("urn:uuid:{}", chapter_info.chapter_uuid),
+            format!("{} - {}", comic.comic.name, chapter_info.chapter_title),
+            export_config.rtl,
+        );
+        let mut epub_writer = EpubWriter::create(&epub_path, metadata)?;
+        if let Some(cover_path) = &export_config.cover_path {
+            epub_writer.add_cover(cover_path, export_config.export_with_cover)?;
+        }
+        epub_writer.add_chapter(&chapter_info.chapter_title, imgs)?;
+        epub_writer.finish().wrap_err("创建epub失败")?;
+
+        // 更新章节导出状态
+        chapter_info.is_epub_exported = true;
+        chapter_info.save_metadata()?;
+        if let Some(chapter_download_dir) = &chapter_info.chapter_download_dir {
+            let library_index = app.get_library_index();
+            if let Err(err) = library_index.update_chapter_metadata(chapter_download_dir) {
+                let err_title = "更新已下载漫画索引失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
+
+        // 更新创建epub的进度
+        let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        // 发送创建epub进度事件
+        let _ = ExportEpubEvent::CreateProgress {
+            uuid: create_event_uuid.clone(),
+            current,
+        }
+        .emit(app);
+
+        Ok(())
+    })?;
+
+    // 标记为成功，后面drop时就不会发送CreateError事件
+    create_error_event_guard.success = true;
+
+    // 发送创建epub完成事件
+    let _ = ExportEpubEvent::CreateEnd {
+        uuid: create_event_uuid,
+        comic_path_word: comic.comic.path_word.clone(),
+        export_dir,
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+/// 把分组中的所有章节合并为一个EPUB，每个章节在目录中都有对应的条目
+///
+/// 直接读取章节的下载目录，而不是拆开章节EPUB，所以合并不依赖章节EPUB是否存在
+#[instrument(level = "error", skip_all)]
+fn merge_group_epub_files(
+    app: &AppHandle,
+    comic: &Comic,
+    merge_epub_path: &Path,
+    mut export_targets: Vec<ExportTarget>,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let group_path_word = export_targets[0].chapter_info.group_path_word.clone();
+    let group_title = export_targets[0].chapter_info.group_name.clone();
+
+    let merge_event_uuid = uuid::Uuid::new_v4().to_string();
+    // 发送开始合并epub事件
+    let _ = ExportEpubEvent::MergeStart {
+        uuid: merge_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        group_title: group_title.clone(),
+        total: 1,
+    }
+    .emit(app);
+    // 如果success为false，drop时发送MergeError事件
+    let mut merge_error_event_guard = EpubMergeErrorEventGuard {
+        uuid: merge_event_uuid.clone(),
+        app: app.clone(),
+        cancel_token: cancel_token.clone(),
+        success: false,
+    };
+
+    export_targets.sort_by_key(|target| FloatOrd(target.chapter_info.order));
+
+    let merge_epub_dir = merge_epub_path
+        .parent()
+        .ok_or_eyre(format!("获取`{}`的父目录失败", merge_epub_path.display()))?;
+    std::fs::create_dir_all(merge_epub_dir)
+        .wrap_err(format!("创建目录`{}`失败", merge_epub_dir.display()))?;
+
+    let export_config = EpubExportConfig::new(app, comic);
+
+    let metadata = EpubMetadata::new(
+        comic,
+        format!("urn:copymanga:{}:{group_path_word}", comic.comic.path_word),
+        format!("{} - {group_title}", comic.comic.name),
+        export_config.rtl,
+    );
+    let mut epub_writer = EpubWriter::create(merge_epub_path, metadata)?;
+    if let Some(cover_path) = &export_config.cover_path {
+        epub_writer.add_cover(cover_path, export_config.export_with_cover)?;
+    }
+    // 逐个章节写入，内存中最多只有一个章节的图片
+    for target in export_targets {
+        cancel_token.check()?;
+
+        let chapter_info = &target.chapter_info;
+        let imgs = export_config.get_chapter_imgs(chapter_info)?;
+        epub_writer.add_chapter(&chapter_info.chapter_title, imgs)?;
+    }
+    epub_writer.finish().wrap_err("合并epub失败")?;
+
+    // 标记为成功，后面drop时就不会发送MergeError事件
+    merge_error_event_guard.success = true;
+    // 发送合并epub完成事件
+    let _ = ExportEpubEvent::MergeEnd {
+        uuid: merge_event_uuid,
+        comic_path_word: comic.comic.path_word.clone(),
+        export_dir: merge_epub_dir.to_path_buf(),
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+/// EPUB的元数据
+struct EpubMetadata {
+    identifier: String,
+    title: String,
+    authors: Vec<String>,
+    subjects: Vec<String>,
+    description: String,
+    /// 是否从右往左翻页
+    rtl: bool,
+}
+
+impl EpubMetadata {
+    fn new(comic: &Comic, identifier: String, title: String, rtl: bool) -> Self {
+        Self {
+            identifier,
+            title,
+            authors: comic
+                .comic
+                .author
+                .iter()
+                .map(|author| author.name.clone())
+                .collect(),
+            subjects: comic
+                .comic
+                .theme
+                .iter()
+                .map(|theme| theme.name.clone())
+                .collect(),
+            description: comic.comic.brief.clone(),
+            rtl,
+        }
+    }
+}
+
+struct ManifestItem {
+    id: String,
+    href: String,
+    media_type: &'static str,
+    properties: Option<&'static str>,
+}
+
+/// 按顺序把图片写入zip，最后再写入`content.opf`和`nav.xhtml`，生成固定版式的EPUB3
+struct EpubWriter {
+    zip_writer: ZipWriter<File>,
+    epub_path: PathBuf,
+    metadata: EpubMetadata,
+    manifest_items: Vec<ManifestItem>,
+    /// 按阅读顺序排列的页面id
+    spine_item_ids: Vec<String>,
+    /// 目录中的条目，(标题, 页面的href)
+    nav_entries: Vec<(String, String)>,
+    has_cover_image: bool,
+}
+
+impl EpubWriter {
+    #[instrument(level = "error", skip_all, fields(epub_path = %epub_path.display()))]
+    fn create(epub_path: &Path, metadata: EpubMetadata) -> eyre::Result<Self> {
+        let epub_file =
+            File::create(epub_path).wrap_err(format!("创建文件`{}`失败", epub_path.display()))?;
+        let mut zip_writer = ZipWriter::new(epub_file);
+
+        // `mimetype`必须是第一个文件，且不能压缩
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        zip_writer
+            .start_file("mimetype", stored)
+            .wrap_err(format!("在`{}`创建`mimetype`失败", epub_path.display()))?;
+        zip_writer
+            .write_all(b"application/epub+zip")
+            .wrap_err("写入`mimetype`失败")?;
+
+        zip_writer
+            .start_file("META-INF/container.xml", SimpleFileOptions::default())
+            .wrap_err(format!(
+                "在`{}`创建`META-INF/container.xml`失败",
+                epub_path.display()
+            ))?;
+        zip_writer
+            .write_all(CONTAINER_XML.as_bytes())
+            .wrap_err("写入`META-INF/container.xml`失败")?;
+
+        Ok(Self {
+            zip_writer,
+            epub_path: epub_path.to_path_buf(),
+            metadata,
+            manifest_items: vec![],
+            spine_item_ids: vec![],
+            nav_entries: vec![],
+            has_cover_image: false,
+        })
+    }
+
+    /// 写入封面，必须在`add_chapter`之前调用
+    ///
+    /// 封面总是会作为EPUB的封面图，`as_page`为`true`时还会作为第一页
+    fn add_cover(&mut self, cover_path: &Path, as_page: bool) -> eyre::Result<()> {
+        let ext = cover_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_eyre(format!("获取`{}`的扩展名失败", cover_path.display()))?;
+        let img = ExportImg::File {
+            filename: format!("cover.{ext}"),
+            path: cover_path.to_path_buf(),
+        };
+
+        let (img_href, dimensions) = self.write_img(&img, "cover-image", Some("cover-image"))?;
+        self.has_cover_image = true;
+
+        if as_page {
+            let title = self.metadata.title.clone();
+            self.write_page("cover", &title, &img_href, dimensions)?;
+        }
+
+        Ok(())
+    }
+
+    /// 写入一个章节，章节的第一页会作为目录中的条目
+    fn add_chapter(&mut self, chapter_title: &str, imgs: Vec<ExportImg>) -> eyre::Result<()> {
+        for (i, img) in imgs.into_iter().enumerate() {
+            let index = self.spine_item_ids.len() + 1;
+            let (img_href, dimensions) = self.write_img(&img, &format!("img-{index:04}"), None)?;
+            let page_href = self.write_page(
+                &format!("page-{index:04}"),
+                chapter_title,
+                &img_href,
+                dimensions,
+            )?;
+
+            if i == 0 {
+                self.nav_entries
+                    .push((chapter_title.to_string(), page_href));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 写入`content.opf`和`nav.xhtml`，完成EPUB
+    #[instrument(level = "error", skip_all, fields(epub_path = %self.epub_path.display()))]
+    fn finish(mut self) -> eyre::Result<()> {
+        if self.spine_item_ids.is_empty() {
+            return Err(eyre!("没有可以写入`{}`的图片", self.epub_path.display()));
+        }
+        // 目录不能为空，没有章节条目时(比如章节没有图片，只有封面)，用第一页作为目录条目
+        if self.nav_entries.is_empty() {
+            let first_page_href = self
+                .manifest_items
+                .iter()
+                .find(|item| item.id == self.spine_item_ids[0])
+                .map(|item| item.href.clone())
+                .ok_or_eyre("找不到第一页")?;
+            self.nav_entries
+                .push((self.metadata.title.clone(), first_page_href));
+        }
+
+        let nav_xhtml = self.build_nav_xhtml();
+        self.write_file("OEBPS/nav.xhtml", nav_xhtml.as_bytes())?;
+        self.manifest_items.push(ManifestItem {
+            id: "nav".to_string(),
+            href: "nav.xhtml".to_string(),
+            media_type: "application/xhtml+xml",
+            properties: Some("nav"),
+        });
+
+        let content_opf = self.build_content_opf();
+        self.write_file("OEBPS/content.opf", content_opf.as_bytes())?;
+
+        self.zip_writer
+            .finish()
+            .wrap_err(format!("关闭`{}`失败", self.epub_path.display()))?;
+
+        Ok(())
+    }
+
+    /// 写入图片，返回图片相对于`content.opf`的href和图片的宽高
+    fn write_img(
+        &mut self,
+        img: &ExportImg,
+        id: &str,
+        properties: Option<&'static str>,
+    ) -> eyre::Result<(String, (u32, u32))> {
+        let filename = img.filename();
+        let (_, ext) = filename
+            .rsplit_once('.')
+            .ok_or_eyre(format!("`{filename}`没有扩展名"))?;
+        let media_type = match ext.to_lowercase().as_str() {
+            "jpg" | "jpeg" => "image/jpeg",
+            "webp" => "image/webp",
+            "png" => "image/png",
+            _ => return Err(eyre!("`{filename}`的格式不支持写入epub")),
+        };
+        let dimensions = img.dimensions()?;
+
+        let href = format!("images/{id}.{ext}");
+        let zip_path = format!("OEBPS/{href}");
+        self.zip_writer
+            .start_file(zip_path.as_str(), SimpleFileOptions::default())
+            .wrap_err(format!(
+                "在`{}`创建`{zip_path}`失败",
+                self.epub_path.display()
+            ))?;
+        img.write_to(&mut self.zip_writer).wrap_err(format!(
+            "将`{filename}`写入`{}`失败",
+            self.epub_path.display()
+        ))?;
+
+        self.manifest_items.push(ManifestItem {
+            id: id.to_string(),
+            href: href.clone(),
+            media_type,
+            properties,
+        });
+
+        Ok((href, dimensions))
+    }
+
+    /// 写入只包含一张图片的页面，返回页面相对于`content.opf`的href
+    fn write_page(
+        &mut self,
+        id: &str,
+        title: &str,
+        img_href: &str,
+        (width, height): (u32, u32),
+    ) -> eyre::Result<String> {
+        let title = escape_xml(title);
+        let page_xhtml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<title>{title}</title>
+<meta name="viewport" content="width={width}, height={height}"/>
+<style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: {width}px; height: {height}px; }}</style>
+</head>
+<body>
+<img src="../{img_href}" alt=""/>
+</body>
+</html>
+"#
+        );
+
+        let href = format!("pages/{id}.xhtml");
+        self.write_file(&format!("OEBPS/{href}"), page_xhtml.as_bytes())?;
+
+        self.manifest_items.push(ManifestItem {
+            id: id.to_string(),
+            href: href.clone(),
+            media_type: "application/xhtml+xml",
+            properties: None,
+        });
+        self.spine_item_ids.push(id.to_string());
+
+        Ok(href)
+    }
+
+    fn write_file(&mut self, zip_path: &str, data: &[u8]) -> eyre::Result<()> {
+        self.zip_writer
+            .start_file(zip_path, SimpleFileOptions::default())
+            .wrap_err(format!(
+                "在`{}`创建`{zip_path}`失败",
+                self.epub_path.display()
+            ))?;
+        self.zip_writer
+            .write_all(data)
+            .wrap_err(format!("写入`{zip_path}`失败"))?;
+        Ok(())
+    }
+
+    fn build_nav_xhtml(&self) -> String {
+        let nav_items = self
+            .nav_entries
+            .iter()
+            .map(|(title, href)| format!(r#"<li><a href="{href}">{}</a></li>"#, escape_xml(title)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<title>目录</title>
+</head>
+<body>
+<nav epub:type="toc" id="toc">
+<h1>目录</h1>
+<ol>
+{nav_items}
+</ol>
+</nav>
+</body>
+</html>
+"#
+        )
+    }
+
+    fn build_content_opf(&self) -> String {
+        let metadata = &self.metadata;
+
+        let mut metadata_lines = vec![
+            format!(
+                r#"<dc:identifier id="book-id">{}</dc:identifier>"#,
+                escape_xml(&metadata.identifier)
+            ),
+            format!("<dc:title>{}</dc:title>", escape_xml(&metadata.title)),
+            "<dc:language>zh</dc:language>".to_string(),
+            "<dc:publisher>拷贝漫画</dc:publisher>".to_string(),
+        ];
+        for author in &metadata.authors {
+            metadata_lines.push(format!("<dc:creator>{}</dc:creator>", escape_xml(author)));
+        }
+        for subject in &metadata.subjects {
+            metadata_lines.push(format!("<dc:subject>{}</dc:subject>", escape_xml(subject)));
+        }
+        if !metadata.description.is_empty() {
+            metadata_lines.push(format!(
+                "<dc:description>{}</dc:description>",
+                escape_xml(&metadata.description)
+            ));
+        }
+        let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+        metadata_lines.push(format!(
+            r#"<meta property="dcterms:modified">{modified}</meta>"#
+        ));
+        // 固定版式
+        metadata_lines
+            .push(r#"<meta property="rendition:layout">pre-paginated</meta>"#.to_string());
+        metadata_lines.push(r#"<meta property="rendition:orientation">auto</meta>"#.to_string());
+        metadata_lines.push(r#"<meta property="rendition:spread">landscape</meta>"#.to_string());
+        if self.has_cover_image {
+            // 兼容只认EPUB2封面写法的阅读器
+            metadata_lines.push(r#"<meta name="cover" content="cover-image"/>"#.to_string());
+        }
+
+        let manifest_lines = self
+            .manifest_items
+            .iter()
+            .map(|item| {
+                let properties = item
+                    .properties
+                    .map(|properties| format!(r#" properties="{properties}""#))
+                    .unwrap_or_default();
+                format!(
+                    r#"<item id="{}" href="{}" media-type="{}"{properties}/>"#,
+                    item.id, item.href, item.media_type
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let spine_lines = self
+            .spine_item_ids
+            .iter()
+            .map(|id| format!(r#"<itemref idref="{id}"/>"#))
+            .collect::<Vec<_>>();
+
+        let page_progression_direction = if metadata.rtl { "rtl" } else { "ltr" };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" prefix="rendition: http://www.idpf.org/vocab/rendition/#" xml:lang="zh">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{}
+</metadata>
+<manifest>
+{}
+</manifest>
+<spine page-progression-direction="{page_progression_direction}">
+{}
+</spine>
+</package>
+"#,
+            metadata_lines.join("\n"),
+            manifest_lines.join("\n"),
+            spine_lines.join("\n"),
+        )
+    }
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
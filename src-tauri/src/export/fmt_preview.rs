@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use float_ord::FloatOrd;
+use tauri::AppHandle;
+use tracing::instrument;
+
+use crate::{
+    config::Config,
+    export::{
+        get_raw_chapter_export_paths, validate_export_dir_fmt, validate_merge_comic_fmt,
+        validate_merge_fmt, ExportFormat, MergeFmtParams, MAX_EXPORT_FILENAME_BYTES,
+    },
+    extensions::AppHandleExt,
+    types::{
+        disambiguate_chapter_paths, ChapterDirFmtParams, ChapterInfo, Comic, ComicDirFmtParams,
+        FmtKind, FmtPathCollision, FmtPathProblem, FmtPreview, FmtPreviewPath,
+    },
+    utils,
+};
+
+/// 模板生成的一个路径
+struct PreviewEntry {
+    /// 章节模板为章节所在的分组，其他模板为空，用于按分组抽样
+    group_path_word: String,
+    source: String,
+    /// 模板直接生成的路径，用来检查冲突
+    path: PathBuf,
+    /// 实际会使用的路径，冲突的章节路径会用`disambiguate_chapter_paths`区分
+    final_path: PathBuf,
+}
+
+/// 用`comic`预览模板`fmt`会生成的路径，不会读写任何文件
+///
+/// 这里用的是`comic`的所有章节，而不只是已下载的章节，所以未下载的漫画也能预览
+#[instrument(
+    level = "error",
+    skip_all,
+    fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name, fmt_kind = ?fmt_kind, fmt)
+)]
+pub fn preview_fmt(
+    app: &AppHandle,
+    comic: &Comic,
+    fmt_kind: FmtKind,
+    fmt: &str,
+    sample_count: usize,
+) -> eyre::Result<FmtPreview> {
+    let config = app.get_config().read().clone();
+
+    let (base_dir, entries) = match fmt_kind {
+        FmtKind::ComicDir => (
+            config.download_dir.clone(),
+            get_comic_dir_entries(&config, comic, fmt)?,
+        ),
+        FmtKind::ChapterDir => get_chapter_dir_entries(&config, comic, fmt)?,
+        FmtKind::PdfExportDir => get_export_entries(&config, comic, fmt, ExportFormat::Pdf)?,
+        FmtKind::CbzExportDir => get_export_entries(&config, comic, fmt, ExportFormat::Cbz)?,
+        FmtKind::EpubExportDir => get_export_entries(&config, comic, fmt, ExportFormat::Epub)?,
+        FmtKind::LongImgExportDir => {
+            let format = ExportFormat::LongImg(config.long_img_format);
+            get_export_entries(&config, comic, fmt, format)?
+        }
+        FmtKind::MergePdf => {
+            validate_merge_fmt(fmt, "合并pdf目录格式")?;
+            get_merge_entries(&config, comic, fmt, ExportFormat::Pdf)?
+        }
+        FmtKind::MergeComicPdf => {
+            validate_merge_comic_fmt(fmt, "所有分组合并pdf目录格式")?;
+            let text_conversion = config.fmt_text_conversion;
+            let fmt_params = MergeFmtParams::new(comic, String::new(), "", text_conversion);
+            let path = fmt_params.to_merge_path(&config.export_dir, fmt, ExportFormat::Pdf)?;
+            let entry = PreviewEntry {
+                group_path_word: String::new(),
+                source: comic.comic.name.clone(),
+                final_path: path.clone(),
+                path,
+            };
+            (config.export_dir.clone(), vec![entry])
+        }
+        FmtKind::MergeCbz => {
+            validate_merge_fmt(fmt, "合并cbz目录格式")?;
+            get_merge_entries(&config, comic, fmt, ExportFormat::Cbz)?
+        }
+        FmtKind::MergeEpub => {
+            validate_merge_fmt(fmt, "合并epub目录格式")?;
+            get_merge_entries(&config, comic, fmt, ExportFormat::Epub)?
+        }
+    };
+
+    let mut group_sample_counts: HashMap<&str, usize> = HashMap::new();
+    let mut samples = Vec::new();
+    for entry in &entries {
+        let group_sample_count = group_sample_counts
+            .entry(&entry.group_path_word)
+            .or_default();
+        if *group_sample_count < sample_count {
+            *group_sample_count += 1;
+            samples.push(FmtPreviewPath {
+                source: entry.source.clone(),
+                path: entry.final_path.to_string_lossy().to_string(),
+            });
+        }
+    }
+
+    Ok(FmtPreview {
+        samples,
+        total_count: u32::try_from(entries.len()).unwrap_or(u32::MAX),
+        collisions: find_collisions(&entries),
+        problems: find_problems(&base_dir, &entries),
+    })
+}
+
+fn get_comic_dir_entries(
+    config: &Config,
+    comic: &Comic,
+    comic_dir_fmt: &str,
+) -> eyre::Result<Vec<PreviewEntry>> {
+    let fmt_params = ComicDirFmtParams::new(&comic.comic, config.fmt_text_conversion);
+    let path =
+        Comic::get_comic_download_dir_by_fmt(&config.download_dir, comic_dir_fmt, &fmt_params)?;
+    let entry = PreviewEntry {
+        group_path_word: String::new(),
+        source: comic.comic.name.clone(),
+        final_path: path.clone(),
+        path,
+    };
+    Ok(vec![entry])
+}
+
+/// 章节目录在漫画目录下，所以返回的第一个值是用配置中的`comic_dir_fmt`生成的漫画目录
+fn get_chapter_dir_entries(
+    config: &Config,
+    comic: &Comic,
+    chapter_dir_fmt: &str,
+) -> eyre::Result<(PathBuf, Vec<PreviewEntry>)> {
+    let comic_fmt_params = ComicDirFmtParams::new(&comic.comic, config.fmt_text_conversion);
+    let comic_download_dir = Comic::get_comic_download_dir_by_fmt(
+        &config.download_dir,
+        &config.comic_dir_fmt,
+        &comic_fmt_params,
+    )?;
+    let chapter_indexes = comic.comic.get_chapter_indexes();
+
+    let mut chapter_dirs = Vec::new();
+    for chapter_info in comic.comic.groups.values().flatten() {
+        let chapter_index = chapter_indexes
+            .get(&chapter_info.chapter_uuid)
+            .cloned()
+            .unwrap_or_default();
+        let fmt_params = ChapterDirFmtParams::new(
+            comic_fmt_params.clone(),
+            chapter_info,
+            chapter_index,
+            config.fmt_text_conversion,
+        );
+        let path = ChapterInfo::get_chapter_download_dir_by_fmt(
+            &comic_download_dir,
+            chapter_dir_fmt,
+            &fmt_params,
+        )?;
+        chapter_dirs.push((chapter_info, path));
+    }
+    let entries = get_chapter_entries(chapter_dirs, "", utils::MAX_FILENAME_BYTES);
+
+    Ok((comic_download_dir, entries))
+}
+
+fn get_export_entries(
+    config: &Config,
+    comic: &Comic,
+    export_dir_fmt: &str,
+    format: ExportFormat,
+) -> eyre::Result<(PathBuf, Vec<PreviewEntry>)> {
+    validate_export_dir_fmt(export_dir_fmt)?;
+
+    let export_paths = get_raw_chapter_export_paths(
+        comic,
+        &config.export_dir,
+        export_dir_fmt,
+        format,
+        config.fmt_text_conversion,
+    )?;
+    let entries = get_chapter_entries(export_paths, format.extension(), MAX_EXPORT_FILENAME_BYTES);
+
+    Ok((config.export_dir.clone(), entries))
+}
+
+/// 每个分组一个合并文件，只预览拆分后的第一部分
+fn get_merge_entries(
+    config: &Config,
+    comic: &Comic,
+    merge_fmt: &str,
+    format: ExportFormat,
+) -> eyre::Result<(PathBuf, Vec<PreviewEntry>)> {
+    let text_conversion = config.fmt_text_conversion;
+    let mut entries = Vec::new();
+    for (group_path_word, chapter_infos) in get_sorted_groups(comic) {
+        let group_title = chapter_infos
+            .first()
+            .map(|chapter_info| chapter_info.group_name.clone())
+            .unwrap_or_else(|| group_path_word.to_string());
+        let fmt_params = MergeFmtParams::new(
+            comic,
+            group_path_word.to_string(),
+            &group_title,
+            text_conversion,
+        );
+        let path = fmt_params.to_merge_path(&config.export_dir, merge_fmt, format)?;
+        entries.push(PreviewEntry {
+            group_path_word: String::new(),
+            source: group_title,
+            final_path: path.clone(),
+            path,
+        });
+    }
+
+    Ok((config.export_dir.clone(), entries))
+}
+
+/// 按`group_path_word`排序的分组，`default`分组排在最前面
+fn get_sorted_groups(comic: &Comic) -> Vec<(&str, &Vec<ChapterInfo>)> {
+    let mut groups: Vec<(&str, &Vec<ChapterInfo>)> = comic
+        .comic
+        .groups
+        .iter()
+        .map(|(group_path_word, chapter_infos)| (group_path_word.as_str(), chapter_infos))
+        .collect();
+    groups.sort_by_key(|(group_path_word, _)| (*group_path_word != "default", *group_path_word));
+    groups
+}
+
+/// 按分组和`order`排序，并像实际下载或导出时一样区分冲突的路径
+fn get_chapter_entries(
+    chapter_paths: Vec<(&ChapterInfo, PathBuf)>,
+    ext: &str,
+    max_bytes: usize,
+) -> Vec<PreviewEntry> {
+    let (chapter_infos, paths): (Vec<&ChapterInfo>, Vec<PathBuf>) =
+        chapter_paths.into_iter().unzip();
+    let mut final_paths = paths.clone();
+    disambiguate_chapter_paths(&chapter_infos, &mut final_paths, ext, max_bytes);
+
+    let mut entries: Vec<(&ChapterInfo, PreviewEntry)> = chapter_infos
+        .into_iter()
+        .zip(paths.into_iter().zip(final_paths))
+        .map(|(chapter_info, (path, final_path))| {
+            let entry = PreviewEntry {
+                group_path_word: chapter_info.group_path_word.clone(),
+                source: get_chapter_source(chapter_info),
+                path,
+                final_path,
+            };
+            (chapter_info, entry)
+        })
+        .collect();
+    entries.sort_by_key(|(chapter_info, _)| {
+        let group_path_word = chapter_info.group_path_word.as_str();
+        (
+            group_path_word != "default",
+            group_path_word,
+            FloatOrd(chapter_info.order),
+        )
+    });
+
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn get_chapter_source(chapter_info: &ChapterInfo) -> String {
+    format!(
+        "{} - {}",
+        chapter_info.group_name, chapter_info.chapter_title
+    )
+}
+
+/// Windows和macOS默认不区分大小写，所以只有大小写不同的路径也算冲突
+fn find_collisions(entries: &[PreviewEntry]) -> Vec<FmtPathCollision> {
+    utils::find_path_collisions(entries.iter().map(|entry| entry.path.as_path()))
+        .into_iter()
+        .map(|indexes| FmtPathCollision {
+            path: entries[indexes[0]].path.to_string_lossy().to_string(),
+            sources: indexes
+                .into_iter()
+                .map(|i| entries[i].source.clone())
+                .collect(),
+        })
+        .collect()
+}
+
+/// 只检查`base_dir`之后由模板生成的部分
+fn find_problems(base_dir: &Path, entries: &[PreviewEntry]) -> Vec<FmtPathProblem> {
+    let mut problems = Vec::new();
+    for entry in entries {
+        let relative_path = entry
+            .final_path
+            .strip_prefix(base_dir)
+            .unwrap_or(&entry.final_path);
+        for component in relative_path.iter() {
+            let component = component.to_string_lossy();
+            if let Some(reason) = check_path_component(&component) {
+                problems.push(FmtPathProblem {
+                    source: entry.source.clone(),
+                    path: entry.final_path.to_string_lossy().to_string(),
+                    component: component.to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// 目录名或文件名有问题时返回原因
+fn check_path_component(component: &str) -> Option<String> {
+    let byte_len = component.len();
+    let max_bytes = utils::MAX_FILENAME_BYTES;
+    if byte_len > max_bytes {
+        return Some(format!(
+            "长度为{byte_len}字节，超过了大部分文件系统允许的{max_bytes}字节"
+        ));
+    }
+
+    if utils::is_windows_reserved_name(component) {
+        return Some(format!("`{component}`是Windows的保留名，无法创建"));
+    }
+
+    if component.ends_with('.') {
+        return Some("以`.`结尾，Windows会自动去掉末尾的`.`".to_string());
+    }
+
+    if component.chars().any(char::is_control) {
+        return Some("包含控制字符".to_string());
+    }
+
+    None
+}
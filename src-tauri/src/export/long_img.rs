@@ -0,0 +1,448 @@
+use std::{
+    collections::HashMap,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicU32, Arc},
+};
+
+use eyre::{eyre, OptionExt, WrapErr};
+use image::{imageops, RgbImage};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tauri::AppHandle;
+use tauri_specta::Event;
+use tracing::instrument;
+
+use crate::{
+    config::ExportSkipMode,
+    events::ExportLongImgEvent,
+    export::{
+        build_grouped_export_targets, get_downloaded_chapters, get_downloaded_chapters_by_uuids,
+        get_export_imgs, get_image_paths, webtoon, ExportCancelToken, ExportFormat, ExportImg,
+        ExportTarget,
+    },
+    extensions::{AppHandleExt, EyreReportToMessage},
+    types::{ChapterInfo, Comic, LongImgFormat},
+};
+
+/// 每张长图最多包含的像素数，用来限制拼接时的内存占用
+///
+/// 以RGB格式拼接，每张长图占用的内存不超过`LONG_IMG_MAX_PIXELS * 3`字节
+const LONG_IMG_MAX_PIXELS: u64 = 48 * 1024 * 1024;
+
+struct LongImgErrorEventGuard {
+    uuid: String,
+    app: AppHandle,
+    cancel_token: ExportCancelToken,
+    success: bool,
+}
+
+impl Drop for LongImgErrorEventGuard {
+    fn drop(&mut self) {
+        if self.success {
+            return;
+        }
+
+        let uuid = self.uuid.clone();
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportLongImgEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportLongImgEvent::Error { uuid }.emit(&self.app);
+        }
+    }
+}
+
+/// 公开接口：导出全部已下载章节为长图
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn long_img(app: &AppHandle, comic: &Comic) -> eyre::Result<()> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取配置
+        let skip_mode = app.get_config().read().export_skip_mode;
+
+        // 获取已下载章节
+        let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+
+        // 调用内部实现
+        export_long_img_internal(app, comic, downloaded_chapters, skip_mode, cancel_token)
+    })
+}
+
+/// 公开接口：导出指定已下载章节为长图
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn long_img_chapters(
+    app: &AppHandle,
+    comic: &Comic,
+    chapter_uuids: Vec<String>,
+) -> eyre::Result<()> {
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取指定章节（用户主动选择，不跳过）
+        let downloaded_chapters =
+            get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
+
+        // 调用内部实现
+        export_long_img_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            ExportSkipMode::None, // 用户主动选择，不跳过
+            cancel_token,
+        )
+    })
+}
+
+/// 内部实现：导出长图
+#[instrument(level = "error", skip_all, fields(skip_mode = ?skip_mode))]
+fn export_long_img_internal(
+    app: &AppHandle,
+    comic: &Comic,
+    downloaded_chapters: Vec<ChapterInfo>,
+    skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let (long_img_format, create_long_img_concurrency) = {
+        let config = app.get_config();
+        let config = config.read();
+        (config.long_img_format, config.create_long_img_concurrency)
+    };
+
+    let grouped_export_targets = build_grouped_export_targets(
+        app,
+        comic,
+        downloaded_chapters,
+        ExportFormat::LongImg(long_img_format),
+    )?;
+    if grouped_export_targets.is_empty() {
+        return Ok(());
+    }
+
+    // 拼接长图很吃内存，用单独的线程池限制同时拼接的章节数
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(create_long_img_concurrency)
+        .build()
+        .wrap_err("rayon线程池创建失败")?;
+
+    thread_pool.install(|| -> eyre::Result<()> {
+        for (_group_path_word, export_targets) in grouped_export_targets {
+            if export_targets.is_empty() {
+                continue;
+            }
+
+            create_group_long_img_files(
+                app,
+                comic,
+                export_targets,
+                long_img_format,
+                skip_mode,
+                cancel_token,
+            )?;
+        }
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[instrument(level = "error", skip_all, fields(long_img_format = ?long_img_format))]
+fn create_group_long_img_files(
+    app: &AppHandle,
+    comic: &Comic,
+    export_targets: Vec<ExportTarget>,
+    long_img_format: LongImgFormat,
+    skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<()> {
+    let create_event_uuid = uuid::Uuid::new_v4().to_string();
+    // 发送开始创建长图事件
+    let _ = ExportLongImgEvent::Start {
+        uuid: create_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        group_title: export_targets[0].chapter_info.group_name.clone(),
+        total: export_targets.len() as u32,
+    }
+    .emit(app);
+    // 如果success为false，drop时发送Error事件
+    let mut create_error_event_guard = LongImgErrorEventGuard {
+        uuid: create_event_uuid.clone(),
+        app: app.clone(),
+        cancel_token: cancel_token.clone(),
+        success: false,
+    };
+
+    let export_dir = {
+        let first_export_path = &export_targets[0].export_path;
+        first_export_path
+            .parent()
+            .ok_or_eyre(format!("获取`{}`的父目录失败", first_export_path.display()))?
+            .to_path_buf()
+    };
+    // 保证导出目录存在
+    std::fs::create_dir_all(&export_dir)
+        .wrap_err(format!("创建目录`{}`失败", export_dir.display()))?;
+
+    let (export_with_cover, img_process_config) = {
+        let config = app.get_config();
+        let config = config.read();
+        (config.export_with_cover, config.img_process.clone())
+    };
+    // 如果启用了封面，则把封面拼接在每张长图的最上面
+    let cover_path = if export_with_cover {
+        comic.get_cover_path()
+    } else {
+        None
+    };
+
+    // 用来记录创建长图的进度
+    let created_count = Arc::new(AtomicU32::new(0));
+    // 并发处理
+    let current_span = tracing::Span::current();
+    let export_targets = export_targets.into_par_iter();
+    export_targets.try_for_each(|target| -> eyre::Result<()> {
+        cancel_token.check()?;
+
+        let mut chapter_info = target.chapter_info;
+        let long_img_path = target.export_path;
+
+        let _enter = current_span.enter();
+
+        let span = tracing::error_span!(
+            "export_long_img_rayon",
+            group_name = chapter_info.group_name,
+            chapter_title = chapter_info.chapter_title
+        );
+        let _enter = span.enter();
+
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+
+        // 跳过逻辑
+        let should_skip = match skip_mode {
+            ExportSkipMode::SkipExported if chapter_info.is_long_img_exported => true,
+            ExportSkipMode::SkipExisting
+                if long_img_path.exists() || get_part_path(&long_img_path, 1)?.exists() =>
+            {
+                true
+            }
+            _ => false,
+        };
+
+        if should_skip {
+            // 更新进度
+            let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            let _ = ExportLongImgEvent::Progress {
+                uuid: create_event_uuid.clone(),
+                current,
+            }
+            .emit(app);
+            return Ok(());
+        }
+
+        let image_paths = get_image_paths(chapter_download_dir).wrap_err(format!(
+            "获取`{}`中的图片失败",
+            chapter_download_dir.display()
+        ))?;
+        // 长图本身就是竖直拼接的，不需要按条漫重新切分
+        let imgs = get_export_imgs(
+            cover_path.as_deref(),
+            image_paths,
+            &img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            None,
+        )?;
+
+        create_long_img_files(imgs, long_img_format, &long_img_path)?;
+
+        // 更新章节导出状态
+        chapter_info.is_long_img_exported = true;
+        chapter_info.save_metadata()?;
+        let library_index = app.get_library_index();
+        if let Err(err) = library_index.update_chapter_metadata(chapter_download_dir) {
+            let err_title = "更新已下载漫画索引失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
+
+        // 更新创建长图的进度
+        let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        // 发送创建长图进度事件
+        let _ = ExportLongImgEvent::Progress {
+            uuid: create_event_uuid.clone(),
+            current,
+        }
+        .emit(app);
+
+        Ok(())
+    })?;
+
+    // 标记为成功，后面drop时就不会发送Error事件
+    create_error_event_guard.success = true;
+
+    // 发送创建长图完成事件
+    let _ = ExportLongImgEvent::End {
+        uuid: create_event_uuid,
+        comic_path_word: comic.comic.path_word.clone(),
+        export_dir,
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+/// 把`imgs`竖直拼接成长图，保存到`long_img_path`
+///
+/// 如果长图的高度超过了格式允许的最大值或`LONG_IMG_MAX_PIXELS`，会平均拆分成多张，
+/// 保存为`{stem}_01.{ext}`、`{stem}_02.{ext}`...
+///
+/// 先只读取图片头规划好每张长图的尺寸，再逐张解码图片写入画布，
+/// 所以内存中最多只有一张长图的画布和一张解码后的图片
+#[allow(clippy::cast_possible_truncation)]
+#[instrument(level = "error", skip_all, fields(long_img_path = %long_img_path.display()))]
+fn create_long_img_files(
+    imgs: Vec<ExportImg>,
+    format: LongImgFormat,
+    long_img_path: &Path,
+) -> eyre::Result<()> {
+    let dimensions = imgs
+        .iter()
+        .map(ExportImg::dimensions)
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let width = get_long_img_width(&dimensions)
+        .ok_or_eyre("没有可以拼接的图片")?
+        .min(format.max_dimension());
+    let total_height: u64 = dimensions
+        .iter()
+        .map(|&(src_width, src_height)| {
+            u64::from(webtoon::scaled_height(src_width, src_height, width))
+        })
+        .sum();
+    if total_height == 0 {
+        return Err(eyre!("没有可以拼接的图片"));
+    }
+    // 每张长图的高度既不能超过格式允许的最大值，也不能超过内存限制
+    let max_part_height = u64::from(format.max_dimension())
+        .min(LONG_IMG_MAX_PIXELS / u64::from(width))
+        .max(1);
+    let part_count = total_height.div_ceil(max_part_height);
+    // 平均拆分，避免最后一张长图特别短
+    let part_height = total_height.div_ceil(part_count) as u32;
+
+    remove_old_long_img_files(long_img_path)?;
+    let get_save_path = |part_index: u32| -> eyre::Result<PathBuf> {
+        if part_count == 1 {
+            Ok(long_img_path.to_path_buf())
+        } else {
+            get_part_path(long_img_path, part_index)
+        }
+    };
+
+    let mut part_index = 0;
+    let mut remaining_height = total_height;
+    let mut canvas = RgbImage::new(width, u64::from(part_height).min(remaining_height) as u32);
+    let mut filled_height = 0;
+    for img in imgs {
+        let filename = img.filename().to_string();
+        let img_data = img.into_data()?;
+        let img = image::load_from_memory(&img_data).wrap_err(format!("解码`{filename}`失败"))?;
+        let img = webtoon::resize_to_width(img, width).to_rgb8();
+
+        let mut src_y = 0;
+        while src_y < img.height() {
+            if canvas.height() == 0 {
+                // 图片头记录的尺寸与实际不一致时，规划好的高度会提前用完，剩下的部分单独作为一张
+                canvas = RgbImage::new(width, img.height() - src_y);
+            }
+
+            let rows = (img.height() - src_y).min(canvas.height() - filled_height);
+            let src = imageops::crop_imm(&img, 0, src_y, width, rows);
+            imageops::replace(&mut canvas, &*src, 0, i64::from(filled_height));
+            src_y += rows;
+            filled_height += rows;
+
+            if filled_height < canvas.height() {
+                continue;
+            }
+            // 画布已填满，保存这张长图，然后换一张新的画布
+            part_index += 1;
+            remaining_height = remaining_height.saturating_sub(u64::from(canvas.height()));
+            let next_height = u64::from(part_height).min(remaining_height) as u32;
+            let part = std::mem::replace(&mut canvas, RgbImage::new(width, next_height));
+            save_long_img_part(&part, format, &get_save_path(part_index)?)?;
+            filled_height = 0;
+        }
+    }
+    // 图片头记录的尺寸与实际不一致时，最后一张画布可能没有填满
+    if filled_height > 0 {
+        part_index += 1;
+        let part = imageops::crop_imm(&canvas, 0, 0, width, filled_height).to_image();
+        save_long_img_part(&part, format, &get_save_path(part_index)?)?;
+    }
+
+    Ok(())
+}
+
+#[instrument(level = "error", skip_all, fields(save_path = %save_path.display()))]
+fn save_long_img_part(
+    part: &RgbImage,
+    format: LongImgFormat,
+    save_path: &Path,
+) -> eyre::Result<()> {
+    let file = std::fs::File::create(save_path)
+        .wrap_err(format!("创建文件`{}`失败", save_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    part.write_to(&mut writer, format.to_image_format())
+        .wrap_err(format!("保存`{}`失败", save_path.display()))?;
+    Ok(())
+}
+
+/// 获取长图的宽度，取出现次数最多的宽度，次数相同时取较宽的
+fn get_long_img_width(dimensions: &[(u32, u32)]) -> Option<u32> {
+    let mut width_counts: HashMap<u32, usize> = HashMap::new();
+    for &(width, _) in dimensions {
+        if width > 0 {
+            *width_counts.entry(width).or_default() += 1;
+        }
+    }
+
+    width_counts
+        .into_iter()
+        .max_by_key(|&(width, count)| (count, width))
+        .map(|(width, _)| width)
+}
+
+/// 获取第`part_index`张长图的路径，`{stem}_01.{ext}`、`{stem}_02.{ext}`...
+fn get_part_path(long_img_path: &Path, part_index: u32) -> eyre::Result<PathBuf> {
+    let stem = long_img_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_eyre(format!("获取`{}`的文件名失败", long_img_path.display()))?;
+    let ext = long_img_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_eyre(format!("获取`{}`的扩展名失败", long_img_path.display()))?;
+    Ok(long_img_path.with_file_name(format!("{stem}_{part_index:02}.{ext}")))
+}
+
+/// 删除之前导出的长图，避免拆分数量变少时残留旧的长图
+fn remove_old_long_img_files(long_img_path: &Path) -> eyre::Result<()> {
+    if long_img_path.exists() {
+        std::fs::remove_file(long_img_path)
+            .wrap_err(format!("删除`{}`失败", long_img_path.display()))?;
+    }
+
+    for part_index in 1.. {
+        let part_path = get_part_path(long_img_path, part_index)?;
+        if !part_path.exists() {
+            break;
+        }
+        std::fs::remove_file(&part_path).wrap_err(format!("删除`{}`失败", part_path.display()))?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,4 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    io::Read,
     path::{Path, PathBuf},
     sync::{atomic::AtomicU32, Arc},
 };
@@ -9,23 +7,28 @@ use eyre::{eyre, OptionExt, WrapErr};
 use float_ord::FloatOrd;
 use lopdf::{
     content::{Content, Operation},
-    dictionary, Bookmark, Document, Object, Stream,
+    dictionary, Document, Object, ObjectId, Stream,
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use serde::Serialize;
 use tauri::AppHandle;
 use tauri_specta::Event;
 use tracing::instrument;
 
 use crate::{
-    config::ExportSkipMode,
+    config::{ExportSkipMode, PdfConfig},
     events::ExportPdfEvent,
     export::{
-        build_grouped_export_targets, contains_any_field, get_downloaded_chapters,
-        get_downloaded_chapters_by_uuids, get_image_paths, ComicExportLockGuard, ExportFormat,
-        ExportTarget, EXPORT_FMT_GROUP_FIELDS,
+        build_grouped_export_targets, get_downloaded_chapters, get_downloaded_chapters_by_uuids,
+        get_export_imgs, get_image_paths, get_temp_export_path,
+        pdf_front_matter::PdfFrontMatter,
+        pdf_manifest::{MergedPdfChapter, MergedPdfManifest, MergedPdfPart},
+        pdf_metadata::PdfMetadata,
+        pdf_writer::PdfStreamWriter,
+        persist_temp_export_file, validate_merge_comic_fmt, validate_merge_fmt, ExportCancelToken,
+        ExportFormat, ExportImg, ExportTarget, MergeFmtParams,
     },
-    extensions::AppHandleExt,
+    extensions::{AppHandleExt, EyreReportToMessage},
+    img_process,
     types::{ChapterInfo, Comic},
     utils,
 };
@@ -33,6 +36,7 @@ use crate::{
 struct PdfCreateErrorEventGuard {
     uuid: String,
     app: AppHandle,
+    cancel_token: ExportCancelToken,
     success: bool,
 }
 
@@ -43,13 +47,19 @@ impl Drop for PdfCreateErrorEventGuard {
         }
 
         let uuid = self.uuid.clone();
-        let _ = ExportPdfEvent::CreateError { uuid }.emit(&self.app);
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportPdfEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportPdfEvent::CreateError { uuid }.emit(&self.app);
+        }
     }
 }
 
 struct PdfMergeErrorEventGuard {
     uuid: String,
     app: AppHandle,
+    cancel_token: ExportCancelToken,
     success: bool,
 }
 
@@ -60,7 +70,12 @@ impl Drop for PdfMergeErrorEventGuard {
         }
 
         let uuid = self.uuid.clone();
-        let _ = ExportPdfEvent::MergeError { uuid }.emit(&self.app);
+        // 被取消时不算出错
+        if self.cancel_token.is_cancelled() {
+            let _ = ExportPdfEvent::Cancelled { uuid }.emit(&self.app);
+        } else {
+            let _ = ExportPdfEvent::MergeError { uuid }.emit(&self.app);
+        }
     }
 }
 
@@ -69,39 +84,28 @@ impl Drop for PdfMergeErrorEventGuard {
 #[allow(clippy::too_many_lines)]
 #[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
 pub fn pdf(app: &AppHandle, comic: &Comic) -> eyre::Result<()> {
-    let comic_path_word = &comic.comic.path_word;
-    let comic_title = &comic.comic.name;
-    let export_lock = app.get_export_lock().inner().clone();
-
-    // 检查导出锁
-    if !export_lock.try_acquire(comic_path_word) {
-        return Err(eyre!("漫画`{comic_title}`正在导出，请稍后再试"));
-    }
-
-    let _guard = ComicExportLockGuard {
-        lock: export_lock.clone(),
-        path_word: comic_path_word.clone(),
-    };
-
-    // 获取配置
-    let (skip_mode, enable_merge) = {
-        let config = app.get_config().inner().read();
-
-        let skip_mode = config.export_skip_mode;
-        let enable_merge = if skip_mode == ExportSkipMode::SkipExported {
-            false
-        } else {
-            config.enable_merge_pdf
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取配置，跳过曾导出过的章节时会增量合并
+        let (skip_mode, enable_merge) = {
+            let config = app.get_config().inner().read();
+            (config.export_skip_mode, config.enable_merge_pdf)
         };
 
-        (skip_mode, enable_merge)
-    };
-
-    // 获取已下载章节
-    let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
-
-    // 调用内部实现
-    export_pdf_internal(app, comic, downloaded_chapters, skip_mode, enable_merge)
+        // 获取已下载章节
+        let downloaded_chapters = get_downloaded_chapters(&comic.comic.groups);
+
+        // 调用内部实现
+        export_pdf_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            skip_mode,
+            enable_merge,
+            cancel_token,
+        )
+    })
 }
 
 /// 公开接口：导出指定已下载章节为PDF
@@ -113,31 +117,169 @@ pub fn pdf_chapters(
     comic: &Comic,
     chapter_uuids: Vec<String>,
 ) -> eyre::Result<()> {
-    let comic_path_word = &comic.comic.path_word;
-    let comic_title = &comic.comic.name;
-    let export_lock = app.get_export_lock().inner().clone();
+    let export_queue = app.get_export_queue().inner().clone();
+    // 同一漫画的导出任务排队执行
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        // 获取指定章节（用户主动选择，不跳过，不合并）
+        let downloaded_chapters =
+            get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
+
+        // 调用内部实现
+        export_pdf_internal(
+            app,
+            comic,
+            downloaded_chapters,
+            ExportSkipMode::None, // 用户主动选择，不跳过
+            false,                // 选择性导出，不合并
+            cancel_token,
+        )
+    })
+}
 
-    // 检查导出锁
-    if !export_lock.try_acquire(comic_path_word) {
-        return Err(eyre!("漫画`{comic_title}`正在导出，请稍后再试"));
-    }
+/// 估算PDF大小时最多从多少个章节中抽取样本重新压缩
+const ESTIMATE_SAMPLE_COUNT: usize = 8;
+/// 每页除图片外的额外开销(页面字典、内容流等)，单位为字节
+const PAGE_OVERHEAD_BYTES: u64 = 512;
+
+/// 公开接口：按当前配置估算指定章节导出为PDF后的总大小，单位为字节
+///
+/// 所有章节只统计原图的数量和文件大小，不读取图片。
+/// 只均匀抽取部分章节，与导出时一样拼接条漫、处理图片，按处理前后的页数和大小推算所有章节，
+/// 重新压缩图片时也只实际压缩这些章节中的一页，按压缩比推算，
+/// 所以结果只是估算值，不包括合并PDF的大小
+///
+/// 与导出任务一样排队执行，可以用`cancel_export`取消，取消时返回`0`
+#[instrument(level = "error", skip_all, fields(comic_uuid = comic.comic.uuid, comic_title = comic.comic.name))]
+pub fn estimate_pdf_size(
+    app: &AppHandle,
+    comic: &Comic,
+    chapter_uuids: &[String],
+) -> eyre::Result<u64> {
+    let export_queue = app.get_export_queue().inner().clone();
+    export_queue.run(&comic.comic.path_word, |cancel_token| {
+        estimate_pdf_size_internal(app, comic, chapter_uuids, cancel_token)
+    })
+}
 
-    let _guard = ComicExportLockGuard {
-        lock: export_lock.clone(),
-        path_word: comic_path_word.clone(),
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_sign_loss)]
+fn estimate_pdf_size_internal(
+    app: &AppHandle,
+    comic: &Comic,
+    chapter_uuids: &[String],
+    cancel_token: &ExportCancelToken,
+) -> eyre::Result<u64> {
+    let (export_with_cover, img_process_config, webtoon_config, pdf_config) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.export_with_cover,
+            config.img_process.clone(),
+            config.webtoon.clone(),
+            config.pdf.clone(),
+        )
     };
+    let cover_path = if export_with_cover {
+        comic.get_cover_path()
+    } else {
+        None
+    };
+
+    let downloaded_chapters = get_downloaded_chapters_by_uuids(&comic.comic.groups, chapter_uuids);
+
+    // 所有章节的原图
+    let mut chapter_image_paths = Vec::with_capacity(downloaded_chapters.len());
+    let mut img_count = 0;
+    let mut img_bytes = 0;
+    for chapter_info in &downloaded_chapters {
+        cancel_token.check()?;
+
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+        let image_paths = get_image_paths(chapter_download_dir).wrap_err(format!(
+            "获取`{}`中的图片失败",
+            chapter_download_dir.display()
+        ))?;
+        img_count += image_paths.len() as u64;
+        img_bytes += get_files_size(&image_paths);
+        chapter_image_paths.push(image_paths);
+    }
+
+    // 均匀抽取部分章节按导出时的方式处理，用章节中间的一页作为重新压缩的样本
+    let sample_count = downloaded_chapters.len().min(ESTIMATE_SAMPLE_COUNT);
+    let mut sample_img_count = 0;
+    let mut sample_img_bytes = 0;
+    let mut sample_page_count = 0;
+    let mut sample_page_bytes = 0;
+    let mut original_bytes = 0;
+    let mut recompressed_bytes = 0;
+    for i in (0..sample_count).map(|i| i * downloaded_chapters.len() / sample_count) {
+        cancel_token.check()?;
+
+        let chapter_info = &downloaded_chapters[i];
+        let image_paths = std::mem::take(&mut chapter_image_paths[i]);
+        sample_img_count += image_paths.len() as u64;
+        sample_img_bytes += get_files_size(&image_paths);
+
+        let chapter_webtoon_config = webtoon_config
+            .mode
+            .should_restitch(chapter_info.is_long)
+            .then_some(&webtoon_config);
+        let imgs = get_export_imgs(
+            cover_path.as_deref(),
+            image_paths,
+            &img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            chapter_webtoon_config,
+        )?;
+
+        sample_page_count += imgs.len() as u64;
+        for img in &imgs {
+            sample_page_bytes += img.data_len()?;
+        }
+
+        if !pdf_config.recompress_img {
+            continue;
+        }
+        let middle = imgs.len() / 2;
+        let Some(img) = imgs.into_iter().nth(middle) else {
+            continue;
+        };
+        let filename = img.filename().to_string();
+        let img_data = img.into_data()?;
+        let recompressed = img_process::recompress_img_data(
+            &img_data,
+            pdf_config.max_img_width,
+            pdf_config.jpeg_quality,
+        )
+        .wrap_err(format!("重新压缩`{filename}`失败"))?;
+        original_bytes += img_data.len();
+        recompressed_bytes += recompressed.len();
+    }
+
+    if sample_img_count == 0 || sample_img_bytes == 0 {
+        return Ok(0);
+    }
+    // 封面、跨页拆分和条漫重新切分都会改变页数和大小，按样本处理前后的比例推算
+    let page_count = (img_count as f64 * sample_page_count as f64 / sample_img_count as f64) as u64;
+    let mut total_bytes = img_bytes as f64 * sample_page_bytes as f64 / sample_img_bytes as f64;
+    if original_bytes != 0 {
+        total_bytes *= recompressed_bytes as f64 / original_bytes as f64;
+    }
+
+    Ok(total_bytes as u64 + page_count * PAGE_OVERHEAD_BYTES)
+}
 
-    // 获取指定章节（用户主动选择，不跳过，不合并）
-    let downloaded_chapters = get_downloaded_chapters_by_uuids(&comic.comic.groups, &chapter_uuids);
-
-    // 调用内部实现
-    export_pdf_internal(
-        app,
-        comic,
-        downloaded_chapters,
-        ExportSkipMode::None, // 用户主动选择，不跳过
-        false,                // 选择性导出，不合并
-    )
+/// `paths`中所有文件的总大小，无法获取大小的文件按`0`计算
+fn get_files_size(paths: &[PathBuf]) -> u64 {
+    paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
 }
 
 /// 内部实现：导出PDF
@@ -150,6 +292,7 @@ fn export_pdf_internal(
     downloaded_chapters: Vec<ChapterInfo>,
     skip_mode: ExportSkipMode,
     enable_merge: bool,
+    cancel_token: &ExportCancelToken,
 ) -> eyre::Result<()> {
     let grouped_export_targets =
         build_grouped_export_targets(app, comic, downloaded_chapters, ExportFormat::Pdf)?;
@@ -175,7 +318,7 @@ fn export_pdf_internal(
                 continue;
             }
 
-            create_group_pdf_files(app, comic, export_targets, skip_mode)?;
+            create_group_pdf_files(app, comic, export_targets, skip_mode, cancel_token)?;
         }
 
         Ok(())
@@ -183,38 +326,72 @@ fn export_pdf_internal(
 
     // 合并PDF
     if let Some(grouped_export_targets) = grouped_export_targets_for_merge {
-        let (export_dir, merge_pdf_fmt) = {
+        let (export_dir, merge_pdf_fmt, merge_pdf_all_groups, merge_comic_pdf_fmt, text_conversion) = {
             let config = app.get_config();
             let config = config.read();
-            (config.export_dir.clone(), config.merge_pdf_fmt.clone())
+            (
+                config.export_dir.clone(),
+                config.merge_pdf_fmt.clone(),
+                config.merge_pdf_all_groups,
+                config.merge_comic_pdf_fmt.clone(),
+                config.fmt_text_conversion,
+            )
         };
 
-        validate_merge_pdf_fmt(&merge_pdf_fmt)?;
-
-        // 合并PDF很吃内存，为了减少爆内存的发生，不使用并发处理，而是逐个合并
-        for (group_path_word, export_targets) in grouped_export_targets {
-            if export_targets.is_empty() {
-                continue;
+        // 跳过曾导出过的章节时，这些章节的PDF可能已被删除，只能在已合并的PDF后面追加
+        let incremental = skip_mode == ExportSkipMode::SkipExported;
+
+        // 合并时边读边写，内存占用只与单个章节PDF有关，但仍然逐个合并，避免同时读写太多文件
+        if merge_pdf_all_groups {
+            validate_merge_comic_fmt(&merge_comic_pdf_fmt, "所有分组合并pdf目录格式")?;
+
+            // 默认分组排在最前面，其他分组按`path_word`排序，保证每次导出的顺序一致
+            let mut sections: Vec<(String, Vec<ExportTarget>)> = grouped_export_targets
+                .into_iter()
+                .filter(|(_, export_targets)| !export_targets.is_empty())
+                .collect();
+            sections.sort_by(|(a, _), (b, _)| (a != "default", a).cmp(&(b != "default", b)));
+            let sections = sections
+                .into_iter()
+                .map(|(_, export_targets)| export_targets)
+                .collect();
+
+            let fmt_params = MergeFmtParams::new(comic, String::new(), "", text_conversion);
+            merge_pdf_files(
+                app,
+                comic,
+                &export_dir,
+                &merge_comic_pdf_fmt,
+                fmt_params,
+                None,
+                sections,
+                incremental,
+                cancel_token,
+            )?;
+        } else {
+            validate_merge_fmt(&merge_pdf_fmt, "合并pdf目录格式")?;
+
+            for (group_path_word, export_targets) in grouped_export_targets {
+                if export_targets.is_empty() {
+                    continue;
+                }
+
+                let group_title = export_targets[0].chapter_info.group_name.clone();
+                let fmt_params =
+                    MergeFmtParams::new(comic, group_path_word, &group_title, text_conversion);
+
+                merge_pdf_files(
+                    app,
+                    comic,
+                    &export_dir,
+                    &merge_pdf_fmt,
+                    fmt_params,
+                    Some(group_title),
+                    vec![export_targets],
+                    incremental,
+                    cancel_token,
+                )?;
             }
-
-            let fmt_params = MergePdfFmtParams {
-                comic_uuid: comic.comic.uuid.clone(),
-                comic_path_word: comic.comic.path_word.clone(),
-                comic_title: comic.comic.name.clone(),
-                author: comic
-                    .comic
-                    .author
-                    .iter()
-                    .map(|author| author.name.clone())
-                    .collect::<Vec<_>>()
-                    .join(", "),
-                group_path_word,
-                group_title: export_targets[0].chapter_info.group_name.clone(),
-            };
-
-            let merge_pdf_path = fmt_params.to_merge_pdf_path(&export_dir, &merge_pdf_fmt)?;
-
-            merge_group_pdf_files(app, comic, &merge_pdf_path, export_targets)?;
         }
     }
 
@@ -228,11 +405,13 @@ fn create_group_pdf_files(
     comic: &Comic,
     export_targets: Vec<ExportTarget>,
     skip_mode: ExportSkipMode,
+    cancel_token: &ExportCancelToken,
 ) -> eyre::Result<()> {
     let create_event_uuid = uuid::Uuid::new_v4().to_string();
     // 发送开始创建pdf事件
     let _ = ExportPdfEvent::CreateStart {
         uuid: create_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
         comic_title: comic.comic.name.clone(),
         group_title: export_targets[0].chapter_info.group_name.clone(),
         total: export_targets.len() as u32,
@@ -242,6 +421,7 @@ fn create_group_pdf_files(
     let mut create_error_event_guard = PdfCreateErrorEventGuard {
         uuid: create_event_uuid.clone(),
         app: app.clone(),
+        cancel_token: cancel_token.clone(),
         success: false,
     };
 
@@ -256,12 +436,31 @@ fn create_group_pdf_files(
     std::fs::create_dir_all(&export_dir)
         .wrap_err(format!("创建目录`{}`失败", export_dir.display()))?;
 
+    let (export_with_cover, img_process_config, webtoon_config, pdf_config) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.export_with_cover,
+            config.img_process.clone(),
+            config.webtoon.clone(),
+            config.pdf.clone(),
+        )
+    };
+    // 如果启用了封面，则把封面作为每个pdf的第一页
+    let cover_path = if export_with_cover {
+        comic.get_cover_path()
+    } else {
+        None
+    };
+
     // 用来记录创建pdf的进度
     let created_count = Arc::new(AtomicU32::new(0));
     //并发处理
     let current_span = tracing::Span::current();
     let export_targets = export_targets.into_par_iter();
     export_targets.try_for_each(|target| -> eyre::Result<()> {
+        cancel_token.check()?;
+
         let mut chapter_info = target.chapter_info;
         let pdf_path = target.export_path;
 
@@ -302,12 +501,52 @@ fn create_group_pdf_files(
             "获取`{}`中的图片失败",
             chapter_download_dir.display()
         ))?;
-
-        create_pdf_file(image_paths, &pdf_path).wrap_err("创建pdf失败")?;
+        // 条漫需要拼接后重新切分成页
+        let chapter_webtoon_config = webtoon_config
+            .mode
+            .should_restitch(chapter_info.is_long)
+            .then_some(&webtoon_config);
+        let imgs = get_export_imgs(
+            cover_path.as_deref(),
+            image_paths,
+            &img_process_config,
+            chapter_info.download_img_process.as_ref(),
+            chapter_webtoon_config,
+        )?;
+
+        let metadata = PdfMetadata::from_chapter(comic, &chapter_info);
+        // 先写到临时文件，校验通过后再重命名，避免留下不完整的pdf
+        let expected_page_count = imgs.len();
+        let temp_path = get_temp_export_path(&pdf_path);
+        if let Err(err) =
+            create_pdf_file(imgs, &metadata, &pdf_config, &temp_path).wrap_err("创建pdf失败")
+        {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        if let Err(err) = validate_pdf_file(&temp_path, expected_page_count) {
+            let _ = std::fs::remove_file(&temp_path);
+            // 发送校验失败事件，让前端知道是哪个章节有问题
+            let _ = ExportPdfEvent::CreateValidationError {
+                uuid: create_event_uuid.clone(),
+                chapter_uuid: chapter_info.chapter_uuid.clone(),
+                chapter_title: chapter_info.chapter_title.clone(),
+                err_msg: format!("{err:#}"),
+            }
+            .emit(app);
+            return Err(err);
+        }
+        persist_temp_export_file(&temp_path, &pdf_path)?;
 
         // 更新章节导出状态
         chapter_info.is_pdf_exported = true;
         chapter_info.save_metadata()?;
+        let library_index = app.get_library_index();
+        if let Err(err) = library_index.update_chapter_metadata(chapter_download_dir) {
+            let err_title = "更新已下载漫画索引失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
 
         // 更新创建pdf的进度
         let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
@@ -335,46 +574,259 @@ fn create_group_pdf_files(
     Ok(())
 }
 
+/// 把`sections`中的章节PDF按顺序合并，超过`merge_pdf_chapter_count`或`merge_pdf_max_size_mb`时拆分为多个部分
+///
+/// `sections`的每一项为一个分组的章节，有多个分组时书签按分组嵌套，
+/// `group_title`为`None`表示所有分组合并为一个PDF。
+/// 拆分后的文件名由合并模板中的`{part}`字段决定，模板中没有`{part}`时会在文件名后面加上` ({part})`
+///
+/// `incremental`为`true`时，如果已合并的章节都排在新章节前面，则之前的部分保持不变，
+/// 最后一部分已经写满时新章节写入新的部分，否则用最后一部分的章节PDF重新写入最后一部分，
+/// 这些章节PDF不全时才把最后一部分完整加载到内存中
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::too_many_lines)]
+#[allow(clippy::too_many_arguments)]
 #[instrument(level = "error", skip_all)]
-fn merge_group_pdf_files(
+fn merge_pdf_files(
     app: &AppHandle,
     comic: &Comic,
-    merge_pdf_path: &Path,
-    mut export_targets: Vec<ExportTarget>,
+    export_dir: &Path,
+    merge_pdf_fmt: &str,
+    mut fmt_params: MergeFmtParams,
+    group_title: Option<String>,
+    mut sections: Vec<Vec<ExportTarget>>,
+    incremental: bool,
+    cancel_token: &ExportCancelToken,
 ) -> eyre::Result<()> {
-    let group_title = export_targets[0].chapter_info.group_name.clone();
+    let (pdf_config, merge_pdf_chapter_count, merge_pdf_max_size_mb) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.pdf.clone(),
+            config.merge_pdf_chapter_count,
+            config.merge_pdf_max_size_mb,
+        )
+    };
+    let max_size_bytes = merge_pdf_max_size_mb * 1024 * 1024;
+    let nested_bookmarks = sections.len() > 1;
+    let cover_path = if pdf_config.merged_cover_page {
+        comic.get_cover_path()
+    } else {
+        None
+    };
+
+    for export_targets in &mut sections {
+        export_targets.sort_by_key(|target| FloatOrd(target.chapter_info.order));
+    }
+    let export_targets: Vec<ExportTarget> = sections.into_iter().flatten().collect();
+
+    let merge_pdf_path = fmt_params.to_merge_path(export_dir, merge_pdf_fmt, ExportFormat::Pdf)?;
+    let merge_pdf_dir = merge_pdf_path
+        .parent()
+        .ok_or_eyre(format!("获取`{}`的父目录失败", merge_pdf_path.display()))?
+        .to_path_buf();
+    std::fs::create_dir_all(&merge_pdf_dir)
+        .wrap_err(format!("创建目录`{}`失败", merge_pdf_dir.display()))?;
+
+    let manifest_path = MergedPdfManifest::path(&merge_pdf_path);
+    let old_manifest = MergedPdfManifest::load(&manifest_path);
+    let chapter_uuids: Vec<&str> = export_targets
+        .iter()
+        .map(|target| target.chapter_info.chapter_uuid.as_str())
+        .collect();
+    // 有章节插入到中间时不能追加，需要完全重新合并
+    let append_to = old_manifest
+        .as_ref()
+        .filter(|manifest| incremental && manifest.can_append(&chapter_uuids, nested_bookmarks));
+    let merged_chapter_count = append_to.map_or(0, MergedPdfManifest::chapter_count);
+    if append_to.is_some() && merged_chapter_count == export_targets.len() {
+        // 没有新章节
+        return Ok(());
+    }
+
+    // 之前的部分保持不变，最后一部分没写满时需要重新写入
+    let mut finished_parts = Vec::new();
+    // 需要从合并PDF中复制页面的最后一部分
+    let mut reopen_part = None;
+    // 从第几个章节开始写入
+    let mut start = merged_chapter_count;
+    if let Some(manifest) = append_to {
+        finished_parts.clone_from(&manifest.parts);
+        let get_file_size = |path: &Path| std::fs::metadata(path).map_or(0, |m| m.len());
+        let last_part_full = finished_parts.last().is_some_and(|last_part| {
+            let first_new_chapter_size = get_file_size(&export_targets[start].export_path);
+            let reach_chapter_count =
+                merge_pdf_chapter_count != 0 && last_part.chapters.len() >= merge_pdf_chapter_count;
+            let reach_max_size = max_size_bytes != 0
+                && get_file_size(&last_part.path) + first_new_chapter_size > max_size_bytes;
+            reach_chapter_count || reach_max_size
+        });
+        // 最后一部分已经写满时保持不变，新章节直接写入新的部分
+        let last_part = if last_part_full {
+            None
+        } else {
+            finished_parts.pop()
+        };
+        if let Some(last_part) = last_part {
+            let last_part_start = start - last_part.chapters.len();
+            let chapter_pdfs_exist = export_targets[last_part_start..start]
+                .iter()
+                .all(|target| target.export_path.is_file());
+            if chapter_pdfs_exist {
+                // 章节PDF都还在，就和新章节一样从章节PDF写入，每次只加载一个章节
+                start = last_part_start;
+            } else {
+                reopen_part = Some(last_part);
+            }
+        }
+    }
 
     let merge_event_uuid = uuid::Uuid::new_v4().to_string();
     // 发送开始合并pdf事件
     let _ = ExportPdfEvent::MergeStart {
         uuid: merge_event_uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
         comic_title: comic.comic.name.clone(),
-        group_title: group_title.clone(),
-        total: 1,
+        group_title: group_title
+            .clone()
+            .unwrap_or_else(|| "所有分组".to_string()),
+        total: (export_targets.len() - start) as u32,
     }
     .emit(app);
     // 如果success为false，drop时发送MergeError事件
     let mut merge_error_event_guard = PdfMergeErrorEventGuard {
         uuid: merge_event_uuid.clone(),
         app: app.clone(),
+        cancel_token: cancel_token.clone(),
         success: false,
     };
 
-    export_targets.sort_by_key(|target| FloatOrd(target.chapter_info.order));
+    let temp_path = |part: usize| merge_pdf_path.with_extension(format!("part{part}.pdf.tmp"));
+    let finish_part = |part: MergePdfPart| -> eyre::Result<MergedPdfPart> {
+        part.finish(
+            comic,
+            group_title.as_deref(),
+            cover_path.as_deref(),
+            &pdf_config,
+        )
+    };
 
-    let merge_pdf_dir = merge_pdf_path
-        .parent()
-        .ok_or_eyre(format!("获取`{}`的父目录失败", merge_pdf_path.display()))?;
-    std::fs::create_dir_all(merge_pdf_dir)
-        .wrap_err(format!("创建目录`{}`失败", merge_pdf_dir.display()))?;
+    // 全部写完才知道要拆分为几部分，所以先写到临时文件，最后再重命名
+    let mut current_part: Option<MergePdfPart> = None;
+    if let Some(last_part) = reopen_part {
+        let temp_path = temp_path(finished_parts.len() + 1);
+        let part = MergePdfPart::reopen(temp_path, &last_part, nested_bookmarks, &pdf_config)?;
+        current_part = Some(part);
+    }
 
-    let chapter_pdf_paths: Vec<PathBuf> = export_targets
-        .into_iter()
-        .map(|target| target.export_path)
-        .collect();
+    for (i, target) in export_targets.into_iter().skip(start).enumerate() {
+        cancel_token.check()?;
+
+        let chapter_pdf_path = target.export_path;
+        if !chapter_pdf_path.is_file() {
+            return Err(eyre!(
+                "章节`{}`的PDF`{}`不存在，无法合并，请使用其他导出跳过模式重新导出",
+                target.chapter_info.chapter_title,
+                chapter_pdf_path.display()
+            ));
+        }
+        let chapter_pdf_size = std::fs::metadata(&chapter_pdf_path)
+            .wrap_err(format!("获取`{}`的大小失败", chapter_pdf_path.display()))?
+            .len();
+
+        let should_split = current_part.as_ref().is_some_and(|part| {
+            let reach_chapter_count =
+                merge_pdf_chapter_count != 0 && part.chapters.len() >= merge_pdf_chapter_count;
+            let reach_max_size = max_size_bytes != 0
+                && part.writer.bytes_written() + chapter_pdf_size > max_size_bytes;
+            reach_chapter_count || reach_max_size
+        });
+        if should_split {
+            if let Some(part) = current_part.take() {
+                finished_parts.push(finish_part(part)?);
+            }
+        }
 
-    // 合并pdf
-    merge_pdf_file(chapter_pdf_paths, merge_pdf_path)?;
+        let part = match &mut current_part {
+            Some(part) => part,
+            None => {
+                let temp_path = temp_path(finished_parts.len() + 1);
+                current_part.insert(MergePdfPart::create(temp_path)?)
+            }
+        };
+
+        // 每次只加载一个章节PDF，写入后就释放
+        let chapter_doc = Document::load(&chapter_pdf_path)
+            .wrap_err(format!("加载`{}`失败", chapter_pdf_path.display()))?;
+        let bookmark_title = chapter_pdf_path
+            .file_stem()
+            .and_then(|file_stem| file_stem.to_str())
+            .ok_or_eyre(format!("获取`{}`的文件名失败", chapter_pdf_path.display()))?
+            .to_string();
+        let page_ids: Vec<ObjectId> = chapter_doc.get_pages().into_values().collect();
+        let chapter = MergedPdfChapter {
+            chapter_info: target.chapter_info,
+            bookmark_title,
+            page_count: page_ids.len(),
+        };
+        part.append_chapter(
+            &chapter_doc,
+            &page_ids,
+            chapter,
+            nested_bookmarks,
+            &pdf_config,
+        )?;
+
+        let _ = ExportPdfEvent::MergeProgress {
+            uuid: merge_event_uuid.clone(),
+            current: i as u32 + 1,
+        }
+        .emit(app);
+    }
+    if let Some(part) = current_part.take() {
+        finished_parts.push(finish_part(part)?);
+    }
+
+    let part_fmt = if finished_parts.len() > 1 && !merge_pdf_fmt.contains("{part") {
+        format!("{merge_pdf_fmt} ({{part}})")
+    } else {
+        merge_pdf_fmt.to_string()
+    };
+    // 新写入的部分从临时文件重命名，之前的部分在拆分数变化时可能也需要重命名
+    for (i, part) in finished_parts.iter_mut().enumerate() {
+        fmt_params.part = i as u32 + 1;
+        let pdf_path = fmt_params.to_merge_path(export_dir, &part_fmt, ExportFormat::Pdf)?;
+        if pdf_path == part.path {
+            continue;
+        }
+        if let Some(parent) = pdf_path.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err(format!("创建目录`{}`失败", parent.display()))?;
+        }
+        std::fs::rename(&part.path, &pdf_path).wrap_err(format!(
+            "将`{}`重命名为`{}`失败",
+            part.path.display(),
+            pdf_path.display()
+        ))?;
+        part.path = pdf_path;
+    }
+
+    // 删除上次合并留下的、这次没有覆盖的部分
+    if let Some(old_manifest) = &old_manifest {
+        for old_part in &old_manifest.parts {
+            let overwritten = finished_parts.iter().any(|part| part.path == old_part.path);
+            if !overwritten && old_part.path.is_file() {
+                std::fs::remove_file(&old_part.path)
+                    .wrap_err(format!("删除`{}`失败", old_part.path.display()))?;
+            }
+        }
+    }
+
+    let manifest = MergedPdfManifest {
+        nested_bookmarks,
+        parts: finished_parts,
+    };
+    manifest.save(&manifest_path)?;
 
     // 标记为成功，后面drop时就不会发送MergeError事件
     merge_error_event_guard.success = true;
@@ -382,113 +834,175 @@ fn merge_group_pdf_files(
     let _ = ExportPdfEvent::MergeEnd {
         uuid: merge_event_uuid,
         comic_path_word: comic.comic.path_word.clone(),
-        export_dir: merge_pdf_dir.to_path_buf(),
+        export_dir: merge_pdf_dir,
     }
     .emit(app);
 
     Ok(())
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct MergePdfFmtParams {
-    comic_uuid: String,
-    comic_path_word: String,
-    comic_title: String,
-    author: String,
-    group_path_word: String,
-    group_title: String,
+/// 合并PDF拆分后正在写入的一部分
+struct MergePdfPart {
+    writer: PdfStreamWriter,
+    temp_path: PathBuf,
+    chapters: Vec<MergedPdfChapter>,
+    /// 当前所在的分组书签，书签不嵌套时为`None`
+    bookmark_group_title: Option<String>,
 }
 
-impl MergePdfFmtParams {
-    #[instrument(
-        level = "error",
-        skip_all,
-        fields(
-            comic_uuid = self.comic_uuid,
-            comic_path_word = self.comic_path_word,
-            comic_title = self.comic_title,
-            group_path_word = self.group_path_word,
-            group_title = self.group_title
-        )
-    )]
-    fn to_merge_pdf_path(&self, export_dir: &Path, merge_pdf_fmt: &str) -> eyre::Result<PathBuf> {
-        use strfmt::strfmt;
+impl MergePdfPart {
+    fn create(temp_path: PathBuf) -> eyre::Result<Self> {
+        Ok(Self {
+            writer: PdfStreamWriter::create(&temp_path)?,
+            temp_path,
+            chapters: Vec::new(),
+            bookmark_group_title: None,
+        })
+    }
 
-        let json_value =
-            serde_json::to_value(self).wrap_err("将MergePdfFmtParams转为serde_json::Value失败")?;
+    /// 把已合并的`merged_part`中的章节页面复制到新的临时文件中，之后可以继续追加章节
+    ///
+    /// 封面页和目录页会在完成时重新生成
+    ///
+    /// 需要把整个`merged_part`加载到内存中，内存占用与这一部分的大小成正比，
+    /// 所以只在这一部分的章节PDF不全、无法从章节PDF重新写入时使用
+    fn reopen(
+        temp_path: PathBuf,
+        merged_part: &MergedPdfPart,
+        nested_bookmarks: bool,
+        pdf_config: &PdfConfig,
+    ) -> eyre::Result<Self> {
+        let merged_pdf_path = &merged_part.path;
+        let merged_doc = Document::load(merged_pdf_path)
+            .wrap_err(format!("加载`{}`失败", merged_pdf_path.display()))?;
+        let page_ids: Vec<ObjectId> = merged_doc
+            .get_pages()
+            .into_values()
+            .skip(merged_part.front_page_count)
+            .collect();
 
-        let json_map = json_value
-            .as_object()
-            .ok_or_eyre("MergePdfFmtParams不是JSON对象")?;
+        let mut part = Self::create(temp_path)?;
+        let mut remaining_page_ids = page_ids.as_slice();
+        for chapter in &merged_part.chapters {
+            let Some((chapter_page_ids, rest)) =
+                remaining_page_ids.split_at_checked(chapter.page_count)
+            else {
+                return Err(eyre!(
+                    "`{}`的页数与合并元数据不一致",
+                    merged_pdf_path.display()
+                ));
+            };
+            remaining_page_ids = rest;
+            part.append_chapter(
+                &merged_doc,
+                chapter_page_ids,
+                chapter.clone(),
+                nested_bookmarks,
+                pdf_config,
+            )?;
+        }
 
-        let vars: HashMap<String, String> = json_map
-            .iter()
-            .map(|(key, value)| {
-                let value = match value {
-                    serde_json::Value::String(s) => s.clone(),
-                    _ => value.to_string(),
-                };
-                (key.clone(), value)
-            })
-            .collect();
+        Ok(part)
+    }
 
-        let dir_fmt_parts: Vec<&str> = merge_pdf_fmt.split('/').collect();
+    /// 把`doc`中属于`chapter`的页面`page_ids`追加到末尾，并添加书签和页码标签
+    fn append_chapter(
+        &mut self,
+        doc: &Document,
+        page_ids: &[ObjectId],
+        chapter: MergedPdfChapter,
+        nested_bookmarks: bool,
+        pdf_config: &PdfConfig,
+    ) -> eyre::Result<()> {
+        // 每个部分的分组书签都要重新开始，拆分后的部分才有完整的书签层级
+        let group_title = &chapter.chapter_info.group_name;
+        if nested_bookmarks && self.bookmark_group_title.as_ref() != Some(group_title) {
+            self.writer.start_bookmark_group(group_title.clone());
+            self.bookmark_group_title = Some(group_title.clone());
+        }
 
-        let mut dir_names = Vec::new();
-        for fmt_part in dir_fmt_parts {
-            let dir_name = strfmt(fmt_part, &vars).wrap_err("格式化合并pdf目录目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
-            if !dir_name.is_empty() {
-                dir_names.push(dir_name);
+        let new_page_ids = self.writer.append_pages(doc, page_ids)?;
+        if let Some(&first_page_id) = new_page_ids.first() {
+            self.writer
+                .add_bookmark(chapter.bookmark_title.clone(), first_page_id);
+            if pdf_config.merged_page_labels {
+                let prefix = format!("Ch.{} p.", chapter.chapter_info.order);
+                self.writer.add_page_label(first_page_id, prefix, true);
             }
         }
+        self.chapters.push(chapter);
 
-        let Some(filename) = dir_names.pop() else {
-            let err_msg =
-            "配置中的导出目录格式至少要有1个层级，例如这个例子是3个层级：{comic_title}/pdf/{group_title}";
-            return Err(eyre!(err_msg));
-        };
+        Ok(())
+    }
 
-        let mut output_path = export_dir.to_path_buf();
-        for dir_name in dir_names {
-            output_path = output_path.join(dir_name);
-        }
+    /// 写入封面页、目录页和元数据等收尾内容，返回这一部分的合并元数据，路径为临时文件的路径
+    fn finish(
+        mut self,
+        comic: &Comic,
+        group_title: Option<&str>,
+        cover_path: Option<&Path>,
+        pdf_config: &PdfConfig,
+    ) -> eyre::Result<MergedPdfPart> {
+        let chapter_infos: Vec<ChapterInfo> = self
+            .chapters
+            .iter()
+            .map(|chapter| chapter.chapter_info.clone())
+            .collect();
+        let front_matter = PdfFrontMatter {
+            comic,
+            group_title,
+            chapter_infos: &chapter_infos,
+            cover_path,
+        };
+        front_matter.write_to(&mut self.writer, pdf_config)?;
+        let front_page_count = self.writer.front_page_count();
 
-        Ok(output_path.join(format!("{filename}.pdf")))
-    }
-}
+        let metadata = PdfMetadata::from_merged(comic, group_title, &chapter_infos);
+        self.writer.finish(&metadata, pdf_config)?;
 
-fn validate_merge_pdf_fmt(merge_pdf_fmt: &str) -> eyre::Result<()> {
-    if !contains_any_field(merge_pdf_fmt, &EXPORT_FMT_GROUP_FIELDS) {
-        return Err(eyre!(
-            "`合并pdf目录格式`不合法，整个模板中必须至少包含一个分组字段 {:?}",
-            EXPORT_FMT_GROUP_FIELDS
-        ));
+        Ok(MergedPdfPart {
+            path: self.temp_path,
+            front_page_count,
+            chapters: self.chapters,
+        })
     }
-
-    Ok(())
 }
 
-/// 用`image_paths`中的图片创建PDF文件，保存到`pdf_path`
+/// 用`imgs`中的图片创建PDF文件，保存到`pdf_path`
 #[allow(clippy::similar_names)]
 #[allow(clippy::cast_possible_truncation)]
 #[instrument(level = "error", skip_all, fields(pdf_path = %pdf_path.display()))]
-fn create_pdf_file(image_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<()> {
+fn create_pdf_file(
+    imgs: Vec<ExportImg>,
+    metadata: &PdfMetadata,
+    pdf_config: &PdfConfig,
+    pdf_path: &Path,
+) -> eyre::Result<()> {
     let mut doc = Document::with_version("1.5");
     let pages_id = doc.new_object_id();
     let mut page_ids = vec![];
 
-    for image_path in image_paths {
-        if !image_path.is_file() {
-            continue;
-        }
-
-        let buffer = read_image_to_buffer(&image_path)
-            .wrap_err(format!("将`{}`读取到buffer失败", image_path.display()))?;
-        let (width, height) = utils::get_dimensions(&buffer)
-            .wrap_err(format!("获取`{}`的尺寸失败", image_path.display()))?;
-        let image_stream = lopdf::xobject::image_from(buffer)
-            .wrap_err(format!("创建`{}`的图片流失败", image_path.display()))?;
+    for img in imgs {
+        let filename = img.filename().to_string();
+        let buffer = img
+            .into_data()
+            .wrap_err(format!("将`{filename}`读取到buffer失败"))?;
+        let (width, height) =
+            utils::get_dimensions(&buffer).wrap_err(format!("获取`{filename}`的尺寸失败"))?;
+        // 用原图的尺寸计算页面布局，这样缩小图片不会改变页面尺寸
+        let layout = PageLayout::new(width, height, pdf_config);
+        let buffer = if pdf_config.recompress_img {
+            img_process::recompress_img_data(
+                &buffer,
+                pdf_config.max_img_width,
+                pdf_config.jpeg_quality,
+            )
+            .wrap_err(format!("重新压缩`{filename}`失败"))?
+        } else {
+            buffer
+        };
+        let image_stream =
+            lopdf::xobject::image_from(buffer).wrap_err(format!("创建`{filename}`的图片流失败"))?;
         // 将图片流添加到doc中
         let img_id = doc.add_object(image_stream);
         // 图片的名称，用于 Do 操作在页面上显示图片
@@ -497,12 +1011,12 @@ fn create_pdf_file(image_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<(
         let cm_operation = Operation::new(
             "cm",
             vec![
-                width.into(),
-                0.into(),
-                0.into(),
-                height.into(),
+                layout.img_width.into(),
                 0.into(),
                 0.into(),
+                layout.img_height.into(),
+                layout.img_x.into(),
+                layout.img_y.into(),
             ],
         );
         // 用于显示图片
@@ -517,7 +1031,7 @@ fn create_pdf_file(image_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<(
             "Type" => "Page",
             "Parent" => pages_id,
             "Contents" => content_id,
-            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "MediaBox" => vec![0.into(), 0.into(), layout.page_width.into(), layout.page_height.into()],
         });
         // 将图片以 XObject 的形式添加到文档中
         // Do 操作只能引用 XObject(所以前面定义的 Do 操作的参数是 img_name, 而不是 img_id)
@@ -538,6 +1052,7 @@ fn create_pdf_file(image_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<(
         "Pages" => pages_id,
     });
     doc.trailer.set("Root", catalog_id);
+    metadata.write_to(&mut doc, pdf_config)?;
 
     doc.compress();
 
@@ -546,96 +1061,69 @@ fn create_pdf_file(image_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<(
     Ok(())
 }
 
-/// 读取`image_path`中的图片数据到buffer中
-#[instrument(level = "error", skip_all, fields(image_path = %image_path.display()))]
-fn read_image_to_buffer(image_path: &Path) -> eyre::Result<Vec<u8>> {
-    let file =
-        std::fs::File::open(image_path).wrap_err(format!("打开`{}`失败", image_path.display()))?;
-    let mut reader = std::io::BufReader::new(file);
-    let mut buffer = vec![];
-    reader
-        .read_to_end(&mut buffer)
-        .wrap_err(format!("读取`{}`失败", image_path.display()))?;
-    Ok(buffer)
-}
+/// 校验`pdf_path`能否解析，并且有`expected_page_count`页
+fn validate_pdf_file(pdf_path: &Path, expected_page_count: usize) -> eyre::Result<()> {
+    let doc = Document::load(pdf_path).wrap_err(format!("解析`{}`失败", pdf_path.display()))?;
 
-/// 将`chapter_pdf_paths`中的PDF合并到`pdf_path`中
-#[allow(clippy::cast_possible_truncation)]
-#[instrument(level = "error", skip_all, fields(pdf_path = %pdf_path.display()))]
-fn merge_pdf_file(chapter_pdf_paths: Vec<PathBuf>, pdf_path: &Path) -> eyre::Result<()> {
-    let mut doc = Document::with_version("1.5");
-    let mut doc_page_ids = vec![];
-    let mut doc_objects = BTreeMap::new();
+    let page_count = doc.get_pages().len();
+    if page_count != expected_page_count {
+        return Err(eyre!(
+            "`{}`应该有{expected_page_count}页，实际有{page_count}页",
+            pdf_path.display()
+        ));
+    }
 
-    for chapter_pdf_path in chapter_pdf_paths {
-        let mut chapter_doc = Document::load(&chapter_pdf_path)
-            .wrap_err(format!("加载`{}`失败", chapter_pdf_path.display()))?;
-        // 重新编号这个章节PDF的对象，避免与doc的对象编号冲突
-        chapter_doc.renumber_objects_with(doc.max_id);
-        doc.max_id = chapter_doc.max_id + 1;
-        // 获取这个章节PDF中的所有页面，并给第一个页面添加书签
-        let mut chapter_page_ids = vec![];
-        for (page_num, object_id) in chapter_doc.get_pages() {
-            // 第一个页面需要添加书签
-            if page_num == 1 {
-                let chapter_title = chapter_pdf_path
-                    .file_stem()
-                    .and_then(|file_stem| file_stem.to_str())
-                    .ok_or_eyre(format!("获取`{}`的文件名失败", chapter_pdf_path.display()))?
-                    .to_string();
-                let bookmark = Bookmark::new(chapter_title, [0.0, 0.0, 1.0], 0, object_id);
-                doc.add_bookmark(bookmark, None);
-            }
-            chapter_page_ids.push(object_id);
-        }
+    Ok(())
+}
 
-        doc_page_ids.extend(chapter_page_ids);
-        doc_objects.extend(chapter_doc.objects);
-    }
-    // 在doc中新建一个"Pages"对象，将所有章节的页面添加到这个"Pages"对象中
-    let pages_id = doc.add_object(dictionary! {
-        "Type" => "Pages",
-        "Count" => doc_page_ids.len() as u32,
-        "Kids" => doc_page_ids.into_iter().map(Object::Reference).collect::<Vec<_>>(),
-    });
+/// 毫米换算为点，1英寸 = 25.4毫米 = 72点
+const PT_PER_MM: f32 = 72.0 / 25.4;
+
+/// 图片在页面上的位置和大小，单位都是点
+struct PageLayout {
+    page_width: f32,
+    page_height: f32,
+    img_x: f32,
+    img_y: f32,
+    img_width: f32,
+    img_height: f32,
+}
 
-    for (object_id, mut object) in doc_objects {
-        match object.type_name().unwrap_or(b"") {
-            b"Page" => {
-                if let Ok(page_dict) = object.as_dict_mut() {
-                    // 将页面对象的"Parent"字段设置为新建的"Pages"对象，这样这个页面就成为了"Pages"对象的子页面
-                    page_dict.set("Parent", pages_id);
-                    doc.objects.insert(object_id, object);
-                };
-            }
-            // 忽略这些对象
-            b"Catalog" | b"Pages" | b"Outlines" | b"Outline" => {}
-            // 将所有其他对象添加到doc中
-            _ => {
-                doc.objects.insert(object_id, object);
-            }
-        }
-    }
-    // 新建一个"Catalog"对象，将"Pages"对象添加到"Catalog"对象中，然后将"Catalog"对象添加到doc中
-    let catalog_id = doc.add_object(dictionary! {
-        "Type" => "Catalog",
-        "Pages" => pages_id,
-    });
-    doc.trailer.set("Root", catalog_id);
-    // 如果有书签没有关联到具体页面，将这些书签指向第一个页面
-    doc.adjust_zero_pages();
-    // 将书签添加到doc中
-    if let Some(outline_id) = doc.build_outline() {
-        if let Ok(Object::Dictionary(catalog_dict)) = doc.get_object_mut(catalog_id) {
-            catalog_dict.set("Outlines", Object::Reference(outline_id));
+impl PageLayout {
+    /// 固定页面尺寸时，图片等比缩放到页边距以内并居中，否则按DPI把图片的像素换算为页面尺寸
+    #[allow(clippy::cast_precision_loss)]
+    fn new(img_width: u32, img_height: u32, pdf_config: &PdfConfig) -> Self {
+        let margin = pdf_config.margin_mm.max(0.0) * PT_PER_MM;
+        let (img_width, img_height) = (img_width.max(1) as f32, img_height.max(1) as f32);
+
+        let Some((page_width_mm, page_height_mm)) = pdf_config.page_size.size_mm(pdf_config) else {
+            let dpi = pdf_config.dpi.max(1) as f32;
+            let img_width = img_width * 72.0 / dpi;
+            let img_height = img_height * 72.0 / dpi;
+            return Self {
+                page_width: img_width + 2.0 * margin,
+                page_height: img_height + 2.0 * margin,
+                img_x: margin,
+                img_y: margin,
+                img_width,
+                img_height,
+            };
+        };
+
+        let page_width = page_width_mm * PT_PER_MM;
+        let page_height = page_height_mm * PT_PER_MM;
+        let available_width = (page_width - 2.0 * margin).max(1.0);
+        let available_height = (page_height - 2.0 * margin).max(1.0);
+        let scale = (available_width / img_width).min(available_height / img_height);
+        let (draw_width, draw_height) = (img_width * scale, img_height * scale);
+
+        Self {
+            page_width,
+            page_height,
+            img_x: (page_width - draw_width) / 2.0,
+            img_y: (page_height - draw_height) / 2.0,
+            img_width: draw_width,
+            img_height: draw_height,
         }
     }
-    // 重新编号doc的对象
-    doc.renumber_objects();
-
-    doc.compress();
-
-    doc.save(pdf_path)
-        .wrap_err(format!("保存`{}`失败", pdf_path.display()))?;
-    Ok(())
 }
@@ -0,0 +1,413 @@
+use std::path::Path;
+
+use eyre::{eyre, WrapErr};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Object, ObjectId, Stream, StringFormat,
+};
+
+use crate::{
+    config::PdfConfig,
+    export::pdf_writer::{PdfBookmark, PdfStreamWriter},
+    types::{ChapterInfo, Comic},
+    utils,
+};
+
+/// 没有任何页面时使用A4的尺寸，单位为点
+const DEFAULT_PAGE_SIZE: (f32, f32) = (595.0, 842.0);
+/// 页面四周留白占页面宽度的比例
+const MARGIN_RATIO: f32 = 0.08;
+/// 目录页每页至少要能放下的行数
+const MIN_TOC_LINES_PER_PAGE: usize = 5;
+
+/// 合并PDF的封面页和目录页
+///
+/// 必须在所有章节都追加到`writer`之后调用，目录页需要知道每个书签指向的页面
+pub struct PdfFrontMatter<'a> {
+    pub comic: &'a Comic,
+    /// 只合并一个分组时为分组标题
+    pub group_title: Option<&'a str>,
+    pub chapter_infos: &'a [ChapterInfo],
+    pub cover_path: Option<&'a Path>,
+}
+
+impl PdfFrontMatter<'_> {
+    /// 按`pdf_config`把封面页和目录页写入`writer`，它们会排在所有章节前面
+    pub fn write_to(
+        &self,
+        writer: &mut PdfStreamWriter,
+        pdf_config: &PdfConfig,
+    ) -> eyre::Result<()> {
+        if !pdf_config.merged_cover_page && !pdf_config.merged_toc_page {
+            return Ok(());
+        }
+
+        let page_size = writer.first_page_size().unwrap_or(DEFAULT_PAGE_SIZE);
+        let font_id = write_cjk_font(writer)?;
+
+        if pdf_config.merged_cover_page {
+            let cover_page_id = self.write_cover_page(writer, font_id, page_size)?;
+            if pdf_config.merged_page_labels {
+                writer.add_page_label(cover_page_id, "封面".to_string(), false);
+            }
+        }
+
+        if pdf_config.merged_toc_page {
+            let toc_page_ids = write_toc_pages(writer, font_id, page_size)?;
+            if let (Some(&first_toc_page_id), true) =
+                (toc_page_ids.first(), pdf_config.merged_page_labels)
+            {
+                writer.add_page_label(first_toc_page_id, "目录".to_string(), true);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 封面页从上到下依次为标题、分组标题、漫画封面、作者和章节范围
+    fn write_cover_page(
+        &self,
+        writer: &mut PdfStreamWriter,
+        font_id: ObjectId,
+        (page_width, page_height): (f32, f32),
+    ) -> eyre::Result<ObjectId> {
+        let margin = page_width * MARGIN_RATIO;
+        let available_width = page_width - 2.0 * margin;
+        let mut ops = Vec::new();
+
+        // 标题太长时缩小字号，保证标题在一行内
+        let title = &self.comic.comic.name;
+        let title_size = (page_width / 14.0).min(available_width / text_width_em(title).max(1.0));
+        let mut top = page_height - margin - title_size;
+        push_centered_text(&mut ops, title, title_size, page_width, top);
+
+        let small_size = title_size.min(page_width / 24.0);
+        if let Some(group_title) = self.group_title {
+            top -= small_size * 2.0;
+            let group_title = truncate_text(group_title, small_size, available_width);
+            push_centered_text(&mut ops, &group_title, small_size, page_width, top);
+        }
+
+        let mut bottom_lines = Vec::new();
+        let authors: Vec<&str> = self
+            .comic
+            .comic
+            .author
+            .iter()
+            .map(|author| author.name.as_str())
+            .collect();
+        if !authors.is_empty() {
+            bottom_lines.push(authors.join(", "));
+        }
+        if let (Some(first), Some(last)) = (self.chapter_infos.first(), self.chapter_infos.last()) {
+            if self.chapter_infos.len() == 1 {
+                bottom_lines.push(first.chapter_title.clone());
+            } else {
+                bottom_lines.push(format!("{} ~ {}", first.chapter_title, last.chapter_title));
+            }
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let mut bottom = margin + small_size * 1.8 * bottom_lines.len() as f32;
+        for (i, line) in bottom_lines.iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let y = margin + small_size * 1.8 * (bottom_lines.len() - 1 - i) as f32;
+            let line = truncate_text(line, small_size, available_width);
+            push_centered_text(&mut ops, &line, small_size, page_width, y);
+        }
+
+        let mut resources = dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        };
+
+        // 漫画封面等比缩放到标题和底部文字之间的区域并居中
+        top -= small_size * 1.5;
+        bottom += small_size;
+        if let Some(cover_path) = self.cover_path.filter(|_| top > bottom) {
+            let buffer = std::fs::read(cover_path)
+                .wrap_err(format!("读取`{}`失败", cover_path.display()))?;
+            let (img_width, img_height) = utils::get_dimensions(&buffer)
+                .wrap_err(format!("获取`{}`的尺寸失败", cover_path.display()))?;
+            let image_stream = lopdf::xobject::image_from(buffer)
+                .wrap_err(format!("创建`{}`的图片流失败", cover_path.display()))?;
+            let img_id = writer.add_object(&Object::Stream(image_stream))?;
+
+            #[allow(clippy::cast_precision_loss)]
+            let (img_width, img_height) = (img_width.max(1) as f32, img_height.max(1) as f32);
+            let scale = (available_width / img_width).min((top - bottom) / img_height);
+            let (draw_width, draw_height) = (img_width * scale, img_height * scale);
+            let x = (page_width - draw_width) / 2.0;
+            let y = bottom + (top - bottom - draw_height) / 2.0;
+            ops.push(Operation::new("q", vec![]));
+            ops.push(Operation::new(
+                "cm",
+                vec![
+                    draw_width.into(),
+                    0.into(),
+                    0.into(),
+                    draw_height.into(),
+                    x.into(),
+                    y.into(),
+                ],
+            ));
+            ops.push(Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]));
+            ops.push(Operation::new("Q", vec![]));
+            resources.set("XObject", dictionary! { "Im1" => img_id });
+        }
+
+        write_page(
+            writer,
+            ops,
+            resources,
+            Vec::new(),
+            (page_width, page_height),
+        )
+    }
+}
+
+/// 目录页的排版
+struct TocLayout {
+    page_width: f32,
+    page_height: f32,
+    margin: f32,
+    font_size: f32,
+    line_height: f32,
+    heading_size: f32,
+}
+
+impl TocLayout {
+    fn new((page_width, page_height): (f32, f32)) -> Self {
+        let font_size = (page_width / 36.0).clamp(8.0, 20.0);
+        Self {
+            page_width,
+            page_height,
+            margin: page_width * MARGIN_RATIO,
+            font_size,
+            line_height: font_size * 1.8,
+            heading_size: font_size * 1.6,
+        }
+    }
+
+    /// 第一行条目上方的y坐标，第一页还要放`目录`标题
+    fn top(&self, is_first_page: bool) -> f32 {
+        let top = self.page_height - self.margin - self.heading_size;
+        if is_first_page {
+            top - self.heading_size
+        } else {
+            top
+        }
+    }
+
+    /// 一页能放下多少行条目
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn lines_per_page(&self, is_first_page: bool) -> usize {
+        let available_height = self.top(is_first_page) - self.margin;
+        if available_height < self.line_height {
+            return 0;
+        }
+        (available_height / self.line_height) as usize
+    }
+}
+
+/// 目录页，每个书签一行，点击跳转到书签指向的页面，放不下时分为多页
+///
+/// 用第一页的尺寸排版，第一页太矮(比如条漫)放不下`MIN_TOC_LINES_PER_PAGE`行时改用A4的尺寸
+#[allow(clippy::cast_precision_loss)]
+fn write_toc_pages(
+    writer: &mut PdfStreamWriter,
+    font_id: ObjectId,
+    page_size: (f32, f32),
+) -> eyre::Result<Vec<ObjectId>> {
+    let mut layout = TocLayout::new(page_size);
+    if layout.lines_per_page(true) < MIN_TOC_LINES_PER_PAGE {
+        layout = TocLayout::new(DEFAULT_PAGE_SIZE);
+    }
+    let TocLayout {
+        page_width,
+        page_height,
+        margin,
+        font_size,
+        line_height,
+        heading_size,
+    } = layout;
+
+    // (缩进层级, 标题, 指向的页面)
+    let mut entries = Vec::new();
+    flatten_bookmarks(writer.bookmarks(), 0, &mut entries);
+
+    let mut page_ids = Vec::new();
+    let mut entries = entries.into_iter().peekable();
+    while page_ids.is_empty() || entries.peek().is_some() {
+        let is_first_page = page_ids.is_empty();
+        let lines_per_page = layout.lines_per_page(is_first_page);
+        // 一行都放不下的话，永远也写不完目录
+        if lines_per_page == 0 && entries.peek().is_some() {
+            return Err(eyre!(
+                "目录页的尺寸`{page_width}x{page_height}`太小，放不下任何一行"
+            ));
+        }
+
+        let mut ops = Vec::new();
+        let mut annots = Vec::new();
+        let mut y = layout.top(is_first_page);
+        if is_first_page {
+            push_centered_text(&mut ops, "目录", heading_size, page_width, y + heading_size);
+        }
+
+        for (depth, title, dest_page_id) in entries.by_ref().take(lines_per_page) {
+            y -= line_height;
+            let x = margin + font_size * 2.0 * depth as f32;
+            let title = truncate_text(&title, font_size, page_width - margin - x);
+            push_text(&mut ops, &title, font_size, x, y);
+
+            // 链接覆盖整行，方便点击
+            let rect = vec![
+                x.into(),
+                (y - (line_height - font_size) / 2.0).into(),
+                (page_width - margin).into(),
+                (y + font_size + (line_height - font_size) / 2.0).into(),
+            ];
+            annots.push(Object::Dictionary(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => rect,
+                "Border" => vec![0.into(), 0.into(), 0.into()],
+                "Dest" => vec![Object::Reference(dest_page_id), "Fit".into()],
+            }));
+        }
+
+        let resources = dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        };
+        let page_id = write_page(writer, ops, resources, annots, (page_width, page_height))?;
+        page_ids.push(page_id);
+    }
+
+    Ok(page_ids)
+}
+
+fn flatten_bookmarks(
+    bookmarks: &[PdfBookmark],
+    depth: usize,
+    entries: &mut Vec<(usize, String, ObjectId)>,
+) {
+    for bookmark in bookmarks {
+        entries.push((depth, bookmark.title.clone(), bookmark.page_id));
+        flatten_bookmarks(&bookmark.children, depth + 1, entries);
+    }
+}
+
+fn write_page(
+    writer: &mut PdfStreamWriter,
+    ops: Vec<Operation>,
+    resources: Dictionary,
+    annots: Vec<Object>,
+    (page_width, page_height): (f32, f32),
+) -> eyre::Result<ObjectId> {
+    let content = Content { operations: ops };
+    let content_id = writer.add_object(&Object::Stream(Stream::new(
+        dictionary! {},
+        content.encode()?,
+    )))?;
+    let mut page = dictionary! {
+        "Contents" => content_id,
+        "Resources" => resources,
+        "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+    };
+    if !annots.is_empty() {
+        page.set("Annots", annots);
+    }
+    writer.add_front_page(page)
+}
+
+/// 写入Adobe预定义的宋体，阅读器会用系统中的中文字体显示，所以不需要嵌入字体文件
+fn write_cjk_font(writer: &mut PdfStreamWriter) -> eyre::Result<ObjectId> {
+    let descriptor_id = writer.add_object(&Object::Dictionary(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => "STSong-Light",
+        "Flags" => 6,
+        "FontBBox" => vec![(-25).into(), (-254).into(), 1000.into(), 880.into()],
+        "ItalicAngle" => 0,
+        "Ascent" => 880,
+        "Descent" => -120,
+        "CapHeight" => 880,
+        "StemV" => 93,
+    }))?;
+    let cid_font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType0",
+        "BaseFont" => "STSong-Light",
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("GB1"),
+            "Supplement" => 2,
+        },
+        "FontDescriptor" => descriptor_id,
+        "DW" => 1000,
+        // ASCII字符是半角的
+        "W" => vec![1.into(), 95.into(), 500.into()],
+    };
+    writer.add_object(&Object::Dictionary(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "STSong-Light",
+        "Encoding" => "UniGB-UCS2-H",
+        "DescendantFonts" => vec![Object::Dictionary(cid_font)],
+    }))
+}
+
+fn push_text(ops: &mut Vec<Operation>, text: &str, font_size: f32, x: f32, y: f32) {
+    ops.push(Operation::new("BT", vec![]));
+    ops.push(Operation::new("Tf", vec!["F1".into(), font_size.into()]));
+    ops.push(Operation::new("Td", vec![x.into(), y.into()]));
+    ops.push(Operation::new("Tj", vec![encode_text(text)]));
+    ops.push(Operation::new("ET", vec![]));
+}
+
+fn push_centered_text(
+    ops: &mut Vec<Operation>,
+    text: &str,
+    font_size: f32,
+    page_width: f32,
+    y: f32,
+) {
+    let x = (page_width - text_width_em(text) * font_size) / 2.0;
+    push_text(ops, text, font_size, x.max(0.0), y);
+}
+
+/// 把`text`编码为`UniGB-UCS2-H`使用的UCS-2，超出基本平面的字符替换为`?`
+fn encode_text(text: &str) -> Object {
+    let bytes = text
+        .chars()
+        .map(|c| u16::try_from(u32::from(c)).unwrap_or(u16::from(b'?')))
+        .flat_map(u16::to_be_bytes)
+        .collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// 文字宽度，单位为字号，ASCII字符占半个字宽
+fn text_width_em(text: &str) -> f32 {
+    text.chars()
+        .map(|c| if c.is_ascii() { 0.5 } else { 1.0 })
+        .sum()
+}
+
+/// 文字超过`max_width`时截断并加上省略号
+fn truncate_text(text: &str, font_size: f32, max_width: f32) -> String {
+    let max_em = max_width / font_size;
+    if text_width_em(text) <= max_em {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut width = text_width_em("…");
+    for c in text.chars() {
+        width += text_width_em(c.encode_utf8(&mut [0; 4]));
+        if width > max_em {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+use crate::types::ChapterInfo;
+
+/// 合并PDF的元数据，记录每个部分包含哪些章节，用于增量合并
+///
+/// 保存在合并PDF旁边，文件名为`{合并PDF的文件名}.合并元数据.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedPdfManifest {
+    /// 书签是否按分组嵌套，与本次合并不一致时不能增量合并
+    pub nested_bookmarks: bool,
+    /// 按顺序排列的各个部分
+    pub parts: Vec<MergedPdfPart>,
+}
+
+/// 合并PDF拆分后的一部分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedPdfPart {
+    pub path: PathBuf,
+    /// 封面页和目录页的数量，它们排在所有章节前面
+    pub front_page_count: usize,
+    pub chapters: Vec<MergedPdfChapter>,
+}
+
+/// 已合并的章节
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedPdfChapter {
+    pub chapter_info: ChapterInfo,
+    pub bookmark_title: String,
+    /// 章节在合并PDF中占多少页
+    pub page_count: usize,
+}
+
+impl MergedPdfManifest {
+    /// `merge_pdf_path`对应的元数据文件路径
+    pub fn path(merge_pdf_path: &Path) -> PathBuf {
+        merge_pdf_path.with_extension("合并元数据.json")
+    }
+
+    /// 读取元数据，文件不存在或无法解析时返回`None`，此时只能完全重新合并
+    pub fn load(manifest_path: &Path) -> Option<Self> {
+        let manifest_json = std::fs::read_to_string(manifest_path).ok()?;
+        serde_json::from_str(&manifest_json).ok()
+    }
+
+    pub fn save(&self, manifest_path: &Path) -> eyre::Result<()> {
+        let manifest_json =
+            serde_json::to_string_pretty(self).wrap_err("将MergedPdfManifest序列化为json失败")?;
+        std::fs::write(manifest_path, manifest_json)
+            .wrap_err(format!("写入文件`{}`失败", manifest_path.display()))?;
+        Ok(())
+    }
+
+    pub fn chapter_count(&self) -> usize {
+        self.parts.iter().map(|part| part.chapters.len()).sum()
+    }
+
+    /// 能否在已合并的PDF后面追加章节
+    ///
+    /// `chapter_uuids`为本次要合并的所有章节，已合并的章节必须按顺序位于最前面，
+    /// 否则说明有章节插入到了中间或被删除，需要完全重新合并
+    pub fn can_append(&self, chapter_uuids: &[&str], nested_bookmarks: bool) -> bool {
+        if self.nested_bookmarks != nested_bookmarks {
+            return false;
+        }
+        if self.parts.iter().any(|part| !part.path.is_file()) {
+            return false;
+        }
+
+        let mut merged_uuids = self
+            .parts
+            .iter()
+            .flat_map(|part| &part.chapters)
+            .map(|chapter| chapter.chapter_info.chapter_uuid.as_str());
+        let mut chapter_uuids = chapter_uuids.iter().copied();
+        merged_uuids.all(|merged_uuid| chapter_uuids.next() == Some(merged_uuid))
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::{Local, NaiveDate};
+use eyre::OptionExt;
+use lopdf::{dictionary, text_string, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::{
+    config::PdfConfig,
+    types::{ChapterInfo, Comic},
+    utils::escape_xml,
+};
+
+/// 写入PDF的Info字典和XMP的元数据
+pub struct PdfMetadata {
+    title: String,
+    authors: Vec<String>,
+    subject: String,
+    keywords: Vec<String>,
+    /// 章节的创建日期，没有则使用导出时的时间
+    creation_date: Option<NaiveDate>,
+}
+
+impl PdfMetadata {
+    /// 单个章节的PDF
+    pub fn from_chapter(comic: &Comic, chapter_info: &ChapterInfo) -> Self {
+        let title = format!("{} - {}", comic.comic.name, chapter_info.chapter_title);
+        Self::new(comic, title, chapter_info.created_date())
+    }
+
+    /// 合并后的PDF，以最后一个章节的创建日期为准，所有分组合并时`group_title`为`None`
+    pub fn from_merged(
+        comic: &Comic,
+        group_title: Option<&str>,
+        chapter_infos: &[ChapterInfo],
+    ) -> Self {
+        let title = match group_title {
+            Some(group_title) => format!("{} - {group_title}", comic.comic.name),
+            None => comic.comic.name.clone(),
+        };
+        let creation_date = chapter_infos
+            .iter()
+            .filter_map(ChapterInfo::created_date)
+            .max();
+        Self::new(comic, title, creation_date)
+    }
+
+    fn new(comic: &Comic, title: String, creation_date: Option<NaiveDate>) -> Self {
+        Self {
+            title,
+            authors: comic
+                .comic
+                .author
+                .iter()
+                .map(|author| author.name.clone())
+                .collect(),
+            subject: comic.comic.brief.clone(),
+            keywords: comic
+                .comic
+                .theme
+                .iter()
+                .map(|theme| theme.name.clone())
+                .collect(),
+            creation_date,
+        }
+    }
+
+    /// 把元数据和`pdf_config`中的阅读方式写入`doc`，必须在`doc.trailer`设置了`Root`之后调用
+    pub fn write_to(&self, doc: &mut Document, pdf_config: &PdfConfig) -> eyre::Result<()> {
+        let info_id = doc.add_object(self.info_dict());
+        doc.trailer.set("Info", info_id);
+
+        let metadata_id = pdf_config
+            .embed_xmp
+            .then(|| doc.add_object(self.xmp_stream()));
+
+        let catalog_id: ObjectId = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .ok()
+            .ok_or_eyre("PDF中没有`Root`")?;
+        let catalog = doc
+            .get_object_mut(catalog_id)
+            .and_then(Object::as_dict_mut)
+            .ok()
+            .ok_or_eyre("PDF的`Root`不是字典")?;
+
+        set_catalog_viewer_preferences(catalog, pdf_config);
+        if let Some(metadata_id) = metadata_id {
+            catalog.set("Metadata", metadata_id);
+        }
+
+        Ok(())
+    }
+
+    /// PDF的Info字典
+    pub fn info_dict(&self) -> Dictionary {
+        let now = Local::now();
+        let creation_date = match self.creation_date {
+            Some(date) => Object::string_literal(format!("D:{}000000", date.format("%Y%m%d"))),
+            None => Object::from(now),
+        };
+
+        dictionary! {
+            "Title" => text_string(&self.title),
+            "Author" => text_string(&self.authors.join(", ")),
+            "Subject" => text_string(&self.subject),
+            "Keywords" => text_string(&self.keywords.join(", ")),
+            "Creator" => text_string(env!("CARGO_PKG_NAME")),
+            "Producer" => text_string("lopdf"),
+            "CreationDate" => creation_date,
+            "ModDate" => Object::from(now),
+        }
+    }
+
+    /// XMP元数据流
+    pub fn xmp_stream(&self) -> Stream {
+        // XMP需要保持明文，方便不解析PDF结构的工具直接读取
+        Stream::new(
+            dictionary! {
+                "Type" => "Metadata",
+                "Subtype" => "XML",
+            },
+            self.to_xmp().into_bytes(),
+        )
+        .with_compression(false)
+    }
+
+    fn to_xmp(&self) -> String {
+        let creation_date = match self.creation_date {
+            Some(date) => date.format("%Y-%m-%d").to_string(),
+            None => Local::now().format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        };
+        let modify_date = Local::now().format("%Y-%m-%dT%H:%M:%S%:z").to_string();
+
+        let rdf_li = |items: &[String]| -> String {
+            items
+                .iter()
+                .map(|item| format!("<rdf:li>{}</rdf:li>", escape_xml(item)))
+                .collect()
+        };
+
+        format!(
+            r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about=""
+ xmlns:dc="http://purl.org/dc/elements/1.1/"
+ xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+ xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+<dc:format>application/pdf</dc:format>
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+<dc:creator><rdf:Seq>{authors}</rdf:Seq></dc:creator>
+<dc:description><rdf:Alt><rdf:li xml:lang="x-default">{subject}</rdf:li></rdf:Alt></dc:description>
+<dc:subject><rdf:Bag>{keywords}</rdf:Bag></dc:subject>
+<dc:language><rdf:Bag><rdf:li>zh</rdf:li></rdf:Bag></dc:language>
+<pdf:Keywords>{pdf_keywords}</pdf:Keywords>
+<pdf:Producer>lopdf</pdf:Producer>
+<xmp:CreatorTool>{creator_tool}</xmp:CreatorTool>
+<xmp:CreateDate>{creation_date}</xmp:CreateDate>
+<xmp:ModifyDate>{modify_date}</xmp:ModifyDate>
+</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+            bom = '\u{feff}',
+            title = escape_xml(&self.title),
+            authors = rdf_li(&self.authors),
+            subject = escape_xml(&self.subject),
+            keywords = rdf_li(&self.keywords),
+            pdf_keywords = escape_xml(&self.keywords.join(", ")),
+            creator_tool = env!("CARGO_PKG_NAME"),
+        )
+    }
+}
+
+/// 按`pdf_config`在`catalog`中设置翻页方向和页面布局
+pub fn set_catalog_viewer_preferences(catalog: &mut Dictionary, pdf_config: &PdfConfig) {
+    let mut viewer_preferences = dictionary! {
+        "DisplayDocTitle" => true,
+    };
+    if pdf_config.rtl {
+        viewer_preferences.set("Direction", "R2L");
+    }
+    catalog.set("ViewerPreferences", viewer_preferences);
+    if pdf_config.two_page_layout {
+        // 奇数页在右边，所以封面会单独占一页
+        catalog.set("PageLayout", "TwoPageRight");
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::WrapErr;
+use lopdf::{dictionary, text_string, Dictionary, Document, Object, ObjectId, StringFormat};
+
+use crate::{
+    config::PdfConfig,
+    export::pdf_metadata::{set_catalog_viewer_preferences, PdfMetadata},
+};
+
+/// 页面可以从父节点继承的属性
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// 边读边写的PDF合并器
+///
+/// 每次只处理一个章节PDF，把它的页面及页面引用的对象重新编号后立即写入文件，
+/// 最后再写入页面树、书签和交叉引用表，所以内存占用只与单个章节PDF的大小有关
+pub struct PdfStreamWriter {
+    writer: CountingWriter,
+    pdf_path: PathBuf,
+    /// 对象的偏移量，下标为对象编号减1，`None`表示已分配编号但还没写入
+    xref: Vec<Option<u64>>,
+    pages_id: ObjectId,
+    page_ids: Vec<ObjectId>,
+    /// 封面、目录等排在所有章节前面的页面，可以最后才写入
+    front_page_ids: Vec<ObjectId>,
+    /// 第一个章节第一页的宽高，生成的页面使用相同的尺寸
+    first_page_size: Option<(f32, f32)>,
+    bookmarks: Vec<PdfBookmark>,
+    /// 调用`start_bookmark_group`后，还没追加任何页面的分组书签标题
+    pending_group_title: Option<String>,
+    /// 之后追加的书签是否作为最后一个顶层书签的子书签
+    in_bookmark_group: bool,
+    /// 页码标签的起始页和标签
+    page_labels: Vec<(ObjectId, PageLabel)>,
+}
+
+/// 书签，点击后跳转到`page_id`
+pub struct PdfBookmark {
+    pub title: String,
+    pub page_id: ObjectId,
+    pub children: Vec<PdfBookmark>,
+}
+
+/// 页码标签，从起始页开始，阅读器显示为`prefix`加上从1开始的页码
+struct PageLabel {
+    prefix: String,
+    /// 为`false`时只显示`prefix`
+    numbered: bool,
+}
+
+impl PdfStreamWriter {
+    pub fn create(pdf_path: &Path) -> eyre::Result<Self> {
+        let file =
+            File::create(pdf_path).wrap_err(format!("创建文件`{}`失败", pdf_path.display()))?;
+        let mut writer = CountingWriter {
+            inner: BufWriter::new(file),
+            bytes_written: 0,
+        };
+        // 第二行的注释包含非ASCII字节，告诉其他程序这是二进制文件
+        writer
+            .write_all(b"%PDF-1.5\n%\xE2\xE3\xCF\xD3\n")
+            .wrap_err(format!("写入`{}`失败", pdf_path.display()))?;
+
+        let mut pdf_writer = Self {
+            writer,
+            pdf_path: pdf_path.to_path_buf(),
+            xref: Vec::new(),
+            pages_id: (0, 0),
+            page_ids: Vec::new(),
+            front_page_ids: Vec::new(),
+            first_page_size: None,
+            bookmarks: Vec::new(),
+            pending_group_title: None,
+            in_bookmark_group: false,
+            page_labels: Vec::new(),
+        };
+        // 页面树的编号先预留，所有页面都写入后才写页面树
+        pdf_writer.pages_id = pdf_writer.alloc_id();
+
+        Ok(pdf_writer)
+    }
+
+    /// 已写入的字节数
+    pub fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written
+    }
+
+    /// 已写入的书签
+    pub fn bookmarks(&self) -> &[PdfBookmark] {
+        &self.bookmarks
+    }
+
+    /// 第一个章节第一页的宽高，还没追加任何页面时返回`None`
+    pub fn first_page_size(&self) -> Option<(f32, f32)> {
+        self.first_page_size
+    }
+
+    /// 之后追加的章节书签都作为标题为`title`的书签的子书签，分组书签指向其中的第一页
+    pub fn start_bookmark_group(&mut self, title: String) {
+        self.pending_group_title = Some(title);
+        self.in_bookmark_group = true;
+    }
+
+    /// 从`first_page_id`开始的页面使用`prefix`作为页码标签，直到下一个标签的起始页
+    pub fn add_page_label(&mut self, first_page_id: ObjectId, prefix: String, numbered: bool) {
+        let label = PageLabel { prefix, numbered };
+        self.page_labels.push((first_page_id, label));
+    }
+
+    /// 写入`object`，返回它的编号
+    pub fn add_object(&mut self, object: &Object) -> eyre::Result<ObjectId> {
+        let id = self.alloc_id();
+        self.write_indirect_object(id, object)?;
+        Ok(id)
+    }
+
+    /// 写入排在所有章节前面的页面`page`，按调用顺序排列，返回页面的编号
+    pub fn add_front_page(&mut self, mut page: Dictionary) -> eyre::Result<ObjectId> {
+        page.set("Type", "Page");
+        page.set("Parent", self.pages_id);
+        let page_id = self.add_object(&Object::Dictionary(page))?;
+        self.front_page_ids.push(page_id);
+        Ok(page_id)
+    }
+
+    /// 把`doc`中的`old_page_ids`按顺序追加到末尾，返回这些页面的新编号
+    pub fn append_pages(
+        &mut self,
+        doc: &Document,
+        old_page_ids: &[ObjectId],
+    ) -> eyre::Result<Vec<ObjectId>> {
+        // 旧编号 -> 新编号
+        let mut id_map = HashMap::new();
+        // 页面需要补上继承的属性，并去掉`Parent`，避免把原来的页面树也复制过来
+        let mut prepared_pages = HashMap::new();
+        let mut stack = Vec::new();
+        for &old_page_id in old_page_ids {
+            id_map.insert(old_page_id, self.alloc_id());
+            prepared_pages.insert(old_page_id, prepare_page(doc, old_page_id));
+            stack.push(old_page_id);
+        }
+
+        // 找出页面直接或间接引用的所有对象，并分配新编号
+        let mut old_ids = Vec::new();
+        while let Some(old_id) = stack.pop() {
+            old_ids.push(old_id);
+            let object = match prepared_pages.get(&old_id) {
+                Some(page) => Some(page),
+                None => doc.get_object(old_id).ok(),
+            };
+            let Some(object) = object else {
+                continue;
+            };
+
+            let mut refs = Vec::new();
+            collect_references(object, &mut refs);
+            for ref_id in refs {
+                if let Entry::Vacant(entry) = id_map.entry(ref_id) {
+                    entry.insert(self.alloc_id());
+                    stack.push(ref_id);
+                }
+            }
+        }
+
+        // 逐个对象重新编号后写入，不存在的对象写为null
+        for old_id in old_ids {
+            let mut object = match prepared_pages.remove(&old_id) {
+                Some(page) => page,
+                None => doc.get_object(old_id).cloned().unwrap_or(Object::Null),
+            };
+            remap_references(&mut object, &id_map);
+            if let Object::Dictionary(page_dict) = &mut object {
+                if old_page_ids.contains(&old_id) {
+                    page_dict.set("Parent", self.pages_id);
+                    if self.first_page_size.is_none() {
+                        self.first_page_size = page_size(page_dict);
+                    }
+                }
+            }
+            self.write_indirect_object(id_map[&old_id], &object)?;
+        }
+
+        let new_page_ids: Vec<ObjectId> = old_page_ids
+            .iter()
+            .map(|old_page_id| id_map[old_page_id])
+            .collect();
+        self.page_ids.extend(&new_page_ids);
+
+        Ok(new_page_ids)
+    }
+
+    /// 添加标题为`title`的书签，调用过`start_bookmark_group`时作为分组书签的子书签
+    pub fn add_bookmark(&mut self, title: String, page_id: ObjectId) {
+        let bookmark = PdfBookmark {
+            title,
+            page_id,
+            children: Vec::new(),
+        };
+        if let Some(group_title) = self.pending_group_title.take() {
+            self.bookmarks.push(PdfBookmark {
+                title: group_title,
+                page_id,
+                children: Vec::new(),
+            });
+        }
+        match self.bookmarks.last_mut() {
+            Some(group) if self.in_bookmark_group => group.children.push(bookmark),
+            _ => self.bookmarks.push(bookmark),
+        }
+    }
+
+    /// 封面、目录等排在所有章节前面的页面数
+    pub fn front_page_count(&self) -> usize {
+        self.front_page_ids.len()
+    }
+
+    /// 写入页面树、书签、页码标签、元数据和交叉引用表，完成PDF
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn finish(mut self, metadata: &PdfMetadata, pdf_config: &PdfConfig) -> eyre::Result<()> {
+        let mut all_page_ids = std::mem::take(&mut self.front_page_ids);
+        all_page_ids.append(&mut self.page_ids);
+        let pages_dict = dictionary! {
+            "Type" => "Pages",
+            "Count" => all_page_ids.len() as i64,
+            "Kids" => all_page_ids.iter().copied().map(Object::Reference).collect::<Vec<_>>(),
+        };
+        self.write_indirect_object(self.pages_id, &Object::Dictionary(pages_dict))?;
+
+        let outlines_id = self.write_outlines()?;
+        let page_labels = self.page_labels_dict(&all_page_ids);
+
+        let info_id = self.alloc_id();
+        self.write_indirect_object(info_id, &Object::Dictionary(metadata.info_dict()))?;
+
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        };
+        if let Some(outlines_id) = outlines_id {
+            catalog.set("Outlines", outlines_id);
+        }
+        if let Some(page_labels) = page_labels {
+            catalog.set("PageLabels", page_labels);
+        }
+        if pdf_config.embed_xmp {
+            let metadata_id = self.alloc_id();
+            self.write_indirect_object(metadata_id, &Object::Stream(metadata.xmp_stream()))?;
+            catalog.set("Metadata", metadata_id);
+        }
+        set_catalog_viewer_preferences(&mut catalog, pdf_config);
+        let catalog_id = self.alloc_id();
+        self.write_indirect_object(catalog_id, &Object::Dictionary(catalog))?;
+
+        self.write_xref_and_trailer(catalog_id, info_id)
+            .wrap_err(format!("写入`{}`失败", self.pdf_path.display()))?;
+        self.writer
+            .flush()
+            .wrap_err(format!("写入`{}`失败", self.pdf_path.display()))?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn alloc_id(&mut self) -> ObjectId {
+        self.xref.push(None);
+        (self.xref.len() as u32, 0)
+    }
+
+    fn write_indirect_object(&mut self, id: ObjectId, object: &Object) -> eyre::Result<()> {
+        self.xref[id.0 as usize - 1] = Some(self.writer.bytes_written);
+        write_indirect_object(&mut self.writer, id, object).wrap_err(format!(
+            "将对象`{} {}`写入`{}`失败",
+            id.0,
+            id.1,
+            self.pdf_path.display()
+        ))
+    }
+
+    /// 写入书签，返回书签根节点的编号，没有书签时返回`None`
+    fn write_outlines(&mut self) -> eyre::Result<Option<ObjectId>> {
+        if self.bookmarks.is_empty() {
+            return Ok(None);
+        }
+
+        let outlines_id = self.alloc_id();
+        let bookmarks = std::mem::take(&mut self.bookmarks);
+        let (first_id, last_id, count) = self.write_outline_items(&bookmarks, outlines_id)?;
+
+        let outlines = dictionary! {
+            "Type" => "Outlines",
+            "First" => first_id,
+            "Last" => last_id,
+            "Count" => count,
+        };
+        self.write_indirect_object(outlines_id, &Object::Dictionary(outlines))?;
+
+        Ok(Some(outlines_id))
+    }
+
+    /// 递归写入`bookmarks`，返回第一项和最后一项的编号，以及展开后可见的书签数
+    #[allow(clippy::cast_possible_wrap)]
+    fn write_outline_items(
+        &mut self,
+        bookmarks: &[PdfBookmark],
+        parent_id: ObjectId,
+    ) -> eyre::Result<(ObjectId, ObjectId, i64)> {
+        let item_ids: Vec<ObjectId> = (0..bookmarks.len()).map(|_| self.alloc_id()).collect();
+        let mut count = bookmarks.len() as i64;
+
+        for (i, bookmark) in bookmarks.iter().enumerate() {
+            let mut item = dictionary! {
+                "Title" => text_string(&bookmark.title),
+                "Parent" => parent_id,
+                "Dest" => vec![Object::Reference(bookmark.page_id), "Fit".into()],
+            };
+            if i > 0 {
+                item.set("Prev", item_ids[i - 1]);
+            }
+            if let Some(&next_id) = item_ids.get(i + 1) {
+                item.set("Next", next_id);
+            }
+            if !bookmark.children.is_empty() {
+                let (first_id, last_id, children_count) =
+                    self.write_outline_items(&bookmark.children, item_ids[i])?;
+                item.set("First", first_id);
+                item.set("Last", last_id);
+                // 正数表示默认展开
+                item.set("Count", children_count);
+                count += children_count;
+            }
+            self.write_indirect_object(item_ids[i], &Object::Dictionary(item))?;
+        }
+
+        Ok((item_ids[0], item_ids[item_ids.len() - 1], count))
+    }
+
+    /// 按`all_page_ids`的顺序生成页码标签的数字树，没有标签时返回`None`
+    #[allow(clippy::cast_possible_wrap)]
+    fn page_labels_dict(&mut self, all_page_ids: &[ObjectId]) -> Option<Dictionary> {
+        if self.page_labels.is_empty() {
+            return None;
+        }
+
+        let page_indexes: HashMap<ObjectId, usize> = all_page_ids
+            .iter()
+            .enumerate()
+            .map(|(i, &page_id)| (page_id, i))
+            .collect();
+        let mut labels: Vec<(usize, Dictionary)> = std::mem::take(&mut self.page_labels)
+            .into_iter()
+            .filter_map(|(page_id, label)| {
+                let page_index = *page_indexes.get(&page_id)?;
+                let mut label_dict = dictionary! {
+                    "P" => text_string(&label.prefix),
+                };
+                if label.numbered {
+                    label_dict.set("S", "D");
+                }
+                Some((page_index, label_dict))
+            })
+            .collect();
+        labels.sort_by_key(|(page_index, _)| *page_index);
+        // 数字树必须从第一页开始
+        if labels
+            .first()
+            .is_none_or(|(page_index, _)| *page_index != 0)
+        {
+            labels.insert(0, (0, dictionary! { "S" => "D" }));
+        }
+
+        let nums: Vec<Object> = labels
+            .into_iter()
+            .flat_map(|(page_index, label_dict)| {
+                [
+                    Object::Integer(page_index as i64),
+                    Object::Dictionary(label_dict),
+                ]
+            })
+            .collect();
+        Some(dictionary! { "Nums" => nums })
+    }
+
+    fn write_xref_and_trailer(
+        &mut self,
+        catalog_id: ObjectId,
+        info_id: ObjectId,
+    ) -> std::io::Result<()> {
+        let xref_offset = self.writer.bytes_written;
+        let size = self.xref.len() + 1;
+
+        let writer = &mut self.writer;
+        writeln!(writer, "xref\n0 {size}")?;
+        writer.write_all(b"0000000000 65535 f \n")?;
+        for offset in &self.xref {
+            match offset {
+                Some(offset) => writeln!(writer, "{offset:010} 00000 n ")?,
+                None => writer.write_all(b"0000000000 65535 f \n")?,
+            }
+        }
+        writeln!(
+            writer,
+            "trailer\n<< /Size {size} /Root {} {} R /Info {} {} R >>\nstartxref\n{xref_offset}\n%%EOF",
+            catalog_id.0, catalog_id.1, info_id.0, info_id.1
+        )
+    }
+}
+
+/// 从页面的`MediaBox`中获取宽高
+fn page_size(page_dict: &Dictionary) -> Option<(f32, f32)> {
+    let media_box = page_dict.get(b"MediaBox").and_then(Object::as_array).ok()?;
+    let [x1, y1, x2, y2] = media_box.as_slice() else {
+        return None;
+    };
+    let (x1, y1) = (x1.as_float().ok()?, y1.as_float().ok()?);
+    let (x2, y2) = (x2.as_float().ok()?, y2.as_float().ok()?);
+    Some(((x2 - x1).abs(), (y2 - y1).abs()))
+}
+
+/// 复制`page_id`对应的页面，补上从父节点继承的属性，并去掉`Parent`
+fn prepare_page(doc: &Document, page_id: ObjectId) -> Object {
+    let Ok(mut page_dict) = doc.get_object(page_id).and_then(Object::as_dict).cloned() else {
+        return Object::Null;
+    };
+
+    let mut parent_id = page_dict.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(id) = parent_id {
+        let Ok(parent_dict) = doc.get_object(id).and_then(Object::as_dict) else {
+            break;
+        };
+        for key in INHERITABLE_PAGE_KEYS {
+            if !page_dict.has(key) {
+                if let Ok(value) = parent_dict.get(key) {
+                    page_dict.set(key, value.clone());
+                }
+            }
+        }
+        parent_id = parent_dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .ok();
+    }
+    page_dict.remove(b"Parent");
+
+    Object::Dictionary(page_dict)
+}
+
+/// 收集`object`中的所有引用
+fn collect_references(object: &Object, refs: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => refs.push(*id),
+        Object::Array(array) => {
+            for item in array {
+                collect_references(item, refs);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter() {
+                collect_references(value, refs);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter() {
+                collect_references(value, refs);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 把`object`中的引用按`id_map`替换为新编号，找不到的引用替换为null
+fn remap_references(object: &mut Object, id_map: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => match id_map.get(id) {
+            Some(new_id) => *id = *new_id,
+            None => *object = Object::Null,
+        },
+        Object::Array(array) => {
+            for item in array {
+                remap_references(item, id_map);
+            }
+        }
+        Object::Dictionary(dict) => remap_dict_references(dict, id_map),
+        Object::Stream(stream) => remap_dict_references(&mut stream.dict, id_map),
+        _ => {}
+    }
+}
+
+fn remap_dict_references(dict: &mut Dictionary, id_map: &HashMap<ObjectId, ObjectId>) {
+    for (_, value) in dict.iter_mut() {
+        remap_references(value, id_map);
+    }
+}
+
+fn write_indirect_object(
+    writer: &mut impl Write,
+    id: ObjectId,
+    object: &Object,
+) -> std::io::Result<()> {
+    writeln!(writer, "{} {} obj", id.0, id.1)?;
+    write_object(writer, object)?;
+    writer.write_all(b"\nendobj\n")
+}
+
+/// 把`object`序列化为PDF语法写入`writer`
+///
+/// lopdf没有公开单个对象的序列化，而合并时需要边读边写，所以自己实现
+#[allow(clippy::cast_possible_wrap)]
+fn write_object(writer: &mut impl Write, object: &Object) -> std::io::Result<()> {
+    match object {
+        Object::Null => writer.write_all(b"null"),
+        Object::Boolean(value) => writer.write_all(if *value { b"true" } else { b"false" }),
+        Object::Integer(value) => write!(writer, "{value}"),
+        Object::Real(value) => write!(writer, "{value}"),
+        Object::Name(name) => write_name(writer, name),
+        Object::String(text, StringFormat::Literal) => {
+            writer.write_all(b"(")?;
+            for &byte in text {
+                match byte {
+                    b'\\' | b'(' | b')' => writer.write_all(&[b'\\', byte])?,
+                    b'\r' => writer.write_all(b"\\r")?,
+                    _ => writer.write_all(&[byte])?,
+                }
+            }
+            writer.write_all(b")")
+        }
+        Object::String(text, StringFormat::Hexadecimal) => {
+            writer.write_all(b"<")?;
+            for byte in text {
+                write!(writer, "{byte:02X}")?;
+            }
+            writer.write_all(b">")
+        }
+        Object::Array(array) => {
+            writer.write_all(b"[")?;
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b" ")?;
+                }
+                write_object(writer, item)?;
+            }
+            writer.write_all(b"]")
+        }
+        Object::Dictionary(dict) => write_dictionary(writer, dict),
+        Object::Stream(stream) => {
+            // 以实际的内容长度为准，原来的`Length`可能是间接引用
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            write_dictionary(writer, &dict)?;
+            writer.write_all(b"\nstream\n")?;
+            writer.write_all(&stream.content)?;
+            writer.write_all(b"\nendstream")
+        }
+        Object::Reference((id, generation)) => write!(writer, "{id} {generation} R"),
+    }
+}
+
+fn write_name(writer: &mut impl Write, name: &[u8]) -> std::io::Result<()> {
+    writer.write_all(b"/")?;
+    for &byte in name {
+        // 空白、分隔符和非可见ASCII字符需要用#转义
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            write!(writer, "#{byte:02X}")?;
+        } else {
+            writer.write_all(&[byte])?;
+        }
+    }
+    Ok(())
+}
+
+fn write_dictionary(writer: &mut impl Write, dict: &Dictionary) -> std::io::Result<()> {
+    writer.write_all(b"<<")?;
+    for (key, value) in dict.iter() {
+        write_name(writer, key)?;
+        writer.write_all(b" ")?;
+        write_object(writer, value)?;
+    }
+    writer.write_all(b">>")
+}
+
+/// 记录已写入字节数的`BufWriter`，用于生成交叉引用表
+struct CountingWriter {
+    inner: BufWriter<File>,
+    bytes_written: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,121 @@
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GenericImageView, Pixel, RgbImage,
+};
+
+use crate::config::WebtoonConfig;
+
+/// 把条漫的切片拼接起来，再按页面的高宽比重新切分成页
+///
+/// 切片依次通过`push`传入，每凑够一页就立即返回，避免把整个章节的图片同时放在内存中
+pub struct StripStitcher {
+    page_aspect_ratio: f32,
+    whitespace_tolerance: u8,
+    /// 还没被切分成页的部分，宽度以第一张切片为准
+    buffer: Option<RgbImage>,
+}
+
+impl StripStitcher {
+    pub fn new(config: &WebtoonConfig) -> Self {
+        Self {
+            page_aspect_ratio: config.page_aspect_ratio,
+            whitespace_tolerance: config.whitespace_tolerance,
+            buffer: None,
+        }
+    }
+
+    /// 拼接`strip`，返回凑够的页
+    pub fn push(&mut self, strip: DynamicImage) -> Vec<DynamicImage> {
+        let mut buffer = match self.buffer.take() {
+            None => strip.to_rgb8(),
+            Some(buffer) => {
+                // 宽度不一致的切片缩放到与第一张切片相同的宽度
+                let strip = resize_to_width(strip, buffer.width()).to_rgb8();
+                let mut stitched = RgbImage::new(buffer.width(), buffer.height() + strip.height());
+                imageops::replace(&mut stitched, &buffer, 0, 0);
+                imageops::replace(&mut stitched, &strip, 0, i64::from(buffer.height()));
+                stitched
+            }
+        };
+
+        let width = buffer.width();
+        let page_height = self.page_height(width);
+        let mut pages = vec![];
+        while buffer.height() >= page_height {
+            let cut_row = self.find_cut_row(&buffer, page_height);
+            let page = imageops::crop_imm(&buffer, 0, 0, width, cut_row).to_image();
+            pages.push(DynamicImage::ImageRgb8(page));
+            buffer = imageops::crop_imm(&buffer, 0, cut_row, width, buffer.height() - cut_row)
+                .to_image();
+        }
+
+        if buffer.height() > 0 {
+            self.buffer = Some(buffer);
+        }
+
+        pages
+    }
+
+    /// 返回剩下的不足一页的部分，如果剩下的部分全是空白则返回`None`
+    pub fn finish(self) -> Option<DynamicImage> {
+        let buffer = self.buffer.as_ref()?;
+        if (0..buffer.height()).all(|y| self.is_whitespace_row(buffer, y)) {
+            return None;
+        }
+
+        self.buffer.map(DynamicImage::ImageRgb8)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn page_height(&self, width: u32) -> u32 {
+        let page_height = (width as f32 * self.page_aspect_ratio).round() as u32;
+        page_height.max(1)
+    }
+
+    /// 在`page_height`往上半页的范围内找最靠下的空白行，找不到就直接在`page_height`处切分
+    fn find_cut_row(&self, buffer: &RgbImage, page_height: u32) -> u32 {
+        let min_cut_row = (page_height / 2).max(1);
+        (min_cut_row..=page_height)
+            .rev()
+            .find(|&y| y < buffer.height() && self.is_whitespace_row(buffer, y))
+            .unwrap_or(page_height)
+    }
+
+    fn is_whitespace_row(&self, buffer: &RgbImage, y: u32) -> bool {
+        let mut min_luma = u8::MAX;
+        let mut max_luma = u8::MIN;
+        for x in 0..buffer.width() {
+            let luma = buffer.get_pixel(x, y).to_luma()[0];
+            min_luma = min_luma.min(luma);
+            max_luma = max_luma.max(luma);
+            if max_luma - min_luma > self.whitespace_tolerance {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 按比例把`img`缩放到`width`宽
+pub fn resize_to_width(img: DynamicImage, width: u32) -> DynamicImage {
+    let (src_width, src_height) = img.dimensions();
+    if src_width == width || src_width == 0 {
+        return img;
+    }
+
+    let height = scaled_height(src_width, src_height, width);
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// 计算`src_width`x`src_height`的图片按比例缩放到`width`宽后的高度
+#[allow(clippy::cast_possible_truncation)]
+pub fn scaled_height(src_width: u32, src_height: u32, width: u32) -> u32 {
+    if src_width == width || src_width == 0 {
+        return src_height;
+    }
+
+    let height = u64::from(src_height) * u64::from(width) / u64::from(src_width);
+    (height as u32).max(1)
+}
@@ -5,7 +5,8 @@ use tauri::{AppHandle, Manager, State};
 
 use crate::{
     account_pool::AccountPool, config::Config, copy_client::CopyClient,
-    downloader::download_manager::DownloadManager, export::ComicExportLock,
+    downloader::download_manager::DownloadManager, export::ExportQueue,
+    library_index::LibraryIndex,
 };
 
 pub trait EyreReportToMessage {
@@ -90,7 +91,8 @@ pub trait AppHandleExt {
     fn get_copy_client(&self) -> State<'_, CopyClient>;
     fn get_download_manager(&self) -> State<'_, DownloadManager>;
     fn get_account_pool(&self) -> State<'_, AccountPool>;
-    fn get_export_lock(&self) -> State<'_, ComicExportLock>;
+    fn get_export_queue(&self) -> State<'_, ExportQueue>;
+    fn get_library_index(&self) -> State<'_, LibraryIndex>;
 }
 
 impl AppHandleExt for AppHandle {
@@ -106,7 +108,10 @@ impl AppHandleExt for AppHandle {
     fn get_account_pool(&self) -> State<'_, AccountPool> {
         self.state::<AccountPool>()
     }
-    fn get_export_lock(&self) -> State<'_, ComicExportLock> {
-        self.state::<ComicExportLock>()
+    fn get_export_queue(&self) -> State<'_, ExportQueue> {
+        self.state::<ExportQueue>()
+    }
+    fn get_library_index(&self) -> State<'_, LibraryIndex> {
+        self.state::<LibraryIndex>()
     }
 }
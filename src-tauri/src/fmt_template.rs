@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+
+use eyre::{eyre, OptionExt, WrapErr};
+use serde::Serialize;
+
+use crate::{utils, zh_convert};
+
+const FILTER_NAMES: [&str; 8] = [
+    "truncate", "default", "upper", "lower", "trim", "s2t", "t2s", "pinyin",
+];
+
+/// 漫画级别的字段，所有模板都能使用
+pub const COMIC_FIELDS: [&str; 9] = [
+    "comic_uuid",
+    "comic_path_word",
+    "comic_title",
+    "alias",
+    "author",
+    "publish_year",
+    "region",
+    "status",
+    "theme",
+];
+/// 分组级别的字段，`comic_dir_fmt`中不能使用
+pub const GROUP_FIELDS: [&str; 3] = ["group_path_word", "group_title", "group_size"];
+/// 章节级别的字段，只能用在`chapter_dir_fmt`和`export_dir_fmt`的最后一层
+pub const CHAPTER_FIELDS: [&str; 6] = [
+    "chapter_uuid",
+    "chapter_title",
+    "order",
+    "chapter_index",
+    "page_count",
+    "chapter_date",
+];
+
+/// 目录名和导出路径的模板，`comic_dir_fmt`、`chapter_dir_fmt`、`export_dir_fmt`和各种合并模板共用
+///
+/// ### 语法
+/// - `{field}`：字段的值，`{field:0>3}`这样的格式说明与`strfmt`相同
+/// - `{field|filter|filter:arg}`：依次用过滤器处理字段的值，格式说明在所有过滤器之后应用
+/// - `<...>`：条件片段，其中所有字段的值都不为空时才输出，比如`{order}< {chapter_title}>`
+/// - `{{`、`}}`：输出`{`、`}`
+///
+/// ### 过滤器
+/// - `truncate:N`：最多保留N个字符
+/// - `default:other`：值为空时改用`other`字段的值，`other`不是字段时直接用`other`本身
+/// - `upper`、`lower`：转为大写、小写
+/// - `trim`：去掉首尾的空白
+/// - `s2t`、`t2s`：简体转繁体、繁体转简体，只按字转换内置对照表中的常用字
+/// - `pinyin`：转为拼音，结果只包含ASCII字符
+///
+/// 配置中的`fmt_text_conversion`会在构造模板参数时统一转换所有文字字段，过滤器在这之后应用
+///
+/// `order`带小数时只对整数部分应用格式说明，比如`{order:0>3}`对`1.5`输出`001.5`
+#[derive(Debug, Clone)]
+pub struct FmtTemplate {
+    /// 完整的模板，模板有多层时为包含所有层的模板，用于错误提示
+    fmt: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    /// `<...>`中的内容
+    Conditional(Vec<Segment>),
+}
+
+#[derive(Debug, Clone)]
+struct Placeholder {
+    /// 占位符原文，比如`{chapter_title|truncate:40}`，用于错误提示
+    text: String,
+    /// 占位符在完整模板中的位置，按字符计数，从1开始
+    position: usize,
+    field: String,
+    spec: String,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Truncate(usize),
+    Default(String),
+    Upper,
+    Lower,
+    Trim,
+    S2t,
+    T2s,
+    Pinyin,
+}
+
+impl FmtTemplate {
+    /// 按`/`拆分`fmt`，分别解析每一层
+    ///
+    /// 出错时报告的是在整个`fmt`中的位置，而不是在某一层中的位置
+    fn parse_layers(fmt: &str) -> eyre::Result<Vec<Self>> {
+        // 当前层之前的字符数，包括分隔各层的`/`
+        let mut offset = 0;
+        let mut layers = Vec::new();
+        for layer in fmt.split('/') {
+            layers.push(Self::parse_layer(fmt, layer, offset)?);
+            offset += layer.chars().count() + 1;
+        }
+        Ok(layers)
+    }
+
+    /// 解析`fmt`中的一层`layer`，`offset`为`layer`之前的字符数，所有位置都加上`offset`
+    fn parse_layer(fmt: &str, layer: &str, offset: usize) -> eyre::Result<Self> {
+        // 把在`layer`中的下标转为在`fmt`中的位置
+        let position = |i: usize| offset + i + 1;
+        let chars: Vec<char> = layer.chars().collect();
+        // 遇到`<`时把外层已解析的内容压栈，遇到`>`时再弹出
+        let mut outer_segments: Vec<(usize, Vec<Segment>)> = Vec::new();
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '{' if chars.get(i + 1) == Some(&'{') => {
+                    literal.push('{');
+                    i += 2;
+                }
+                '}' if chars.get(i + 1) == Some(&'}') => {
+                    literal.push('}');
+                    i += 2;
+                }
+                '{' => {
+                    let Some(len) = chars[i..].iter().position(|&c| c == '}') else {
+                        let text: String = chars[i..].iter().collect();
+                        return Err(template_err(fmt, position(i), &text, "`{`没有对应的`}`"));
+                    };
+                    let text: String = chars[i..=i + len].iter().collect();
+                    let placeholder = Placeholder::parse(fmt, text, position(i))?;
+                    flush_literal(&mut literal, &mut segments);
+                    segments.push(Segment::Placeholder(placeholder));
+                    i += len + 1;
+                }
+                '}' => {
+                    return Err(template_err(
+                        fmt,
+                        position(i),
+                        "}",
+                        "多余的`}`，要输出`}`请写成`}}`",
+                    ));
+                }
+                '<' => {
+                    flush_literal(&mut literal, &mut segments);
+                    outer_segments.push((position(i), std::mem::take(&mut segments)));
+                    i += 1;
+                }
+                '>' => {
+                    let Some((_, outer)) = outer_segments.pop() else {
+                        return Err(template_err(
+                            fmt,
+                            position(i),
+                            ">",
+                            "多余的`>`，没有对应的`<`",
+                        ));
+                    };
+                    flush_literal(&mut literal, &mut segments);
+                    let inner = std::mem::replace(&mut segments, outer);
+                    segments.push(Segment::Conditional(inner));
+                    i += 1;
+                }
+                c => {
+                    literal.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        if let Some((open_position, _)) = outer_segments.pop() {
+            return Err(template_err(fmt, open_position, "<", "`<`没有对应的`>`"));
+        }
+        flush_literal(&mut literal, &mut segments);
+
+        Ok(FmtTemplate {
+            fmt: fmt.to_string(),
+            segments,
+        })
+    }
+
+    /// 模板中用到的所有字段，包括`default`过滤器的参数
+    pub fn used_fields(&self) -> Vec<&str> {
+        let mut fields = Vec::new();
+        collect_used_fields(&self.segments, &mut fields);
+        fields
+    }
+
+    /// 第一个字段不属于`fields`的占位符，`default`过滤器的参数不是字段时会当作文字，所以不检查
+    fn find_unknown_placeholder(&self, fields: &[&str]) -> Option<&Placeholder> {
+        find_unknown_placeholder(&self.segments, fields)
+    }
+
+    pub fn render(&self, vars: &HashMap<String, String>) -> eyre::Result<String> {
+        let mut output = String::new();
+        render_segments(&self.fmt, &self.segments, vars, &mut output)?;
+        Ok(output)
+    }
+}
+
+impl Placeholder {
+    fn parse(fmt: &str, text: String, position: usize) -> eyre::Result<Self> {
+        let inner = &text[1..text.len() - 1];
+        let mut parts = inner.split('|');
+        let head = parts.next().unwrap_or_default();
+        let (field, spec) = head.split_once(':').unwrap_or((head, ""));
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(template_err(fmt, position, &text, "字段名不能为空"));
+        }
+
+        let mut filters = Vec::new();
+        for part in parts {
+            let (name, arg) = match part.split_once(':') {
+                Some((name, arg)) => (name.trim(), Some(arg)),
+                None => (part.trim(), None),
+            };
+            let filter = match (name, arg) {
+                ("truncate", Some(arg)) => match arg.trim().parse() {
+                    Ok(max_chars) if max_chars > 0 => Filter::Truncate(max_chars),
+                    _ => {
+                        let reason = format!(
+                            "`truncate`的参数必须是正整数，比如`truncate:40`，而不是`{arg}`"
+                        );
+                        return Err(template_err(fmt, position, &text, &reason));
+                    }
+                },
+                ("default", Some(arg)) => Filter::Default(arg.trim().to_string()),
+                ("upper", None) => Filter::Upper,
+                ("lower", None) => Filter::Lower,
+                ("trim", None) => Filter::Trim,
+                ("s2t", None) => Filter::S2t,
+                ("t2s", None) => Filter::T2s,
+                ("pinyin", None) => Filter::Pinyin,
+                ("truncate" | "default", None) => {
+                    let reason = format!("过滤器`{name}`需要参数，比如`{name}:xxx`");
+                    return Err(template_err(fmt, position, &text, &reason));
+                }
+                ("upper" | "lower" | "trim" | "s2t" | "t2s" | "pinyin", Some(_)) => {
+                    let reason = format!("过滤器`{name}`不需要参数");
+                    return Err(template_err(fmt, position, &text, &reason));
+                }
+                _ => {
+                    let reason = format!("未知的过滤器`{name}`，可用的过滤器有 {FILTER_NAMES:?}");
+                    return Err(template_err(fmt, position, &text, &reason));
+                }
+            };
+            filters.push(filter);
+        }
+
+        Ok(Placeholder {
+            field: field.to_string(),
+            spec: spec.to_string(),
+            filters,
+            text,
+            position,
+        })
+    }
+
+    fn render(&self, fmt: &str, vars: &HashMap<String, String>) -> eyre::Result<String> {
+        let Some(value) = vars.get(&self.field) else {
+            let mut fields: Vec<&str> = vars.keys().map(String::as_str).collect();
+            fields.sort_unstable();
+            let reason = format!("没有`{}`字段，可用的字段有 {fields:?}", self.field);
+            return Err(template_err(fmt, self.position, &self.text, &reason));
+        };
+
+        let mut value = value.clone();
+        for filter in &self.filters {
+            value = match filter {
+                Filter::Truncate(max_chars) => value.chars().take(*max_chars).collect(),
+                Filter::Default(other) if value.is_empty() => {
+                    vars.get(other).cloned().unwrap_or_else(|| other.clone())
+                }
+                Filter::Default(_) => value,
+                Filter::Upper => value.to_uppercase(),
+                Filter::Lower => value.to_lowercase(),
+                Filter::Trim => value.trim().to_string(),
+                Filter::S2t => zh_convert::to_traditional(&value),
+                Filter::T2s => zh_convert::to_simplified(&value),
+                Filter::Pinyin => zh_convert::to_pinyin(&value),
+            };
+        }
+
+        if self.spec.is_empty() && self.field != "order" {
+            return Ok(value);
+        }
+
+        let formatted = if self.field == "order" {
+            format_order(&value, &self.spec)
+        } else {
+            apply_spec(&value, &self.spec)
+        };
+        formatted.map_err(|err| {
+            let reason = format!("格式说明`{}`不合法: {err}", self.spec);
+            template_err(fmt, self.position, &self.text, &reason)
+        })
+    }
+}
+
+fn flush_literal(literal: &mut String, segments: &mut Vec<Segment>) {
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(std::mem::take(literal)));
+    }
+}
+
+fn collect_used_fields<'a>(segments: &'a [Segment], fields: &mut Vec<&'a str>) {
+    for segment in segments {
+        match segment {
+            Segment::Literal(_) => {}
+            Segment::Placeholder(placeholder) => {
+                fields.push(&placeholder.field);
+                for filter in &placeholder.filters {
+                    if let Filter::Default(other) = filter {
+                        fields.push(other);
+                    }
+                }
+            }
+            Segment::Conditional(inner) => collect_used_fields(inner, fields),
+        }
+    }
+}
+
+fn find_unknown_placeholder<'a>(
+    segments: &'a [Segment],
+    fields: &[&str],
+) -> Option<&'a Placeholder> {
+    segments.iter().find_map(|segment| match segment {
+        Segment::Literal(_) => None,
+        Segment::Placeholder(placeholder) => {
+            (!fields.contains(&placeholder.field.as_str())).then_some(placeholder)
+        }
+        Segment::Conditional(inner) => find_unknown_placeholder(inner, fields),
+    })
+}
+
+/// 把`segments`渲染到`output`，返回其中所有字段的值是否都不为空
+fn render_segments(
+    fmt: &str,
+    segments: &[Segment],
+    vars: &HashMap<String, String>,
+    output: &mut String,
+) -> eyre::Result<bool> {
+    let mut all_non_empty = true;
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => output.push_str(literal),
+            Segment::Placeholder(placeholder) => {
+                let value = placeholder.render(fmt, vars)?;
+                all_non_empty &= !value.is_empty();
+                output.push_str(&value);
+            }
+            Segment::Conditional(inner) => {
+                let mut inner_output = String::new();
+                if render_segments(fmt, inner, vars, &mut inner_output)? {
+                    output.push_str(&inner_output);
+                }
+            }
+        }
+    }
+    Ok(all_non_empty)
+}
+
+fn apply_spec(value: &str, spec: &str) -> Result<String, strfmt::FmtError> {
+    let vars = HashMap::from([("v".to_string(), value.to_string())]);
+    strfmt::strfmt(&format!("{{v:{spec}}}"), &vars)
+}
+
+/// 只对`order`的整数部分应用格式说明，非零的小数部分原样追加在后面
+///
+/// 直接格式化整个数字的话，`{order:0>4}`对`5.1`会输出`05.1`，而不是`0005.1`
+fn format_order(order: &str, spec: &str) -> Result<String, strfmt::FmtError> {
+    let (int_part, frac_part) = order.split_once('.').unwrap_or((order, ""));
+    let formatted_int = apply_spec(int_part, spec)?;
+    if frac_part.is_empty() || frac_part == "0" {
+        Ok(formatted_int)
+    } else {
+        Ok(format!("{formatted_int}.{frac_part}"))
+    }
+}
+
+fn template_err(fmt: &str, position: usize, text: &str, reason: &str) -> eyre::Report {
+    eyre!("模板`{fmt}`中第{position}个字符处的`{text}`不合法，{reason}")
+}
+
+/// 把模板参数转为字段名到字段值的映射，字符串以外的值用json表示
+pub fn to_vars<T: Serialize>(fmt_params: &T) -> eyre::Result<HashMap<String, String>> {
+    let json_value =
+        serde_json::to_value(fmt_params).wrap_err("将模板参数转为serde_json::Value失败")?;
+    let json_map = json_value.as_object().ok_or_eyre("模板参数不是JSON对象")?;
+
+    let vars = json_map
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                _ => value.to_string(),
+            };
+            (key.clone(), value)
+        })
+        .collect();
+    Ok(vars)
+}
+
+/// 按`/`拆分`fmt`，每一层分别渲染为目录名或文件名，渲染后为空的层会被忽略
+///
+/// 每一层都会用`utils::sanitize_filename`处理，最多`utils::MAX_FILENAME_BYTES`字节
+pub fn render_path(fmt: &str, vars: &HashMap<String, String>) -> eyre::Result<Vec<String>> {
+    let mut dir_names = Vec::new();
+    for template in FmtTemplate::parse_layers(fmt)? {
+        let dir_name = template.render(vars)?;
+        let dir_name = utils::sanitize_filename(&dir_name, utils::MAX_FILENAME_BYTES);
+        if !dir_name.is_empty() {
+            dir_names.push(dir_name);
+        }
+    }
+    Ok(dir_names)
+}
+
+pub fn contains_any_field(fmt: &str, fields: &[&str]) -> eyre::Result<bool> {
+    Ok(find_used_field(fmt, fields)?.is_some())
+}
+
+/// `fmt`中用到的第一个属于`fields`的字段，`fmt`可以包含多层
+pub fn find_used_field<'a>(fmt: &str, fields: &[&'a str]) -> eyre::Result<Option<&'a str>> {
+    for template in FmtTemplate::parse_layers(fmt)? {
+        let used_fields = template.used_fields();
+        if let Some(field) = fields
+            .iter()
+            .copied()
+            .find(|field| used_fields.contains(field))
+        {
+            return Ok(Some(field));
+        }
+    }
+    Ok(None)
+}
+
+/// 检查`fmt`中所有占位符的字段是否都属于`fields`，`fmt`可以包含多层
+pub fn validate_fields(fmt: &str, fields: &[&str]) -> eyre::Result<()> {
+    for template in FmtTemplate::parse_layers(fmt)? {
+        if let Some(placeholder) = template.find_unknown_placeholder(fields) {
+            let reason = format!("没有`{}`字段，可用的字段有 {fields:?}", placeholder.field);
+            return Err(template_err(
+                fmt,
+                placeholder.position,
+                &placeholder.text,
+                &reason,
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    fn render(fmt: &str, vars: &HashMap<String, String>) -> String {
+        render_path(fmt, vars).unwrap().join("/")
+    }
+
+    fn render_err(fmt: &str, vars: &HashMap<String, String>) -> String {
+        render_path(fmt, vars).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn filters() {
+        let vars = vars(&[
+            ("chapter_title", "  Chapter One  "),
+            ("comic_title", "书"),
+            ("alias", ""),
+            ("author", "第13话"),
+        ]);
+        assert_eq!(render("{chapter_title|trim|truncate:4}", &vars), "Chap");
+        assert_eq!(render("{chapter_title|trim|upper}", &vars), "CHAPTER ONE");
+        assert_eq!(render("{chapter_title|trim|lower}", &vars), "chapter one");
+        assert_eq!(render("{comic_title|s2t}", &vars), "書");
+        assert_eq!(render("{comic_title|s2t|t2s}", &vars), "书");
+        assert_eq!(render("{author|pinyin}", &vars), "Di 13 Hua");
+        // `default`的参数是字段时用字段的值，否则用参数本身
+        assert_eq!(render("{alias|default:comic_title}", &vars), "书");
+        assert_eq!(render("{alias|default:无}", &vars), "无");
+        assert_eq!(render("{comic_title|default:无}", &vars), "书");
+        // 格式说明在所有过滤器之后应用
+        assert_eq!(render("{comic_title:_>3|s2t}", &vars), "__書");
+    }
+
+    #[test]
+    fn order_spec() {
+        let int_vars = vars(&[("order", "5")]);
+        let frac_vars = vars(&[("order", "5.1")]);
+        assert_eq!(render("{order:0>4}", &int_vars), "0005");
+        assert_eq!(render("{order:0>4}", &frac_vars), "0005.1");
+        assert_eq!(render("{order}", &vars(&[("order", "5.0")])), "5");
+    }
+
+    #[test]
+    fn conditionals() {
+        let with_title = vars(&[("order", "1"), ("chapter_title", "开端")]);
+        let without_title = vars(&[("order", "1"), ("chapter_title", "")]);
+        assert_eq!(render("{order}< {chapter_title}>", &with_title), "1 开端");
+        assert_eq!(render("{order}< {chapter_title}>", &without_title), "1");
+        // 内层条件不满足时只去掉内层
+        let fmt = "{order}< [{order}< {chapter_title}>]>";
+        assert_eq!(render(fmt, &without_title), "1 [1]");
+        assert_eq!(render(fmt, &with_title), "1 [1 开端]");
+    }
+
+    #[test]
+    fn escaping() {
+        let vars = vars(&[("order", "1")]);
+        assert_eq!(render("{{{order}}}", &vars), "{1}");
+        assert_eq!(render("{{order}}", &vars), "{order}");
+    }
+
+    #[test]
+    fn layers() {
+        let vars = vars(&[("comic_title", "a"), ("chapter_title", "")]);
+        assert_eq!(
+            render_path("{comic_title}/{chapter_title}/b", &vars).unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            find_used_field("{comic_title}/{order}", &["order"]).unwrap(),
+            Some("order")
+        );
+        assert_eq!(
+            find_used_field("{comic_title}/x", &["order"]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let fields = ["comic_title", "order"];
+        assert!(validate_fields("{comic_title}/{order}", &fields).is_ok());
+        // `default`的参数不是字段时当作文字，不算未知字段
+        assert!(validate_fields("{comic_title|default:foo}", &fields).is_ok());
+
+        let err = validate_fields("{comic_title}/{foo}", &fields).unwrap_err();
+        assert!(err.to_string().contains("第15个字符处的`{foo}`"), "{err}");
+        assert!(err.to_string().contains("没有`foo`字段"), "{err}");
+
+        let err = render_err("x/{foo}", &vars(&[("order", "1")]));
+        assert!(err.contains("第3个字符处的`{foo}`"), "{err}");
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        let vars = vars(&[("order", "1")]);
+        // 位置是在整个模板中的位置，而不是在某一层中的位置
+        let cases = [
+            ("ab/{order", "第4个字符处的`{order`"),
+            ("ab/c}", "第5个字符处的`}`"),
+            ("ab/c>", "第5个字符处的`>`"),
+            ("ab/<c", "第4个字符处的`<`"),
+            ("a/b/{order|foo}", "第5个字符处的`{order|foo}`"),
+            ("a/{order|truncate:0}", "第3个字符处的`{order|truncate:0}`"),
+            ("a/{order|upper:1}", "第3个字符处的`{order|upper:1}`"),
+            ("a/{ }", "第3个字符处的`{ }`"),
+            ("中文/{order:x}", "第4个字符处的`{order:x}`"),
+        ];
+        for (fmt, expected) in cases {
+            let err = render_err(fmt, &vars);
+            assert!(err.contains(expected), "`{fmt}`: {err}");
+            assert!(err.contains(&format!("模板`{fmt}`")), "`{fmt}`: {err}");
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::io::Cursor;
+
+use eyre::{eyre, WrapErr};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView, GrayImage,
+    ImageFormat, Luma, Rgb, RgbImage,
+};
+use tracing::instrument;
+
+use crate::config::ImgProcessConfig;
+
+/// 按`config`处理图片，返回处理后的图片
+///
+/// 处理顺序为：裁剪纯色边框 -> 拆分跨页 -> 灰度 -> 对比度 -> gamma
+///
+/// 如果跨页被拆分，返回的图片按从右到左的顺序排列
+pub fn process_img(img: DynamicImage, config: &ImgProcessConfig) -> Vec<DynamicImage> {
+    let img = if config.auto_crop {
+        auto_crop(img, config.auto_crop_tolerance)
+    } else {
+        img
+    };
+
+    let imgs = if config.split_spread {
+        split_spread(img)
+    } else {
+        vec![img]
+    };
+
+    imgs.into_iter()
+        .map(|img| {
+            let img = if config.grayscale {
+                img.grayscale()
+            } else {
+                img
+            };
+
+            let img = if config.adjusts_contrast() {
+                img.adjust_contrast(config.contrast)
+            } else {
+                img
+            };
+
+            if config.adjusts_gamma() {
+                adjust_gamma(&img, config.gamma)
+            } else {
+                img
+            }
+        })
+        .collect()
+}
+
+/// 解码`img_data`，按`config`处理后再编码为`format`格式
+#[instrument(level = "error", skip_all, fields(format = ?format))]
+pub fn process_img_data(
+    img_data: &[u8],
+    format: ImageFormat,
+    config: &ImgProcessConfig,
+) -> eyre::Result<Vec<Vec<u8>>> {
+    let img = image::load_from_memory(img_data).wrap_err("解码图片失败")?;
+
+    process_img(img, config)
+        .iter()
+        .map(|img| encode_img(img, format))
+        .collect()
+}
+
+/// 将`img`编码为`format`格式
+///
+/// 灰度图保持单通道编码，其他图片按格式转换为RGB或RGBA
+#[instrument(level = "error", skip_all, fields(format = ?format))]
+pub fn encode_img(img: &DynamicImage, format: ImageFormat) -> eyre::Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    match (format, img) {
+        (ImageFormat::WebP | ImageFormat::Jpeg, DynamicImage::ImageLuma8(gray_img)) => {
+            gray_img.write_to(&mut Cursor::new(&mut data), format)
+        }
+        (ImageFormat::WebP, _) => img
+            .to_rgba8()
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::WebP),
+        (ImageFormat::Jpeg, _) => img
+            .to_rgb8()
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Jpeg),
+        _ => return Err(eyre!("不支持的图片格式: {format:?}")),
+    }
+    .wrap_err("编码图片失败")?;
+
+    Ok(data)
+}
+
+/// 把`img_data`等比缩小到不超过`max_width`宽(为0时不缩小)，再以`quality`编码为JPEG
+#[instrument(level = "error", skip_all, fields(max_width, quality))]
+pub fn recompress_img_data(img_data: &[u8], max_width: u32, quality: u8) -> eyre::Result<Vec<u8>> {
+    let mut img = image::load_from_memory(img_data).wrap_err("解码图片失败")?;
+    if max_width != 0 && img.width() > max_width {
+        let height = u64::from(img.height()) * u64::from(max_width) / u64::from(img.width());
+        let height = u32::try_from(height).unwrap_or(u32::MAX).max(1);
+        img = img.resize_exact(max_width, height, FilterType::Lanczos3);
+    }
+
+    let mut data = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut data, quality.clamp(1, 100));
+    // JPEG不支持透明通道，灰度图保持单通道编码
+    match img {
+        DynamicImage::ImageLuma8(gray_img) => gray_img.write_with_encoder(encoder),
+        img => img.to_rgb8().write_with_encoder(encoder),
+    }
+    .wrap_err("编码图片失败")?;
+
+    Ok(data)
+}
+
+/// 根据扩展名获取图片格式
+pub fn format_from_extension(ext: &str) -> Option<ImageFormat> {
+    match ext.to_lowercase().as_str() {
+        "webp" => Some(ImageFormat::WebP),
+        "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+        _ => None,
+    }
+}
+
+/// 裁剪掉图片四周的纯色边框
+///
+/// 以左上角像素为边框颜色，亮度差不超过`tolerance`的行和列都视为边框
+fn auto_crop(img: DynamicImage, tolerance: u8) -> DynamicImage {
+    let gray_img = img.to_luma8();
+    let (width, height) = gray_img.dimensions();
+    if width == 0 || height == 0 {
+        return img;
+    }
+
+    let border = gray_img.get_pixel(0, 0)[0];
+    let is_border = |pixel: &Luma<u8>| pixel[0].abs_diff(border) <= tolerance;
+    let row_is_border = |y: u32| (0..width).all(|x| is_border(gray_img.get_pixel(x, y)));
+    let col_is_border =
+        |x: u32, top: u32, bottom: u32| (top..bottom).all(|y| is_border(gray_img.get_pixel(x, y)));
+
+    let Some(top) = (0..height).find(|&y| !row_is_border(y)) else {
+        // 整张图都是纯色(比如空白页)，不裁剪
+        return img;
+    };
+    // 上面已经找到了非边框行，所以这里一定能找到
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !row_is_border(y))
+        .unwrap_or(top)
+        + 1;
+    let left = (0..width)
+        .find(|&x| !col_is_border(x, top, bottom))
+        .unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|&x| !col_is_border(x, top, bottom))
+        .unwrap_or(width - 1)
+        + 1;
+
+    if left == 0 && top == 0 && right == width && bottom == height {
+        return img;
+    }
+
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// 将横向的跨页拆分为两页，按从右到左的顺序返回
+fn split_spread(img: DynamicImage) -> Vec<DynamicImage> {
+    let (width, height) = img.dimensions();
+    if width <= height {
+        return vec![img];
+    }
+
+    let left_width = width / 2;
+    let right_width = width - left_width;
+    let right_page = img.crop_imm(left_width, 0, right_width, height);
+    let left_page = img.crop_imm(0, 0, left_width, height);
+
+    vec![right_page, left_page]
+}
+
+/// gamma校正
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn adjust_gamma(img: &DynamicImage, gamma: f32) -> DynamicImage {
+    let lut: Vec<u8> = (0..=255u8)
+        .map(|v| {
+            let normalized = f32::from(v) / 255.0;
+            (normalized.powf(1.0 / gamma) * 255.0)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+        .collect();
+
+    if let DynamicImage::ImageLuma8(gray_img) = img {
+        let adjusted = GrayImage::from_fn(gray_img.width(), gray_img.height(), |x, y| {
+            let Luma([luma]) = *gray_img.get_pixel(x, y);
+            Luma([lut[luma as usize]])
+        });
+        return DynamicImage::ImageLuma8(adjusted);
+    }
+
+    let rgb_img = img.to_rgb8();
+    let adjusted = RgbImage::from_fn(rgb_img.width(), rgb_img.height(), |x, y| {
+        let Rgb([r, g, b]) = *rgb_img.get_pixel(x, y);
+        Rgb([lut[r as usize], lut[g as usize], lut[b as usize]])
+    });
+    DynamicImage::ImageRgb8(adjusted)
+}
@@ -7,14 +7,22 @@ mod errors;
 mod events;
 mod export;
 mod extensions;
+mod fmt_template;
+mod img_process;
+mod library_dedup;
+mod library_index;
+mod library_migration;
+mod library_watcher;
 mod logger;
 mod responses;
 mod types;
 mod utils;
+mod zh_convert;
 
 use eyre::WrapErr;
 use parking_lot::RwLock;
 use tauri::{Manager, Wry};
+use tauri_specta::Event;
 
 use crate::{
     account_pool::AccountPool,
@@ -24,9 +32,12 @@ use crate::{
     downloader::download_manager::DownloadManager,
     errors::install_custom_eyre_handler,
     events::{
-        DownloadEvent, ExportCbzEvent, ExportPdfEvent, LogEvent, UpdateDownloadedComicsEvent,
+        DownloadEvent, ExportCbzEvent, ExportEpubEvent, ExportLongImgEvent, ExportPdfEvent,
+        LibraryChangedEvent, LogEvent, UpdateDownloadedComicsEvent,
     },
-    export::ComicExportLock,
+    export::ExportQueue,
+    extensions::{AppHandleExt, EyreReportToMessage},
+    library_index::LibraryIndex,
 };
 
 fn generate_context() -> tauri::Context<Wry> {
@@ -59,7 +70,17 @@ pub fn run() {
             export_cbz,
             export_pdf,
             export_cbz_chapters,
+            update_cbz_comic_info,
             export_pdf_chapters,
+            estimate_pdf_size,
+            export_long_img,
+            export_long_img_chapters,
+            export_epub,
+            export_epub_chapters,
+            preview_fmt,
+            migrate_library,
+            merge_duplicate_comics,
+            cancel_export,
             update_downloaded_comics,
             get_logs_dir_size,
             show_path_in_file_manager,
@@ -72,8 +93,11 @@ pub fn run() {
             DownloadEvent,
             ExportCbzEvent,
             ExportPdfEvent,
+            ExportLongImgEvent,
+            ExportEpubEvent,
             UpdateDownloadedComicsEvent,
             LogEvent,
+            LibraryChangedEvent,
         ]);
 
     #[cfg(debug_assertions)]
@@ -111,11 +135,35 @@ pub fn run() {
             let account_pool = AccountPool::new(app.handle()).wrap_err("创建AccountPool失败")?;
             app.manage(account_pool);
 
-            let export_lock = ComicExportLock::new();
-            app.manage(export_lock);
+            let export_queue = ExportQueue::new();
+            app.manage(export_queue);
+
+            let library_index = LibraryIndex::new(app.handle()).wrap_err("创建LibraryIndex失败")?;
+            app.manage(library_index);
 
             logger::init(app.handle())?;
 
+            // 程序关闭期间下载目录里可能有变化，所以每次启动都在后台把变化同步到索引
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let download_dir = app_handle.get_config().read().download_dir.clone();
+                match app_handle.get_library_index().reconcile(&download_dir) {
+                    Ok(comic_path_words) if !comic_path_words.is_empty() => {
+                        // 让前端刷新已下载的漫画列表和当前漫画
+                        let _ = LibraryChangedEvent { comic_path_words }.emit(&app_handle);
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        // 错误日志会通过`LogEvent`发送到前端，以通知的形式显示
+                        let err_title = "同步已下载漫画索引失败";
+                        let message = err.to_message();
+                        tracing::error!(err_title, message);
+                    }
+                }
+            });
+
+            library_watcher::init(app.handle());
+
             Ok(())
         })
         .run(generate_context())
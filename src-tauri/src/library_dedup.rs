@@ -0,0 +1,688 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use eyre::{OptionExt, WrapErr};
+use indexmap::IndexMap;
+use tauri::AppHandle;
+use tracing::instrument;
+use walkdir::WalkDir;
+
+use crate::{
+    extensions::{AppHandleExt, EyreReportToMessage, PathIsImg},
+    library_migration::{self, MoveJournal, STAGING_DIR_PREFIX},
+    types::{
+        ChapterInfo, Comic, DuplicateChapter, DuplicateChapterCopy, DuplicateComicCopy,
+        DuplicateComicFailure, DuplicateComicMerge, DuplicateComicSkip, DuplicateComicsReport,
+    },
+    utils,
+};
+
+/// 一个需要合并的漫画
+struct ComicMergeTask {
+    merge: DuplicateComicMerge,
+    /// 合并了所有版本的元数据，`comic_download_dir`和`chapter_download_dir`都是合并后的路径
+    merged_comic: Comic,
+    /// 保留的版本没有封面时，从其他版本移过来的封面
+    cover_move: Option<(PathBuf, PathBuf)>,
+}
+
+/// 合并下载目录里同一个漫画的多个版本
+///
+/// 下载章节最多的版本会被保留，每个章节选图片最完整的版本移到保留的版本里，合并元数据后删除其他版本
+///
+/// 每个漫画单独合并，某个漫画合并失败时只回滚这个漫画，`dry_run`为`true`时只生成合并计划，不会移动或删除任何文件
+#[instrument(level = "error", skip_all, fields(dry_run))]
+pub fn merge_duplicate_comics(
+    app: &AppHandle,
+    dry_run: bool,
+) -> eyre::Result<DuplicateComicsReport> {
+    let download_dir = app.get_config().read().download_dir.clone();
+
+    let (tasks, skipped) = plan_merges(app, &download_dir)?;
+    let mut report = DuplicateComicsReport {
+        skipped,
+        ..Default::default()
+    };
+
+    if dry_run {
+        report.merges = tasks.into_iter().map(|task| task.merge).collect();
+        return Ok(report);
+    }
+
+    let export_queue = app.get_export_queue();
+    for task in tasks {
+        let merge = &task.merge;
+        // 计划生成后才创建的下载任务也要跳过
+        if library_migration::has_unfinished_download_tasks(app, &merge.comic_path_word) {
+            report
+                .skipped
+                .push(create_skip(merge, "漫画有未完成的下载任务".to_string()));
+            continue;
+        }
+
+        // 与这个漫画的导出任务排队执行，避免导出时章节目录被移走
+        let result = export_queue.run(&merge.comic_path_word, |_| {
+            Ok(Some(merge_comic(&download_dir, &task)))
+        });
+
+        match result {
+            Ok(Some(Ok(()))) => {
+                let library_index = app.get_library_index();
+                for copy in &task.merge.copies {
+                    if let Err(err) = library_index.update_dir(&copy.comic_dir) {
+                        let err_title = "更新已下载漫画索引失败";
+                        let message = err.to_message();
+                        tracing::error!(err_title, message);
+                    }
+                }
+                report.merges.push(task.merge);
+            }
+            Ok(Some(Err(err))) => report.failed.push(DuplicateComicFailure {
+                merge: task.merge.clone(),
+                error: err.error.to_message(),
+                rollback_error: err
+                    .rollback_error
+                    .as_ref()
+                    .map(EyreReportToMessage::to_message),
+            }),
+            // 排队时被取消
+            Ok(None) => report
+                .skipped
+                .push(create_skip(merge, "合并被取消".to_string())),
+            Err(err) => report.failed.push(DuplicateComicFailure {
+                merge: task.merge.clone(),
+                error: err.to_message(),
+                rollback_error: None,
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+/// 多个版本中应该保留的版本，选下载章节最多的，有多个时选排在最前面的
+pub fn choose_kept_copy(comics: &[Comic]) -> usize {
+    comics
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, comic)| (downloaded_chapters(comic).count(), Reverse(*i)))
+        .map_or(0, |(i, _)| i)
+}
+
+/// 找出所有有多个版本的漫画，生成合并计划
+fn plan_merges(
+    app: &AppHandle,
+    download_dir: &Path,
+) -> eyre::Result<(Vec<ComicMergeTask>, Vec<DuplicateComicSkip>)> {
+    let library_index = app.get_library_index();
+    library_index.ensure_built(download_dir)?;
+    // 已经按照元数据文件的修改时间排序，最新的排在最前面
+    let downloaded_comics = library_index.get_downloaded_comics()?;
+
+    let all_comic_dirs: Vec<(String, PathBuf)> = downloaded_comics
+        .iter()
+        .filter_map(|comic| {
+            let comic_download_dir = comic.comic_download_dir.clone()?;
+            Some((comic.comic.path_word.clone(), comic_download_dir))
+        })
+        .collect();
+
+    let mut comics_by_path_word: IndexMap<String, Vec<Comic>> = IndexMap::new();
+    for comic in downloaded_comics {
+        comics_by_path_word
+            .entry(comic.comic.path_word.clone())
+            .or_default()
+            .push(comic);
+    }
+
+    let mut tasks = Vec::new();
+    let mut skipped = Vec::new();
+    for (comic_path_word, comics) in comics_by_path_word {
+        if comics.len() < 2 {
+            continue;
+        }
+
+        let comic_dirs: Vec<PathBuf> = comics
+            .iter()
+            .filter_map(|comic| comic.comic_download_dir.clone())
+            .collect();
+
+        let skip_reason = if comic_dirs
+            .iter()
+            .any(|dir| library_migration::is_in_staging_dir(download_dir, dir))
+        {
+            Some("漫画在上次没有完成的迁移或合并的暂存目录里，请手动检查".to_string())
+        } else if library_migration::has_unfinished_download_tasks(app, &comic_path_word) {
+            Some("漫画有未完成的下载任务".to_string())
+        } else {
+            find_nested_dir(&comic_dirs, &all_comic_dirs)
+        };
+
+        let task_result = match skip_reason {
+            Some(reason) => Err(reason),
+            None => create_task(&comics).map_err(|err| err.to_message()),
+        };
+        let task_result = task_result.and_then(|task| match find_conflict(&task, &comics) {
+            Some(reason) => Err(reason),
+            None => Ok(task),
+        });
+
+        match task_result {
+            Ok(task) => tasks.push(task),
+            Err(reason) => skipped.push(DuplicateComicSkip {
+                comic_path_word,
+                comic_title: comics[0].comic.name.clone(),
+                comic_dirs,
+                reason,
+            }),
+        }
+    }
+
+    Ok((tasks, skipped))
+}
+
+/// 版本目录里有其他漫画或其他版本的目录时，合并会把它们一起移走或删除，所以不能合并
+fn find_nested_dir(comic_dirs: &[PathBuf], all_comic_dirs: &[(String, PathBuf)]) -> Option<String> {
+    for comic_dir in comic_dirs {
+        let nested = all_comic_dirs
+            .iter()
+            .find(|(_, dir)| dir != comic_dir && dir.starts_with(comic_dir));
+        if let Some((path_word, dir)) = nested {
+            return Some(format!(
+                "漫画`{path_word}`的目录`{}`在版本`{}`里",
+                dir.display(),
+                comic_dir.display()
+            ));
+        }
+    }
+
+    None
+}
+
+/// 比较各个版本的章节，计算合并后每个章节的目录，并合并元数据
+fn create_task(comics: &[Comic]) -> eyre::Result<ComicMergeTask> {
+    let kept_index = choose_kept_copy(comics);
+    let comic_dirs = comics
+        .iter()
+        .map(|comic| {
+            comic
+                .comic_download_dir
+                .clone()
+                .ok_or_eyre("`comic_download_dir`字段为`None`")
+        })
+        .collect::<eyre::Result<Vec<PathBuf>>>()?;
+    let kept_dir = &comic_dirs[kept_index];
+
+    // 每个版本里下载过的章节，键为章节ID
+    let downloaded_chapter_maps: Vec<HashMap<&str, &ChapterInfo>> = comics
+        .iter()
+        .map(|comic| {
+            downloaded_chapters(comic)
+                .map(|chapter_info| (chapter_info.chapter_uuid.as_str(), chapter_info))
+                .collect()
+        })
+        .collect();
+
+    let mut merged_comic = merge_metadata(comics);
+    let mut chapter_infos: Vec<&mut ChapterInfo> =
+        merged_comic.comic.groups.values_mut().flatten().collect();
+    chapter_infos.sort_by(|a, b| {
+        (&a.group_path_word, a.order)
+            .partial_cmp(&(&b.group_path_word, b.order))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut chapters = Vec::new();
+    let mut kept_chapter_counts = vec![0; comics.len()];
+    for chapter_info in chapter_infos {
+        let mut copies = Vec::new();
+        let mut copy_indexes = Vec::new();
+        for (i, downloaded_chapter_map) in downloaded_chapter_maps.iter().enumerate() {
+            let Some(copy_chapter_info) =
+                downloaded_chapter_map.get(chapter_info.chapter_uuid.as_str())
+            else {
+                continue;
+            };
+            let chapter_dir = copy_chapter_info
+                .chapter_download_dir
+                .clone()
+                .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+            let (img_count, img_size) = measure_chapter_dir(&chapter_dir)?;
+            copies.push(DuplicateChapterCopy {
+                chapter_dir,
+                img_count,
+                img_size,
+                is_kept: false,
+            });
+            copy_indexes.push(i);
+
+            // 任何一个版本导出过都算导出过
+            chapter_info.is_pdf_exported |= copy_chapter_info.is_pdf_exported;
+            chapter_info.is_cbz_exported |= copy_chapter_info.is_cbz_exported;
+            chapter_info.is_long_img_exported |= copy_chapter_info.is_long_img_exported;
+            chapter_info.is_epub_exported |= copy_chapter_info.is_epub_exported;
+            chapter_info.is_long |= copy_chapter_info.is_long;
+        }
+
+        if copies.is_empty() {
+            // 没下载过的章节，目录也改为在保留的版本里
+            chapter_info.is_downloaded = None;
+            chapter_info.chapter_download_dir = chapter_info
+                .chapter_download_dir
+                .as_ref()
+                .and_then(|dir| {
+                    comic_dirs
+                        .iter()
+                        .find_map(|comic_dir| dir.strip_prefix(comic_dir).ok())
+                })
+                .map(|relative_dir| kept_dir.join(relative_dir));
+            continue;
+        }
+
+        // 优先选页数完整的，然后是图片更多、更大的，都相同时优先选保留的版本里的
+        let chapter_size = chapter_info.chapter_size;
+        let best = (0..copies.len())
+            .max_by_key(|&j| {
+                let copy = &copies[j];
+                (
+                    copy.img_count >= chapter_size,
+                    copy.img_count,
+                    copy.img_size,
+                    copy_indexes[j] == kept_index,
+                )
+            })
+            .unwrap_or_default();
+        copies[best].is_kept = true;
+        let best_copy_index = copy_indexes[best];
+        kept_chapter_counts[best_copy_index] += 1;
+
+        let relative_dir = library_migration::strip_prefix(
+            &copies[best].chapter_dir,
+            &comic_dirs[best_copy_index],
+        )?;
+        let new_dir = kept_dir.join(relative_dir);
+
+        chapter_info.is_downloaded = Some(true);
+        chapter_info.chapter_download_dir = Some(new_dir.clone());
+        // 保留哪个版本的图片，就沿用那个版本下载时做过的处理
+        chapter_info.download_img_process = downloaded_chapter_maps[best_copy_index]
+            .get(chapter_info.chapter_uuid.as_str())
+            .and_then(|copy_chapter_info| copy_chapter_info.download_img_process.clone());
+
+        chapters.push(DuplicateChapter {
+            chapter_uuid: chapter_info.chapter_uuid.clone(),
+            source: format!(
+                "{} - {}",
+                chapter_info.group_name, chapter_info.chapter_title
+            ),
+            chapter_size,
+            copies,
+            new_dir,
+        });
+    }
+
+    merged_comic.comic_download_dir = Some(kept_dir.clone());
+    merged_comic.is_downloaded = Some(true);
+
+    let copies = comics
+        .iter()
+        .zip(&comic_dirs)
+        .zip(kept_chapter_counts)
+        .map(
+            |((comic, comic_dir), kept_chapter_count)| DuplicateComicCopy {
+                comic_dir: comic_dir.clone(),
+                downloaded_chapter_count: downloaded_chapters(comic).count() as i64,
+                kept_chapter_count,
+            },
+        )
+        .collect();
+
+    let cover_move = if comics[kept_index].get_cover_path().is_some() {
+        None
+    } else {
+        comics
+            .iter()
+            .find_map(Comic::get_cover_path)
+            .and_then(|cover_path| {
+                let file_name = cover_path.file_name()?.to_owned();
+                Some((cover_path, kept_dir.join(file_name)))
+            })
+    };
+
+    let merge = DuplicateComicMerge {
+        comic_uuid: merged_comic.comic.uuid.clone(),
+        comic_path_word: merged_comic.comic.path_word.clone(),
+        comic_title: merged_comic.comic.name.clone(),
+        kept_dir: kept_dir.clone(),
+        copies,
+        chapters,
+    };
+
+    Ok(ComicMergeTask {
+        merge,
+        merged_comic,
+        cover_move,
+    })
+}
+
+/// 以最新的元数据为基础，补上其他版本里才有的分组和章节
+fn merge_metadata(comics: &[Comic]) -> Comic {
+    let mut merged_comic = comics[0].clone();
+    for comic in &comics[1..] {
+        for (group_path_word, group) in &comic.groups {
+            merged_comic
+                .groups
+                .entry(group_path_word.clone())
+                .or_insert_with(|| group.clone());
+        }
+
+        for (group_path_word, chapter_infos) in &comic.comic.groups {
+            let merged_chapter_infos = merged_comic
+                .comic
+                .groups
+                .entry(group_path_word.clone())
+                .or_default();
+            for chapter_info in chapter_infos {
+                let exists = merged_chapter_infos
+                    .iter()
+                    .any(|merged| merged.chapter_uuid == chapter_info.chapter_uuid);
+                if !exists {
+                    merged_chapter_infos.push(chapter_info.clone());
+                }
+            }
+        }
+    }
+
+    merged_comic
+}
+
+/// 返回合并时会出问题的原因，没有问题时返回`None`
+fn find_conflict(task: &ComicMergeTask, comics: &[Comic]) -> Option<String> {
+    let merge = &task.merge;
+    let chapters = &merge.chapters;
+
+    if let Some(indexes) =
+        utils::find_path_collisions(chapters.iter().map(|chapter| chapter.new_dir.as_path()))
+            .first()
+    {
+        let sources = indexes
+            .iter()
+            .map(|&i| format!("`{}`", chapters[i].source))
+            .collect::<Vec<String>>()
+            .join(", ");
+        return Some(format!("章节{sources}合并后的目录相同"));
+    }
+
+    for chapter in chapters {
+        let parent_chapter = chapters.iter().find(|other| {
+            other.new_dir != chapter.new_dir && chapter.new_dir.starts_with(&other.new_dir)
+        });
+        if let Some(parent_chapter) = parent_chapter {
+            return Some(format!(
+                "章节`{}`合并后的目录在章节`{}`的目录里",
+                chapter.source, parent_chapter.source
+            ));
+        }
+    }
+
+    // 保留的版本里要丢弃的章节会先移走，它们的位置可以给其他版本的章节用
+    let discarded_dirs_in_kept_dir: HashSet<&PathBuf> = chapters
+        .iter()
+        .flat_map(|chapter| &chapter.copies)
+        .filter(|copy| !copy.is_kept && copy.chapter_dir.starts_with(&merge.kept_dir))
+        .map(|copy| &copy.chapter_dir)
+        .collect();
+    for chapter in chapters {
+        let is_own_dir = chapter
+            .copies
+            .iter()
+            .any(|copy| copy.chapter_dir == chapter.new_dir);
+        if chapter.new_dir.exists()
+            && !is_own_dir
+            && !discarded_dirs_in_kept_dir.contains(&chapter.new_dir)
+        {
+            return Some(format!(
+                "章节`{}`合并后的目录`{}`已存在",
+                chapter.source,
+                chapter.new_dir.display()
+            ));
+        }
+    }
+
+    // 其他版本合并后会被删除，里面不能有元数据、封面和已下载章节以外的文件
+    for comic in comics {
+        let Some(comic_dir) = &comic.comic_download_dir else {
+            continue;
+        };
+        if *comic_dir == merge.kept_dir {
+            continue;
+        }
+
+        let metadata_path = comic_dir.join("元数据.json");
+        let cover_path = comic.get_cover_path();
+        let chapter_dirs: Vec<&PathBuf> = chapters
+            .iter()
+            .flat_map(|chapter| &chapter.copies)
+            .map(|copy| &copy.chapter_dir)
+            .filter(|chapter_dir| chapter_dir.starts_with(comic_dir))
+            .collect();
+
+        let unknown_file = WalkDir::new(comic_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(walkdir::DirEntry::into_path)
+            .find(|path| {
+                *path != metadata_path
+                    && cover_path.as_ref() != Some(path)
+                    && !chapter_dirs.iter().any(|dir| path.starts_with(dir))
+            });
+        if let Some(unknown_file) = unknown_file {
+            return Some(format!(
+                "版本`{}`里有无法识别的文件`{}`，请手动处理",
+                comic_dir.display(),
+                unknown_file.display()
+            ));
+        }
+    }
+
+    None
+}
+
+/// 合并一个漫画，失败时回滚这个漫画已经移动过的目录，并恢复原来的元数据
+#[instrument(
+    level = "error",
+    skip_all,
+    fields(
+        comic_uuid = task.merge.comic_uuid,
+        comic_title = task.merge.comic_title,
+        kept_dir = ?task.merge.kept_dir
+    )
+)]
+fn merge_comic(download_dir: &Path, task: &ComicMergeTask) -> Result<(), MergeError> {
+    let staging_dir = download_dir.join(format!("{STAGING_DIR_PREFIX}{}", task.merge.comic_uuid));
+
+    let mut journal = MoveJournal::default();
+    let mut metadata_backups = Vec::new();
+    let result = move_and_merge_dirs(
+        download_dir,
+        &staging_dir,
+        task,
+        &mut journal,
+        &mut metadata_backups,
+    );
+
+    let Err(err) = result else {
+        // 暂存目录里只剩下其他版本和被丢弃的章节
+        if let Err(err) = std::fs::remove_dir_all(&staging_dir)
+            .wrap_err(format!("删除`{}`失败", staging_dir.display()))
+        {
+            let err_title = "删除合并时被丢弃的版本失败，需要手动删除";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
+        return Ok(());
+    };
+
+    let err_title = "合并漫画的多个版本失败，正在回滚";
+    let message = err.to_message();
+    tracing::error!(err_title, message);
+
+    // 先恢复元数据，再把目录移回去，因为元数据是在目录移动完成后写入的
+    let rollback_result =
+        restore_metadata(metadata_backups).and_then(|()| journal.rollback(download_dir));
+    if let Err(rollback_err) = &rollback_result {
+        let err_title = "回滚合并失败，需要手动处理";
+        let message = rollback_err.to_message();
+        tracing::error!(err_title, message);
+    }
+
+    Err(MergeError {
+        error: err,
+        rollback_error: rollback_result.err(),
+    })
+}
+
+fn move_and_merge_dirs(
+    download_dir: &Path,
+    staging_dir: &Path,
+    task: &ComicMergeTask,
+    journal: &mut MoveJournal,
+    metadata_backups: &mut Vec<(PathBuf, Option<Vec<u8>>)>,
+) -> eyre::Result<()> {
+    let merge = &task.merge;
+
+    // 先把保留的版本里要丢弃的章节移到暂存目录，给其他版本的章节腾出位置
+    for chapter in &merge.chapters {
+        for (i, copy) in chapter.copies.iter().enumerate() {
+            if copy.is_kept || !copy.chapter_dir.starts_with(&merge.kept_dir) {
+                continue;
+            }
+            let discarded_dir = staging_dir.join(format!("{}-{i}", chapter.chapter_uuid));
+            journal.rename(&copy.chapter_dir, &discarded_dir)?;
+            library_migration::remove_empty_dirs(copy.chapter_dir.parent(), &merge.kept_dir);
+        }
+    }
+
+    for chapter in &merge.chapters {
+        let Some(kept_copy) = chapter.copies.iter().find(|copy| copy.is_kept) else {
+            continue;
+        };
+        if kept_copy.chapter_dir != chapter.new_dir {
+            journal.rename(&kept_copy.chapter_dir, &chapter.new_dir)?;
+        }
+    }
+
+    if let Some((from, to)) = &task.cover_move {
+        journal.rename(from, to)?;
+    }
+
+    // 其他版本里只剩下元数据、封面和被丢弃的章节，整个移到暂存目录
+    for (i, copy) in merge.copies.iter().enumerate() {
+        if copy.comic_dir == merge.kept_dir {
+            continue;
+        }
+        journal.rename(&copy.comic_dir, &staging_dir.join(i.to_string()))?;
+        library_migration::remove_empty_dirs(copy.comic_dir.parent(), download_dir);
+    }
+
+    for chapter_info in downloaded_chapters(&task.merged_comic) {
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .ok_or_eyre("`chapter_download_dir`字段为`None`")?;
+        backup_metadata(
+            &chapter_download_dir.join("章节元数据.json"),
+            metadata_backups,
+        )?;
+        chapter_info
+            .save_metadata()
+            .wrap_err("重写章节元数据失败")?;
+    }
+
+    backup_metadata(&merge.kept_dir.join("元数据.json"), metadata_backups)?;
+    task.merged_comic
+        .save_metadata()
+        .wrap_err("重写漫画元数据失败")?;
+
+    Ok(())
+}
+
+struct MergeError {
+    error: eyre::Report,
+    /// 回滚也失败时的错误
+    rollback_error: Option<eyre::Report>,
+}
+
+/// 记录元数据文件被重写前的内容，文件原本不存在时记录为`None`
+fn backup_metadata(
+    metadata_path: &Path,
+    metadata_backups: &mut Vec<(PathBuf, Option<Vec<u8>>)>,
+) -> eyre::Result<()> {
+    let original_metadata = if metadata_path.is_file() {
+        let metadata = std::fs::read(metadata_path)
+            .wrap_err(format!("读取`{}`失败", metadata_path.display()))?;
+        Some(metadata)
+    } else {
+        None
+    };
+    metadata_backups.push((metadata_path.to_path_buf(), original_metadata));
+    Ok(())
+}
+
+fn restore_metadata(metadata_backups: Vec<(PathBuf, Option<Vec<u8>>)>) -> eyre::Result<()> {
+    for (metadata_path, original_metadata) in metadata_backups.into_iter().rev() {
+        match original_metadata {
+            Some(metadata) => std::fs::write(&metadata_path, metadata)
+                .wrap_err(format!("恢复`{}`失败", metadata_path.display()))?,
+            None => std::fs::remove_file(&metadata_path)
+                .wrap_err(format!("删除`{}`失败", metadata_path.display()))?,
+        }
+    }
+    Ok(())
+}
+
+/// 章节目录里图片的数量和总大小
+fn measure_chapter_dir(chapter_dir: &Path) -> eyre::Result<(i64, u64)> {
+    let mut img_count = 0;
+    let mut img_size = 0;
+    let entries = std::fs::read_dir(chapter_dir)
+        .wrap_err(format!("读取目录`{}`失败", chapter_dir.display()))?;
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if !path.is_img() {
+            continue;
+        }
+        let metadata = entry
+            .metadata()
+            .wrap_err(format!("获取`{}`的大小失败", path.display()))?;
+        img_count += 1;
+        img_size += metadata.len();
+    }
+    Ok((img_count, img_size))
+}
+
+fn downloaded_chapters(comic: &Comic) -> impl Iterator<Item = &ChapterInfo> {
+    comic
+        .comic
+        .groups
+        .values()
+        .flatten()
+        .filter(|chapter_info| chapter_info.is_downloaded == Some(true))
+}
+
+fn create_skip(merge: &DuplicateComicMerge, reason: String) -> DuplicateComicSkip {
+    DuplicateComicSkip {
+        comic_path_word: merge.comic_path_word.clone(),
+        comic_title: merge.comic_title.clone(),
+        comic_dirs: merge
+            .copies
+            .iter()
+            .map(|copy| copy.comic_dir.clone())
+            .collect(),
+        reason,
+    }
+}
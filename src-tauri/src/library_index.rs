@@ -0,0 +1,661 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use eyre::{OptionExt, WrapErr};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, Manager};
+use tracing::instrument;
+use walkdir::WalkDir;
+
+use crate::{
+    extensions::{EyreReportToMessage, WalkDirEntryExt},
+    types::Comic,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS comics (
+    comic_dir TEXT PRIMARY KEY,
+    path_word TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    modified_time INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS comics_path_word ON comics (path_word);
+CREATE TABLE IF NOT EXISTS chapters (
+    chapter_dir TEXT PRIMARY KEY,
+    metadata TEXT NOT NULL,
+    modified_time INTEGER NOT NULL
+);
+";
+
+/// 索引表结构的版本，保存在数据库的`user_version`里，表结构变化时加1
+///
+/// 版本不一致时直接删掉旧表，索引会在下次使用时重建
+const SCHEMA_VERSION: i64 = 1;
+
+/// `meta`表中记录索引对应哪个下载目录的键，下载目录变化后需要重建索引
+const DOWNLOAD_DIR_KEY: &str = "download_dir";
+
+/// 已下载漫画的索引，保存在`app_data_dir`下的SQLite数据库里
+///
+/// 索引保存的是`元数据.json`和`章节元数据.json`的原始内容，查询已下载的漫画时不用再遍历整个下载目录
+///
+/// 索引只是缓存，元数据文件才是准确的，所以下载和导出更新元数据文件后要同步更新索引
+pub struct LibraryIndex {
+    conn: Mutex<Connection>,
+    /// 保证同一时间只有一个重建在进行
+    rebuild_lock: Mutex<()>,
+}
+
+/// 遍历下载目录得到的一个漫画
+struct IndexedComic {
+    comic_dir: PathBuf,
+    path_word: String,
+    metadata: String,
+    modified_time: i64,
+}
+
+/// 索引中的一个漫画以及它的所有章节元数据的路径和内容
+struct IndexedComicWithChapters {
+    comic_dir: PathBuf,
+    metadata: String,
+    chapter_metadata: Vec<(PathBuf, String)>,
+}
+
+/// 遍历下载目录得到的一个章节
+struct IndexedChapter {
+    chapter_dir: PathBuf,
+    metadata: String,
+    modified_time: i64,
+}
+
+/// 遍历下载目录的结果
+#[derive(Default)]
+struct ScanResult {
+    /// 新增的或修改时间有变化的漫画
+    comics: Vec<IndexedComic>,
+    /// 新增的或修改时间有变化的章节
+    chapters: Vec<IndexedChapter>,
+    /// 遍历到的所有元数据文件，包括没有变化的
+    metadata_paths: HashSet<PathBuf>,
+}
+
+impl LibraryIndex {
+    pub fn new(app: &AppHandle) -> eyre::Result<Self> {
+        let app_data_dir = app.path().app_data_dir()?;
+        let db_path = app_data_dir.join("library_index.db");
+
+        let conn =
+            Connection::open(&db_path).wrap_err(format!("打开`{}`失败", db_path.display()))?;
+        let schema_version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .wrap_err(format!("查询`{}`的版本失败", db_path.display()))?;
+        if schema_version != SCHEMA_VERSION {
+            conn.execute_batch(&format!(
+                "DROP TABLE IF EXISTS meta; DROP TABLE IF EXISTS comics; DROP TABLE IF EXISTS chapters; PRAGMA user_version = {SCHEMA_VERSION};"
+            ))
+            .wrap_err(format!("删除`{}`中的旧索引失败", db_path.display()))?;
+        }
+        conn.execute_batch(SCHEMA)
+            .wrap_err(format!("初始化`{}`失败", db_path.display()))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            rebuild_lock: Mutex::new(()),
+        })
+    }
+
+    /// 索引不是为`download_dir`建立的话(比如第一次运行或修改了下载目录)，就重建索引
+    pub fn ensure_built(&self, download_dir: &Path) -> eyre::Result<()> {
+        if self.is_built_for(download_dir)? {
+            return Ok(());
+        }
+
+        let _rebuild_guard = self.rebuild_lock.lock();
+        // 等待锁的时候可能已经有其他线程重建好了
+        if self.is_built_for(download_dir)? {
+            return Ok(());
+        }
+        self.rebuild_locked(download_dir)
+    }
+
+    /// 只同步程序关闭期间下载目录里的变化，索引不是为`download_dir`建立的话就重建索引
+    ///
+    /// 仍然要遍历`download_dir`找到新增和删除的元数据文件，但只会读取修改时间与索引中不同的元数据文件
+    ///
+    /// 返回有变化的漫画的`path_word`，重建索引时返回所有漫画的`path_word`
+    #[instrument(level = "error", skip_all, fields(download_dir = ?download_dir))]
+    pub fn reconcile(&self, download_dir: &Path) -> eyre::Result<Vec<String>> {
+        let _rebuild_guard = self.rebuild_lock.lock();
+        if !self.is_built_for(download_dir)? {
+            self.rebuild_locked(download_dir)?;
+            let rows = self.query_string_pairs("SELECT path_word, comic_dir FROM comics")?;
+            let mut comic_path_words: Vec<String> =
+                rows.into_iter().map(|(path_word, _)| path_word).collect();
+            comic_path_words.sort();
+            comic_path_words.dedup();
+            return Ok(comic_path_words);
+        }
+
+        let indexed_modified_times = self.get_modified_times()?;
+        // 遍历时不持有数据库的锁，这样同步期间也能正常查询旧的索引
+        let scan_result = scan_dir(download_dir, &indexed_modified_times);
+
+        let removed_metadata_paths: Vec<&PathBuf> = indexed_modified_times
+            .keys()
+            .filter(|metadata_path| !scan_result.metadata_paths.contains(*metadata_path))
+            .collect();
+        let related_paths: Vec<PathBuf> = removed_metadata_paths
+            .iter()
+            .filter_map(|metadata_path| metadata_path.parent())
+            .map(Path::to_path_buf)
+            .chain(
+                scan_result
+                    .comics
+                    .iter()
+                    .map(|comic| comic.comic_dir.clone()),
+            )
+            .chain(
+                scan_result
+                    .chapters
+                    .iter()
+                    .map(|chapter| chapter.chapter_dir.clone()),
+            )
+            .collect();
+        // 更新前查询一次，找到被删除或移走的漫画
+        let mut comic_path_words = self.get_related_path_words(&related_paths)?;
+
+        {
+            let mut conn = self.conn.lock();
+            let tx = conn.transaction()?;
+            for metadata_path in &removed_metadata_paths {
+                let Some(dir) = metadata_path.parent() else {
+                    continue;
+                };
+                let sql = if metadata_path.ends_with("元数据.json") {
+                    "DELETE FROM comics WHERE comic_dir = ?1"
+                } else {
+                    "DELETE FROM chapters WHERE chapter_dir = ?1"
+                };
+                tx.execute(sql, params![path_to_string(dir)])?;
+            }
+            for comic in &scan_result.comics {
+                insert_comic(&tx, comic)?;
+            }
+            for chapter in &scan_result.chapters {
+                insert_chapter(&tx, chapter)?;
+            }
+            tx.commit().wrap_err("保存索引失败")?;
+        }
+
+        // 更新后再查询一次，找到新增或移入的漫画
+        comic_path_words.extend(self.get_related_path_words(&related_paths)?);
+
+        tracing::debug!(
+            updated_comic_count = scan_result.comics.len(),
+            updated_chapter_count = scan_result.chapters.len(),
+            removed_count = removed_metadata_paths.len(),
+            "同步已下载漫画索引成功"
+        );
+
+        let mut comic_path_words: Vec<String> = comic_path_words.into_iter().collect();
+        comic_path_words.sort();
+        Ok(comic_path_words)
+    }
+
+    fn rebuild_locked(&self, download_dir: &Path) -> eyre::Result<()> {
+        // 遍历时不持有数据库的锁，这样重建期间也能正常查询旧的索引
+        let ScanResult {
+            comics, chapters, ..
+        } = scan_dir(download_dir, &HashMap::new());
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM comics", [])?;
+        tx.execute("DELETE FROM chapters", [])?;
+        for comic in &comics {
+            insert_comic(&tx, comic)?;
+        }
+        for chapter in &chapters {
+            insert_chapter(&tx, chapter)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![DOWNLOAD_DIR_KEY, path_to_string(download_dir)],
+        )?;
+        tx.commit().wrap_err("保存索引失败")?;
+
+        tracing::debug!(
+            comic_count = comics.len(),
+            chapter_count = chapters.len(),
+            "重建已下载漫画索引成功"
+        );
+
+        Ok(())
+    }
+
+    fn is_built_for(&self, download_dir: &Path) -> eyre::Result<bool> {
+        let indexed_download_dir: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![DOWNLOAD_DIR_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("查询索引对应的下载目录失败")?;
+
+        Ok(indexed_download_dir.as_deref() == Some(path_to_string(download_dir).as_str()))
+    }
+
+    /// 索引中所有元数据文件的路径和修改时间
+    fn get_modified_times(&self) -> eyre::Result<HashMap<PathBuf, i64>> {
+        let sql = "SELECT comic_dir, '元数据.json', modified_time FROM comics UNION ALL SELECT chapter_dir, '章节元数据.json', modified_time FROM chapters";
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| {
+            let dir: String = row.get(0)?;
+            let metadata_filename: String = row.get(1)?;
+            let metadata_path = Path::new(&dir).join(metadata_filename);
+            Ok((metadata_path, row.get(2)?))
+        })?;
+        let modified_times = rows
+            .collect::<Result<_, _>>()
+            .wrap_err(format!("查询`{sql}`失败"))?;
+        Ok(modified_times)
+    }
+
+    /// 用`comic_dir`下的`元数据.json`更新索引，文件不存在时从索引中删除这个漫画
+    ///
+    /// 只更新漫画本身，不会更新漫画目录下的章节
+    pub fn update_comic_metadata(&self, comic_dir: &Path) -> eyre::Result<()> {
+        let metadata_path = comic_dir.join("元数据.json");
+        if !metadata_path.is_file() {
+            self.conn.lock().execute(
+                "DELETE FROM comics WHERE comic_dir = ?1",
+                params![path_to_string(comic_dir)],
+            )?;
+            return Ok(());
+        }
+
+        let comic = read_comic(&metadata_path)?;
+        insert_comic(&self.conn.lock(), &comic)?;
+        Ok(())
+    }
+
+    /// 用`chapter_dir`下的`章节元数据.json`更新索引，文件不存在时从索引中删除这个章节
+    pub fn update_chapter_metadata(&self, chapter_dir: &Path) -> eyre::Result<()> {
+        let metadata_path = chapter_dir.join("章节元数据.json");
+        if !metadata_path.is_file() {
+            self.conn.lock().execute(
+                "DELETE FROM chapters WHERE chapter_dir = ?1",
+                params![path_to_string(chapter_dir)],
+            )?;
+            return Ok(());
+        }
+
+        let chapter = read_chapter(&metadata_path)?;
+        insert_chapter(&self.conn.lock(), &chapter)?;
+        Ok(())
+    }
+
+    /// 重新遍历`dir`，更新`dir`下所有漫画和章节的索引
+    ///
+    /// `dir`不存在时相当于从索引中删除`dir`下的所有漫画和章节
+    #[instrument(level = "error", skip_all, fields(dir = ?dir))]
+    pub fn update_dir(&self, dir: &Path) -> eyre::Result<()> {
+        let ScanResult {
+            comics, chapters, ..
+        } = if dir.exists() {
+            scan_dir(dir, &HashMap::new())
+        } else {
+            ScanResult::default()
+        };
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        delete_dir(&tx, dir)?;
+        for comic in &comics {
+            insert_comic(&tx, comic)?;
+        }
+        for chapter in &chapters {
+            insert_chapter(&tx, chapter)?;
+        }
+        tx.commit().wrap_err("保存索引失败")?;
+
+        Ok(())
+    }
+
+    /// 漫画`path_word`和漫画目录，一个漫画在下载目录里有多个版本时会有多个漫画目录
+    ///
+    /// 元数据文件已经不存在的漫画会从索引中删除
+    pub fn get_comic_dirs(&self) -> eyre::Result<Vec<(String, PathBuf)>> {
+        let rows =
+            self.query_string_pairs("SELECT path_word, comic_dir FROM comics ORDER BY comic_dir")?;
+
+        let mut comic_dirs = Vec::new();
+        for (path_word, comic_dir) in rows {
+            let comic_dir = PathBuf::from(comic_dir);
+            if !comic_dir.join("元数据.json").is_file() {
+                self.update_comic_metadata(&comic_dir)?;
+                continue;
+            }
+            comic_dirs.push((path_word, comic_dir));
+        }
+
+        Ok(comic_dirs)
+    }
+
+    /// 索引中所有已下载的漫画，按元数据文件的修改时间排序，最新的排在最前面
+    ///
+    /// 完全依赖索引，不会访问元数据文件，无法解析的漫画会被跳过
+    #[instrument(level = "error", skip_all)]
+    pub fn get_downloaded_comics(&self) -> eyre::Result<Vec<Comic>> {
+        let comic_rows = self.query_string_pairs(
+            "SELECT comic_dir, metadata FROM comics ORDER BY modified_time DESC",
+        )?;
+        let chapter_rows = self.query_string_pairs("SELECT chapter_dir, metadata FROM chapters")?;
+
+        let mut comic_indexes: HashMap<PathBuf, usize> = HashMap::new();
+        let mut comics: Vec<IndexedComicWithChapters> = Vec::new();
+        for (comic_dir, metadata) in comic_rows {
+            let comic_dir = PathBuf::from(comic_dir);
+            comic_indexes.insert(comic_dir.clone(), comics.len());
+            comics.push(IndexedComicWithChapters {
+                comic_dir,
+                metadata,
+                chapter_metadata: Vec::new(),
+            });
+        }
+
+        for (chapter_dir, metadata) in chapter_rows {
+            let chapter_dir = PathBuf::from(chapter_dir);
+            let metadata_path = chapter_dir.join("章节元数据.json");
+            // 章节属于离它最近的漫画目录
+            let comic_index = chapter_dir
+                .ancestors()
+                .skip(1)
+                .find_map(|dir| comic_indexes.get(dir));
+            if let Some(&comic_index) = comic_index {
+                comics[comic_index]
+                    .chapter_metadata
+                    .push((metadata_path, metadata));
+            }
+        }
+
+        let mut downloaded_comics = Vec::new();
+        for comic in comics {
+            let comic_result = Comic::from_indexed_metadata(
+                comic.comic_dir,
+                &comic.metadata,
+                &comic.chapter_metadata,
+            );
+            match comic_result {
+                Ok(comic) => downloaded_comics.push(comic),
+                Err(err) => {
+                    let err_title = "获取已下载漫画的过程中遇到错误，已跳过";
+                    let message = err.to_message();
+                    tracing::error!(err_title, message);
+                }
+            }
+        }
+
+        Ok(downloaded_comics)
+    }
+
+    /// 元数据文件已经不存在的漫画目录和章节目录，不会修改索引
+    pub fn get_missing_dirs(&self) -> eyre::Result<Vec<PathBuf>> {
+        let rows = self.query_string_pairs(
+            "SELECT comic_dir, '元数据.json' FROM comics UNION ALL SELECT chapter_dir, '章节元数据.json' FROM chapters",
+        )?;
+
+        let missing_dirs = rows
+            .into_iter()
+            .map(|(dir, metadata_filename)| (PathBuf::from(dir), metadata_filename))
+            .filter(|(dir, metadata_filename)| !dir.join(metadata_filename).is_file())
+            .map(|(dir, _)| dir)
+            .collect();
+
+        Ok(missing_dirs)
+    }
+
+    /// 与`paths`相关的漫画的`path_word`
+    ///
+    /// 漫画目录在某个路径里，或者某个路径在漫画目录里，就认为这个漫画与这个路径相关
+    pub fn get_related_path_words(&self, paths: &[PathBuf]) -> eyre::Result<HashSet<String>> {
+        let rows = self.query_string_pairs("SELECT path_word, comic_dir FROM comics")?;
+
+        let path_words = rows
+            .into_iter()
+            .filter(|(_, comic_dir)| {
+                let comic_dir = Path::new(comic_dir);
+                paths
+                    .iter()
+                    .any(|path| comic_dir.starts_with(path) || path.starts_with(comic_dir))
+            })
+            .map(|(path_word, _)| path_word)
+            .collect();
+
+        Ok(path_words)
+    }
+
+    /// 执行返回两列文本的查询
+    fn query_string_pairs(&self, sql: &str) -> eyre::Result<Vec<(String, String)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let rows = rows
+            .collect::<Result<_, _>>()
+            .wrap_err(format!("查询`{sql}`失败"))?;
+        Ok(rows)
+    }
+}
+
+/// 遍历`dir`下所有的漫画和章节元数据，无法读取的元数据会被跳过
+///
+/// 修改时间与`indexed_modified_times`中记录的相同的元数据文件不会被读取
+///
+/// 会顺便为新读取的旧版本漫画创建章节元数据，参考`Comic::create_chapter_metadata_for_old_version`
+fn scan_dir(dir: &Path, indexed_modified_times: &HashMap<PathBuf, i64>) -> ScanResult {
+    let err_title = "建立已下载漫画索引的过程中遇到错误，已跳过";
+
+    let mut comics = Vec::new();
+    let mut chapters = Vec::new();
+    let mut metadata_paths = HashSet::new();
+    for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+        let is_comic_metadata = entry.is_comic_metadata();
+        if !is_comic_metadata && !entry.is_chapter_metadata() {
+            continue;
+        }
+
+        let metadata_path = entry.path();
+        metadata_paths.insert(metadata_path.to_path_buf());
+        let is_unchanged =
+            indexed_modified_times
+                .get(metadata_path)
+                .is_some_and(|&indexed_modified_time| {
+                    entry
+                        .metadata()
+                        .ok()
+                        .and_then(|metadata| get_modified_time(&metadata).ok())
+                        == Some(indexed_modified_time)
+                });
+        if is_unchanged {
+            continue;
+        }
+
+        let result = if is_comic_metadata {
+            read_comic(metadata_path).map(|comic| comics.push(comic))
+        } else {
+            read_chapter(metadata_path).map(|chapter| chapters.push(chapter))
+        };
+
+        if let Err(err) = result {
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+        }
+    }
+
+    // TODO: 这是为了兼容v0.10.2及之前的版本，后续需要移除，计划在v0.12.0之后移除
+    for comic in &comics {
+        let result = serde_json::from_str::<Comic>(&comic.metadata)
+            .wrap_err("将元数据反序列化为Comic失败")
+            .and_then(|c| c.create_chapter_metadata_for_old_version(&comic.comic_dir))
+            .wrap_err("为旧版本创建章节元数据失败");
+        let created_chapter_dirs = match result {
+            Ok(created_chapter_dirs) => created_chapter_dirs,
+            Err(err) => {
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+                continue;
+            }
+        };
+
+        for chapter_dir in created_chapter_dirs {
+            let metadata_path = chapter_dir.join("章节元数据.json");
+            match read_chapter(&metadata_path) {
+                Ok(chapter) => {
+                    chapters.push(chapter);
+                    metadata_paths.insert(metadata_path);
+                }
+                Err(err) => {
+                    let message = err.to_message();
+                    tracing::error!(err_title, message);
+                }
+            }
+        }
+    }
+
+    ScanResult {
+        comics,
+        chapters,
+        metadata_paths,
+    }
+}
+
+fn read_comic(metadata_path: &Path) -> eyre::Result<IndexedComic> {
+    let metadata = std::fs::read_to_string(metadata_path)
+        .wrap_err(format!("读取`{}`失败", metadata_path.display()))?;
+
+    let comic_json: serde_json::Value = serde_json::from_str(&metadata).wrap_err(format!(
+        "将`{}`反序列化为serde_json::Value失败",
+        metadata_path.display()
+    ))?;
+    let path_word = comic_json
+        .pointer("/comic/path_word")
+        .and_then(|path_word| path_word.as_str())
+        .ok_or_eyre(format!(
+            "`{}`没有`comic.path_word`字段",
+            metadata_path.display()
+        ))?
+        .to_string();
+
+    let modified_time = read_modified_time(metadata_path)?;
+
+    let comic_dir = metadata_path
+        .parent()
+        .ok_or_eyre(format!("`{}`没有父目录", metadata_path.display()))?
+        .to_path_buf();
+
+    Ok(IndexedComic {
+        comic_dir,
+        path_word,
+        metadata,
+        modified_time,
+    })
+}
+
+fn read_chapter(metadata_path: &Path) -> eyre::Result<IndexedChapter> {
+    let metadata = std::fs::read_to_string(metadata_path)
+        .wrap_err(format!("读取`{}`失败", metadata_path.display()))?;
+
+    let chapter_dir = metadata_path
+        .parent()
+        .ok_or_eyre(format!("`{}`没有父目录", metadata_path.display()))?
+        .to_path_buf();
+
+    let modified_time = read_modified_time(metadata_path)?;
+
+    Ok(IndexedChapter {
+        chapter_dir,
+        metadata,
+        modified_time,
+    })
+}
+
+fn read_modified_time(path: &Path) -> eyre::Result<i64> {
+    path.metadata()
+        .and_then(|metadata| get_modified_time(&metadata))
+        .wrap_err(format!("获取`{}`的修改时间失败", path.display()))
+}
+
+/// 修改时间，单位为毫秒
+fn get_modified_time(metadata: &std::fs::Metadata) -> std::io::Result<i64> {
+    let modified_time = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| {
+            i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+        });
+    Ok(modified_time)
+}
+
+fn insert_comic(conn: &Connection, comic: &IndexedComic) -> eyre::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO comics (comic_dir, path_word, metadata, modified_time) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            path_to_string(&comic.comic_dir),
+            comic.path_word,
+            comic.metadata,
+            comic.modified_time
+        ],
+    )
+    .wrap_err(format!("将`{}`写入索引失败", comic.comic_dir.display()))?;
+    Ok(())
+}
+
+fn insert_chapter(conn: &Connection, chapter: &IndexedChapter) -> eyre::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO chapters (chapter_dir, metadata, modified_time) VALUES (?1, ?2, ?3)",
+        params![
+            path_to_string(&chapter.chapter_dir),
+            chapter.metadata,
+            chapter.modified_time
+        ],
+    )
+    .wrap_err(format!("将`{}`写入索引失败", chapter.chapter_dir.display()))?;
+    Ok(())
+}
+
+/// 删除`dir`以及`dir`下所有的漫画和章节
+fn delete_dir(conn: &Connection, dir: &Path) -> eyre::Result<()> {
+    for (table, column) in [("comics", "comic_dir"), ("chapters", "chapter_dir")] {
+        let dirs: Vec<String> = {
+            let mut stmt = conn.prepare(&format!("SELECT {column} FROM {table}"))?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut stmt = conn.prepare(&format!("DELETE FROM {table} WHERE {column} = ?1"))?;
+        for indexed_dir in dirs {
+            // 用`Path::starts_with`按路径层级比较，`a/b`不算在`a/bc`里
+            if Path::new(&indexed_dir).starts_with(dir) {
+                stmt.execute(params![indexed_dir])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
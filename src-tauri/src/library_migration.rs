@@ -0,0 +1,562 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, OptionExt, WrapErr};
+use indexmap::IndexMap;
+use tauri::AppHandle;
+use tracing::instrument;
+use walkdir::WalkDir;
+
+use crate::{
+    downloader::download_task_state::DownloadTaskState,
+    extensions::{AppHandleExt, EyreReportToMessage, WalkDirEntryExt},
+    types::{
+        ChapterMigration, Comic, ComicMigration, ComicMigrationFailure, ComicMigrationSkip,
+        LibraryMigrationReport,
+    },
+    utils,
+};
+
+/// 迁移或合并重复版本时暂存目录名的前缀
+///
+/// 暂存目录都在下载目录内，所以只需要重命名，不需要复制
+pub const STAGING_DIR_PREFIX: &str = ".迁移中-";
+
+/// 一个需要迁移的漫画
+struct ComicMigrationTask {
+    migration: ComicMigration,
+    /// `comic_download_dir`和`chapter_download_dir`已更新为新路径，迁移后用来重写元数据
+    migrated_comic: Comic,
+}
+
+/// 按当前的`comic_dir_fmt`和`chapter_dir_fmt`移动已下载的漫画目录和章节目录，并重写漫画的元数据
+///
+/// 每个漫画单独迁移，某个漫画迁移失败时只回滚这个漫画，不影响其他漫画
+///
+/// 有冲突、有多个版本或有未完成下载任务的漫画会被跳过，`dry_run`为`true`时只生成迁移计划，不会移动任何目录
+#[instrument(level = "error", skip_all, fields(dry_run))]
+pub fn migrate_library(app: &AppHandle, dry_run: bool) -> eyre::Result<LibraryMigrationReport> {
+    let download_dir = app.get_config().read().download_dir.clone();
+
+    let (tasks, skipped) = plan_migration(app, &download_dir)?;
+    let mut report = LibraryMigrationReport {
+        skipped,
+        ..Default::default()
+    };
+
+    if dry_run {
+        report.migrations = tasks.into_iter().map(|task| task.migration).collect();
+        return Ok(report);
+    }
+
+    let export_queue = app.get_export_queue();
+    for task in tasks {
+        let migration = &task.migration;
+        // 计划生成后才创建的下载任务也要跳过
+        if has_unfinished_download_tasks(app, &migration.comic_path_word) {
+            report
+                .skipped
+                .push(create_skip(migration, "漫画有未完成的下载任务".to_string()));
+            continue;
+        }
+
+        // 与这个漫画的导出任务排队执行，避免导出时章节目录被移走
+        let result = export_queue.run(&migration.comic_path_word, |_| {
+            Ok(Some(migrate_comic(&download_dir, &task)))
+        });
+
+        match result {
+            Ok(Some(Ok(()))) => {
+                // 旧目录和新目录都要更新，新目录可能在旧目录里，所以先更新旧目录
+                let library_index = app.get_library_index();
+                for dir in [&task.migration.old_dir, &task.migration.new_dir] {
+                    if let Err(err) = library_index.update_dir(dir) {
+                        let err_title = "更新已下载漫画索引失败";
+                        let message = err.to_message();
+                        tracing::error!(err_title, message);
+                    }
+                }
+                report.migrations.push(task.migration);
+            }
+            Ok(Some(Err(err))) => report.failed.push(ComicMigrationFailure {
+                migration: task.migration.clone(),
+                error: err.error.to_message(),
+                rollback_error: err
+                    .rollback_error
+                    .as_ref()
+                    .map(EyreReportToMessage::to_message),
+            }),
+            // 排队时被取消
+            Ok(None) => report
+                .skipped
+                .push(create_skip(migration, "迁移被取消".to_string())),
+            Err(err) => report.failed.push(ComicMigrationFailure {
+                migration: task.migration.clone(),
+                error: err.to_message(),
+                rollback_error: None,
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+/// 找出所有需要迁移的漫画，以及因为各种原因不能迁移的漫画
+fn plan_migration(
+    app: &AppHandle,
+    download_dir: &Path,
+) -> eyre::Result<(Vec<ComicMigrationTask>, Vec<ComicMigrationSkip>)> {
+    let mut comics_by_path_word: IndexMap<String, Vec<Comic>> = IndexMap::new();
+    for entry in WalkDir::new(download_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        if !entry.is_comic_metadata() {
+            continue;
+        }
+
+        match Comic::from_metadata(entry.path()) {
+            Ok(comic) => comics_by_path_word
+                .entry(comic.comic.path_word.clone())
+                .or_default()
+                .push(comic),
+            Err(err) => {
+                let err_title = "生成迁移计划的过程中遇到错误，已跳过";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
+    }
+
+    let mut skipped = Vec::new();
+    let mut tasks = Vec::new();
+    // 所有漫画迁移后的目录，不需要迁移或被跳过的漫画为原来的目录，用来检查冲突
+    let mut final_dirs: Vec<(String, PathBuf)> = Vec::new();
+    let mut old_dirs: Vec<(String, PathBuf)> = Vec::new();
+    for (comic_path_word, comics) in comics_by_path_word {
+        for comic in &comics {
+            if let Some(comic_download_dir) = &comic.comic_download_dir {
+                old_dirs.push((comic_path_word.clone(), comic_download_dir.clone()));
+            }
+        }
+
+        if comics.len() > 1 {
+            for comic in comics {
+                let reason = "漫画在下载目录里有多个版本，请先处理重复的版本".to_string();
+                skipped.push(create_skip_from_comic(&comic, reason));
+                if let Some(comic_download_dir) = comic.comic_download_dir {
+                    final_dirs.push((comic_path_word.clone(), comic_download_dir));
+                }
+            }
+            continue;
+        }
+
+        let Some(comic) = comics.into_iter().next() else {
+            continue;
+        };
+        let comic_download_dir = comic
+            .comic_download_dir
+            .clone()
+            .ok_or_eyre("`comic_download_dir`字段为`None`")?;
+
+        let skip_reason = if is_in_staging_dir(download_dir, &comic_download_dir) {
+            Some("漫画在上次没有完成的迁移的暂存目录里，请手动检查".to_string())
+        } else if has_unfinished_download_tasks(app, &comic_path_word) {
+            Some("漫画有未完成的下载任务".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = skip_reason {
+            skipped.push(create_skip_from_comic(&comic, reason));
+            final_dirs.push((comic_path_word, comic_download_dir));
+            continue;
+        }
+
+        match create_task(app, comic)? {
+            Some(task) => {
+                final_dirs.push((comic_path_word, task.migration.new_dir.clone()));
+                tasks.push(task);
+            }
+            None => final_dirs.push((comic_path_word, comic_download_dir)),
+        }
+    }
+
+    let conflicts = find_conflicts(&tasks, &final_dirs, &old_dirs);
+    let mut planned_tasks = Vec::new();
+    for task in tasks {
+        match conflicts.get(&task.migration.comic_path_word) {
+            Some(reason) => skipped.push(create_skip(&task.migration, reason.clone())),
+            None => planned_tasks.push(task),
+        }
+    }
+
+    Ok((planned_tasks, skipped))
+}
+
+/// 用当前的目录格式计算漫画的新路径，路径没有变化时返回`None`
+fn create_task(app: &AppHandle, comic: Comic) -> eyre::Result<Option<ComicMigrationTask>> {
+    let old_dir = comic
+        .comic_download_dir
+        .clone()
+        .ok_or_eyre("`comic_download_dir`字段为`None`")?;
+
+    let mut migrated_comic = comic.clone();
+    migrated_comic
+        .update_download_dir_fields_by_fmt(app)
+        .wrap_err(format!("计算漫画`{}`的新目录失败", comic.comic.name))?;
+    let new_dir = migrated_comic
+        .comic_download_dir
+        .clone()
+        .ok_or_eyre("`comic_download_dir`字段为`None`")?;
+
+    let new_chapter_dirs: HashMap<&str, &PathBuf> = migrated_comic
+        .comic
+        .groups
+        .values()
+        .flatten()
+        .filter_map(|chapter_info| {
+            let chapter_download_dir = chapter_info.chapter_download_dir.as_ref()?;
+            Some((chapter_info.chapter_uuid.as_str(), chapter_download_dir))
+        })
+        .collect();
+
+    let mut chapters = Vec::new();
+    for chapter_info in comic.comic.groups.values().flatten() {
+        if chapter_info.is_downloaded != Some(true) {
+            continue;
+        }
+        let (Some(old_chapter_dir), Some(new_chapter_dir)) = (
+            &chapter_info.chapter_download_dir,
+            new_chapter_dirs.get(chapter_info.chapter_uuid.as_str()),
+        ) else {
+            continue;
+        };
+        if old_chapter_dir == *new_chapter_dir {
+            continue;
+        }
+
+        chapters.push(ChapterMigration {
+            chapter_uuid: chapter_info.chapter_uuid.clone(),
+            source: format!(
+                "{} - {}",
+                chapter_info.group_name, chapter_info.chapter_title
+            ),
+            old_dir: old_chapter_dir.clone(),
+            new_dir: (*new_chapter_dir).clone(),
+        });
+    }
+
+    if old_dir == new_dir && chapters.is_empty() {
+        return Ok(None);
+    }
+
+    chapters.sort_by(|a, b| a.old_dir.cmp(&b.old_dir));
+    let migration = ComicMigration {
+        comic_uuid: comic.comic.uuid.clone(),
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        old_dir,
+        new_dir,
+        chapters,
+    };
+
+    Ok(Some(ComicMigrationTask {
+        migration,
+        migrated_comic,
+    }))
+}
+
+/// 返回有冲突的漫画的`path_word`及原因
+///
+/// `final_dirs`和`old_dirs`包含所有漫画，而不只是需要迁移的漫画
+fn find_conflicts(
+    tasks: &[ComicMigrationTask],
+    final_dirs: &[(String, PathBuf)],
+    old_dirs: &[(String, PathBuf)],
+) -> HashMap<String, String> {
+    let mut conflicts = HashMap::new();
+
+    for indexes in utils::find_path_collisions(final_dirs.iter().map(|(_, dir)| dir.as_path())) {
+        for &i in &indexes {
+            let others = indexes
+                .iter()
+                .filter(|&&j| j != i)
+                .map(|&j| format!("`{}`", final_dirs[j].0))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let reason = format!("新目录与漫画{others}的目录相同");
+            conflicts.entry(final_dirs[i].0.clone()).or_insert(reason);
+        }
+    }
+
+    for task in tasks {
+        let migration = &task.migration;
+        let comic_path_word = &migration.comic_path_word;
+
+        // 新目录在其他漫画的目录里的话，其他漫画迁移时会把这个漫画一起移走
+        let parent_comic = final_dirs.iter().chain(old_dirs).find(|(path_word, dir)| {
+            path_word != comic_path_word
+                && migration.new_dir != *dir
+                && migration.new_dir.starts_with(dir)
+        });
+        if let Some((path_word, dir)) = parent_comic {
+            let reason = format!("新目录在漫画`{path_word}`的目录`{}`里", dir.display());
+            conflicts.entry(comic_path_word.clone()).or_insert(reason);
+            continue;
+        }
+
+        // 同理，旧目录里有其他漫画的话，迁移时会把其他漫画一起移走
+        let child_comic = old_dirs.iter().find(|(path_word, dir)| {
+            path_word != comic_path_word && dir.starts_with(&migration.old_dir)
+        });
+        if let Some((path_word, dir)) = child_comic {
+            let reason = format!("漫画`{path_word}`的目录`{}`在旧目录里", dir.display());
+            conflicts.entry(comic_path_word.clone()).or_insert(reason);
+            continue;
+        }
+
+        // 新目录是旧目录的上层目录时，旧目录移走后它可能就不存在了，真的还存在的话迁移时会回滚
+        let new_dir_taken = migration.new_dir.exists()
+            && migration.new_dir != migration.old_dir
+            && !migration.old_dir.starts_with(&migration.new_dir);
+        if new_dir_taken {
+            let reason = format!("新目录`{}`已存在", migration.new_dir.display());
+            conflicts.entry(comic_path_word.clone()).or_insert(reason);
+            continue;
+        }
+
+        let outside_chapter = migration
+            .chapters
+            .iter()
+            .find(|chapter| !chapter.old_dir.starts_with(&migration.old_dir));
+        if let Some(chapter) = outside_chapter {
+            let reason = format!(
+                "章节`{}`的目录`{}`不在漫画目录里",
+                chapter.source,
+                chapter.old_dir.display()
+            );
+            conflicts.entry(comic_path_word.clone()).or_insert(reason);
+        }
+    }
+
+    conflicts
+}
+
+/// 迁移一个漫画，失败时回滚这个漫画已经移动过的目录，并恢复原来的元数据
+#[instrument(
+    level = "error",
+    skip_all,
+    fields(
+        comic_uuid = task.migration.comic_uuid,
+        comic_title = task.migration.comic_title,
+        old_dir = ?task.migration.old_dir,
+        new_dir = ?task.migration.new_dir
+    )
+)]
+fn migrate_comic(download_dir: &Path, task: &ComicMigrationTask) -> Result<(), MigrationError> {
+    let metadata_path = task.migration.old_dir.join("元数据.json");
+
+    let original_metadata = std::fs::read(&metadata_path)
+        .wrap_err(format!("读取`{}`失败", metadata_path.display()))
+        .map_err(|err| MigrationError {
+            error: err,
+            rollback_error: None,
+        })?;
+
+    let mut journal = MoveJournal::default();
+    let Err(err) = move_comic_dirs(download_dir, task, &mut journal) else {
+        return Ok(());
+    };
+
+    let err_title = "迁移漫画目录失败，正在回滚";
+    let message = err.to_message();
+    tracing::error!(err_title, message);
+
+    let rollback_result = journal.rollback(download_dir).and_then(|()| {
+        std::fs::write(&metadata_path, &original_metadata)
+            .wrap_err(format!("恢复`{}`失败", metadata_path.display()))
+    });
+    if let Err(rollback_err) = &rollback_result {
+        let err_title = "回滚漫画目录失败，需要手动处理";
+        let message = rollback_err.to_message();
+        tracing::error!(err_title, message);
+    }
+
+    Err(MigrationError {
+        error: err,
+        rollback_error: rollback_result.err(),
+    })
+}
+
+fn move_comic_dirs(
+    download_dir: &Path,
+    task: &ComicMigrationTask,
+    journal: &mut MoveJournal,
+) -> eyre::Result<()> {
+    let migration = &task.migration;
+
+    // 先把漫画目录移到暂存目录，这样新目录在旧目录里或旧目录在新目录里时也能移动
+    let comic_dir_changed = migration.old_dir != migration.new_dir;
+    let working_dir = if comic_dir_changed {
+        let staging_dir =
+            download_dir.join(format!("{STAGING_DIR_PREFIX}{}", migration.comic_uuid));
+        journal.rename(&migration.old_dir, &staging_dir)?;
+        remove_empty_dirs(migration.old_dir.parent(), download_dir);
+        staging_dir
+    } else {
+        migration.old_dir.clone()
+    };
+
+    // 章节目录也先全部移到暂存目录，避免新路径与其他章节的旧路径相同
+    let mut staged_chapter_dirs = Vec::new();
+    for chapter in &migration.chapters {
+        let old_relative_dir = strip_prefix(&chapter.old_dir, &migration.old_dir)?;
+        let new_relative_dir = strip_prefix(&chapter.new_dir, &migration.new_dir)?;
+        if old_relative_dir == new_relative_dir {
+            continue;
+        }
+
+        let old_chapter_dir = working_dir.join(old_relative_dir);
+        let staging_dir = working_dir.join(format!("{STAGING_DIR_PREFIX}{}", chapter.chapter_uuid));
+        journal.rename(&old_chapter_dir, &staging_dir)?;
+        staged_chapter_dirs.push((
+            old_chapter_dir,
+            staging_dir,
+            working_dir.join(new_relative_dir),
+        ));
+    }
+
+    // 删除旧的空分组目录等，它们的名字可能正好是某个章节的新目录名
+    for (old_chapter_dir, _, _) in &staged_chapter_dirs {
+        remove_empty_dirs(old_chapter_dir.parent(), &working_dir);
+    }
+
+    for (_, staging_dir, new_chapter_dir) in &staged_chapter_dirs {
+        journal.rename(staging_dir, new_chapter_dir)?;
+    }
+
+    if comic_dir_changed {
+        journal.rename(&working_dir, &migration.new_dir)?;
+    }
+
+    task.migrated_comic
+        .save_metadata()
+        .wrap_err("重写漫画元数据失败")?;
+
+    Ok(())
+}
+
+struct MigrationError {
+    error: eyre::Report,
+    /// 回滚也失败时的错误
+    rollback_error: Option<eyre::Report>,
+}
+
+/// 记录已经完成的移动，失败时按相反的顺序移回去
+#[derive(Default)]
+pub struct MoveJournal {
+    moves: Vec<(PathBuf, PathBuf)>,
+}
+
+impl MoveJournal {
+    pub fn rename(&mut self, from: &Path, to: &Path) -> eyre::Result<()> {
+        // 目标是空目录时`rename`在一些系统上会直接覆盖，所以要先检查
+        if to.exists() {
+            return Err(eyre!("`{}`已存在", to.display()));
+        }
+
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err(format!("创建目录`{}`失败", parent.display()))?;
+        }
+
+        std::fs::rename(from, to).wrap_err(format!(
+            "将`{}`移动到`{}`失败",
+            from.display(),
+            to.display()
+        ))?;
+
+        self.moves.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    }
+
+    pub fn rollback(self, download_dir: &Path) -> eyre::Result<()> {
+        for (from, to) in self.moves.into_iter().rev() {
+            // 移动时为`to`创建的上层目录已经没用了，其中可能就有`from`
+            remove_empty_dirs(to.parent(), download_dir);
+
+            if let Some(parent) = from.parent() {
+                std::fs::create_dir_all(parent)
+                    .wrap_err(format!("创建目录`{}`失败", parent.display()))?;
+            }
+
+            std::fs::rename(&to, &from).wrap_err(format!(
+                "将`{}`移回`{}`失败",
+                to.display(),
+                from.display()
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 从`dir`开始往上删除空目录，直到遇到非空目录或`stop_dir`，`stop_dir`本身不会被删除
+pub fn remove_empty_dirs(dir: Option<&Path>, stop_dir: &Path) {
+    let mut dir = dir;
+    while let Some(current_dir) = dir {
+        if current_dir == stop_dir || !current_dir.starts_with(stop_dir) {
+            break;
+        }
+        // 只有空目录才能删除成功
+        if std::fs::remove_dir(current_dir).is_err() {
+            break;
+        }
+        dir = current_dir.parent();
+    }
+}
+
+pub fn strip_prefix<'a>(path: &'a Path, prefix: &Path) -> eyre::Result<&'a Path> {
+    path.strip_prefix(prefix).wrap_err(format!(
+        "无法从路径`{}`中移除前缀`{}`",
+        path.display(),
+        prefix.display()
+    ))
+}
+
+pub fn is_in_staging_dir(download_dir: &Path, path: &Path) -> bool {
+    let relative_path = path.strip_prefix(download_dir).unwrap_or(path);
+    relative_path
+        .iter()
+        .any(|component| component.to_string_lossy().starts_with(STAGING_DIR_PREFIX))
+}
+
+/// 暂停或失败的任务继续下载时还会用旧目录，所以只有已完成的任务不算
+pub fn has_unfinished_download_tasks(app: &AppHandle, comic_path_word: &str) -> bool {
+    let download_manager = app.get_download_manager();
+    let download_tasks = download_manager.download_tasks.read();
+    download_tasks.values().any(|task| {
+        task.comic.comic.path_word == comic_path_word
+            && *task.state_sender.borrow() != DownloadTaskState::Completed
+    })
+}
+
+fn create_skip(migration: &ComicMigration, reason: String) -> ComicMigrationSkip {
+    ComicMigrationSkip {
+        comic_path_word: migration.comic_path_word.clone(),
+        comic_title: migration.comic_title.clone(),
+        comic_download_dir: migration.old_dir.clone(),
+        reason,
+    }
+}
+
+fn create_skip_from_comic(comic: &Comic, reason: String) -> ComicMigrationSkip {
+    ComicMigrationSkip {
+        comic_path_word: comic.comic.path_word.clone(),
+        comic_title: comic.comic.name.clone(),
+        comic_download_dir: comic.comic_download_dir.clone().unwrap_or_default(),
+        reason,
+    }
+}
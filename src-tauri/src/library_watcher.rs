@@ -0,0 +1,254 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use eyre::{OptionExt, WrapErr};
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::AppHandle;
+use tauri_specta::Event as _;
+use tokio::{sync::watch, time::Instant};
+use tracing::{instrument, Instrument};
+
+use crate::{
+    events::LibraryChangedEvent,
+    extensions::{AppHandleExt, EyreReportToMessage},
+};
+
+/// 下载时会频繁创建文件，收到第一个事件后等待一段时间，把这段时间内的变化合并后再处理
+const DEBOUNCE_DURATION: Duration = Duration::from_secs(1);
+
+static DOWNLOAD_DIR_SENDER: OnceLock<watch::Sender<PathBuf>> = OnceLock::new();
+
+/// 开始监听下载目录，程序外对下载目录的修改会同步到已下载漫画的索引，并发送`LibraryChangedEvent`
+pub fn init(app: &AppHandle) {
+    let download_dir = app.get_config().read().download_dir.clone();
+    let (sender, receiver) = watch::channel(download_dir);
+    DOWNLOAD_DIR_SENDER.get_or_init(|| sender);
+    tauri::async_runtime::spawn(library_watcher(app.clone(), receiver));
+}
+
+/// 修改下载目录后调用，让watcher改为监听新的下载目录
+#[instrument(level = "error", skip_all, fields(download_dir = ?download_dir))]
+pub fn watch_download_dir(download_dir: PathBuf) -> eyre::Result<()> {
+    DOWNLOAD_DIR_SENDER
+        .get()
+        .ok_or_eyre("DOWNLOAD_DIR_SENDER未初始化")?
+        .send_replace(download_dir);
+    Ok(())
+}
+
+#[instrument(level = "error", skip_all)]
+async fn library_watcher(app: AppHandle, mut download_dir_receiver: watch::Receiver<PathBuf>) {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let event_handler_span = tracing::error_span!("library_watcher_event_handler");
+
+    let event_handler = move |res| {
+        let send_event_task = async {
+            if let Err(err) = sender.send(res).await.map_err(eyre::Report::from) {
+                let err_title = "发送下载目录watcher事件失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        };
+
+        tauri::async_runtime::block_on(send_event_task.instrument(event_handler_span.clone()));
+    };
+
+    let mut watcher = match RecommendedWatcher::new(event_handler, notify::Config::default())
+        .map_err(eyre::Report::from)
+    {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            let err_title = "创建下载目录watcher失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+            return;
+        }
+    };
+
+    let mut watched_dir: Option<PathBuf> = None;
+    loop {
+        let download_dir = download_dir_receiver.borrow_and_update().clone();
+        if let Some(watched_dir) = watched_dir.take() {
+            // 旧的下载目录可能已经被删除了，所以忽略取消监听的错误
+            let _ = watcher.unwatch(&watched_dir);
+        }
+        match watch_dir(&mut watcher, &download_dir) {
+            Ok(()) => watched_dir = Some(download_dir.clone()),
+            Err(err) => {
+                let err_title = "下载目录watcher监听下载目录失败";
+                let message = err.to_message();
+                tracing::error!(err_title, message);
+            }
+        }
+
+        let mut changed_paths: HashSet<PathBuf> = HashSet::new();
+        let mut deadline = Instant::now();
+        loop {
+            tokio::select! {
+                Some(res) = receiver.recv() => match res.map_err(eyre::Report::from) {
+                    Ok(event) => {
+                        if changed_paths.is_empty() {
+                            deadline = Instant::now() + DEBOUNCE_DURATION;
+                        }
+                        collect_changed_paths(&event, &download_dir, &mut changed_paths);
+                    }
+                    Err(err) => {
+                        let err_title = "接收下载目录watcher事件失败";
+                        let message = err.to_message();
+                        tracing::error!(err_title, message);
+                    }
+                },
+                () = tokio::time::sleep_until(deadline), if !changed_paths.is_empty() => {
+                    let changed_paths = std::mem::take(&mut changed_paths);
+                    handle_changed_paths(&app, changed_paths).await;
+                }
+                res = download_dir_receiver.changed() => {
+                    if res.is_err() {
+                        return;
+                    }
+                    // 下载目录变了，旧目录里还没处理的变化不用再处理，索引会为新的下载目录重建
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn watch_dir(watcher: &mut RecommendedWatcher, download_dir: &Path) -> eyre::Result<()> {
+    // 还没下载过漫画时下载目录可能不存在
+    std::fs::create_dir_all(download_dir)
+        .wrap_err(format!("创建`{}`失败", download_dir.display()))?;
+    watcher
+        .watch(download_dir, RecursiveMode::Recursive)
+        .wrap_err(format!("监听`{}`失败", download_dir.display()))?;
+    Ok(())
+}
+
+/// 只收集可能影响索引的路径，图片内容的修改等变化会被忽略
+fn collect_changed_paths(event: &Event, download_dir: &Path, changed_paths: &mut HashSet<PathBuf>) {
+    // 有事件丢失了，只能重新遍历整个下载目录
+    if event.need_rescan() {
+        changed_paths.insert(download_dir.to_path_buf());
+        return;
+    }
+
+    if matches!(event.kind, EventKind::Access(_)) {
+        return;
+    }
+
+    let is_structure_changed = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    );
+    for path in &event.paths {
+        if is_structure_changed || is_metadata_file(path) {
+            changed_paths.insert(path.clone());
+        }
+    }
+}
+
+#[instrument(level = "error", skip_all, fields(changed_path_count = changed_paths.len()))]
+async fn handle_changed_paths(app: &AppHandle, changed_paths: HashSet<PathBuf>) {
+    let app_clone = app.clone();
+    let update_task =
+        tauri::async_runtime::spawn_blocking(move || update_index(&app_clone, changed_paths));
+
+    let comic_path_words = match update_task.await.map_err(eyre::Report::from) {
+        Ok(Ok(comic_path_words)) => comic_path_words,
+        Ok(Err(err)) | Err(err) => {
+            let err_title = "根据下载目录的变化更新已下载漫画索引失败";
+            let message = err.to_message();
+            tracing::error!(err_title, message);
+            return;
+        }
+    };
+
+    if comic_path_words.is_empty() {
+        return;
+    }
+
+    tracing::debug!(?comic_path_words, "下载目录有变化");
+    let _ = LibraryChangedEvent { comic_path_words }.emit(app);
+}
+
+/// 根据变化的路径更新索引，返回受影响的漫画的`path_word`
+fn update_index(app: &AppHandle, changed_paths: HashSet<PathBuf>) -> eyre::Result<Vec<String>> {
+    let library_index = app.get_library_index();
+
+    let mut comic_metadata_dirs = Vec::new();
+    let mut chapter_metadata_dirs = Vec::new();
+    let mut dirs = Vec::new();
+    let mut has_removed_path = false;
+    for path in changed_paths {
+        let file_name = path.file_name().and_then(|file_name| file_name.to_str());
+        if file_name == Some("元数据.json") {
+            comic_metadata_dirs.extend(path.parent().map(Path::to_path_buf));
+        } else if file_name == Some("章节元数据.json") {
+            chapter_metadata_dirs.extend(path.parent().map(Path::to_path_buf));
+        } else if path.is_dir() {
+            dirs.push(path);
+        } else if !path.exists() {
+            // 被删除或移走的可能是目录，但现在已经无法判断，稍后统一检查索引里的元数据文件是否还在
+            has_removed_path = true;
+        }
+    }
+
+    // 只保留最外层的目录，里面的目录会在遍历外层目录时一起更新
+    dirs.sort();
+    let mut outermost_dirs: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !outermost_dirs.iter().any(|outer| dir.starts_with(outer)) {
+            outermost_dirs.push(dir);
+        }
+    }
+    let is_in_outermost_dirs =
+        |dir: &PathBuf| outermost_dirs.iter().any(|outer| dir.starts_with(outer));
+    comic_metadata_dirs.retain(|dir| !is_in_outermost_dirs(dir));
+    chapter_metadata_dirs.retain(|dir| !is_in_outermost_dirs(dir));
+
+    let missing_dirs = if has_removed_path {
+        library_index.get_missing_dirs()?
+    } else {
+        Vec::new()
+    };
+
+    let related_paths: Vec<PathBuf> = outermost_dirs
+        .iter()
+        .chain(&comic_metadata_dirs)
+        .chain(&chapter_metadata_dirs)
+        .chain(&missing_dirs)
+        .cloned()
+        .collect();
+    // 更新前查询一次，找到被删除或移走的漫画
+    let mut comic_path_words = library_index.get_related_path_words(&related_paths)?;
+
+    for dir in &missing_dirs {
+        library_index.update_comic_metadata(dir)?;
+        library_index.update_chapter_metadata(dir)?;
+    }
+    for dir in &outermost_dirs {
+        library_index.update_dir(dir)?;
+    }
+    for comic_dir in &comic_metadata_dirs {
+        library_index.update_comic_metadata(comic_dir)?;
+    }
+    for chapter_dir in &chapter_metadata_dirs {
+        library_index.update_chapter_metadata(chapter_dir)?;
+    }
+
+    // 更新后再查询一次，找到新增或移入的漫画
+    comic_path_words.extend(library_index.get_related_path_words(&related_paths)?);
+
+    let mut comic_path_words: Vec<String> = comic_path_words.into_iter().collect();
+    comic_path_words.sort();
+    Ok(comic_path_words)
+}
+
+fn is_metadata_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|file_name| file_name == "元数据.json" || file_name == "章节元数据.json")
+}
@@ -1,19 +1,29 @@
+mod cbz_comic_info_update;
 mod chapter_info;
 mod comic;
 mod comic_info;
 mod download_format;
+mod fmt_preview;
 mod get_favorite_ordering;
 mod get_favorite_result;
+mod library_dedup;
+mod library_migration;
 mod log_metadata;
+mod long_img_format;
 mod search_result;
 
+pub use cbz_comic_info_update::*;
 pub use chapter_info::*;
 pub use comic::*;
 pub use comic_info::*;
 pub use download_format::*;
+pub use fmt_preview::*;
 pub use get_favorite_ordering::*;
 pub use get_favorite_result::*;
+pub use library_dedup::*;
+pub use library_migration::*;
 pub use log_metadata::*;
+pub use long_img_format::*;
 pub use search_result::*;
 
 pub type AsyncMutex<T> = tokio::sync::Mutex<T>;
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct CbzComicInfoUpdateReport {
+    /// `ComicInfo.xml`有变化并已重写的CBZ
+    pub updated_cbz_paths: Vec<PathBuf>,
+    /// 标记为已导出CBZ，但按当前的章节导出格式找不到CBZ的章节，通常是修改过导出格式或手动移动过CBZ
+    pub missing_chapters: Vec<MissingCbzChapter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingCbzChapter {
+    pub chapter_uuid: String,
+    pub chapter_title: String,
+    /// 按当前的章节导出格式，CBZ应该在的路径
+    pub expected_cbz_path: PathBuf,
+}
@@ -1,15 +1,17 @@
-use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
-use eyre::{OptionExt, WrapErr};
+use chrono::NaiveDate;
+use eyre::{eyre, OptionExt, WrapErr};
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use tauri::AppHandle;
 use tracing::instrument;
 
-use crate::{extensions::AppHandleExt, types::Comic, utils};
+use crate::{
+    config::{ImgProcessConfig, TextConversion},
+    fmt_template,
+    types::{Comic, ComicDirFmtParams},
+    utils,
+};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
@@ -28,12 +30,22 @@ pub struct ChapterInfo {
     pub group_size: i64,
     /// 此章节在group中的顺序
     pub order: f64,
+    /// 章节的创建时间，比如`2023-05-12`
+    pub datetime_created: String,
     /// 漫画的连载状态
     pub comic_status: ComicStatus,
+    /// 是否为条漫，下载章节时才能知道
+    pub is_long: bool,
     /// 是否曾导出过PDF
     pub is_pdf_exported: bool,
     /// 是否曾导出过CBZ
     pub is_cbz_exported: bool,
+    /// 是否曾导出过长图
+    pub is_long_img_exported: bool,
+    /// 是否曾导出过EPUB
+    pub is_epub_exported: bool,
+    /// 下载时对图片做过的处理，为`None`表示下载时没有处理，导出时会跳过已经做过的处理
+    pub download_img_process: Option<ImgProcessConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_downloaded: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -41,6 +53,12 @@ pub struct ChapterInfo {
 }
 
 impl ChapterInfo {
+    /// 从`datetime_created`中解析出章节的创建日期
+    pub fn created_date(&self) -> Option<NaiveDate> {
+        let date = self.datetime_created.get(..10)?;
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+    }
+
     #[instrument(
         level = "error",
         skip_all,
@@ -151,10 +169,10 @@ impl ChapterInfo {
         level = "error",
         skip_all,
         fields(
-            comic_uuid = fmt_params.comic_uuid,
-            comic_path_word = fmt_params.comic_path_word,
-            comic_title = fmt_params.comic_title,
-            author = fmt_params.author,
+            comic_uuid = fmt_params.comic.comic_uuid,
+            comic_path_word = fmt_params.comic.comic_path_word,
+            comic_title = fmt_params.comic.comic_title,
+            author = fmt_params.comic.author,
             group_path_word = fmt_params.group_path_word,
             group_title = fmt_params.group_title,
             chapter_uuid = fmt_params.chapter_uuid,
@@ -163,43 +181,15 @@ impl ChapterInfo {
         )
     )]
     pub fn get_chapter_download_dir_by_fmt(
-        app: &AppHandle,
         comic_download_dir: &Path,
+        chapter_dir_fmt: &str,
         fmt_params: &ChapterDirFmtParams,
     ) -> eyre::Result<PathBuf> {
-        use strfmt::strfmt;
-
-        let json_value = serde_json::to_value(fmt_params)
-            .wrap_err("将ChapterDirFmtParams转为serde_json::Value失败")?;
-
-        let json_map = json_value
-            .as_object()
-            .ok_or_eyre("ChapterDirFmtParams不是JSON对象")?;
-
-        let vars: HashMap<String, String> = json_map
-            .into_iter()
-            .map(|(k, v)| {
-                let key = k.clone();
-                let value = match v {
-                    serde_json::Value::String(s) => s.clone(),
-                    _ => v.to_string(),
-                };
-                (key, value)
-            })
-            .collect();
-        let mut chapter_dir_fmt = app.get_config().read().chapter_dir_fmt.clone();
-        utils::preprocess_order_placeholder(&mut chapter_dir_fmt, &vars)?;
-
-        let dir_fmt_parts: Vec<&str> = chapter_dir_fmt.split('/').collect();
-
-        let mut dir_names = Vec::new();
-        for fmt in dir_fmt_parts {
-            let dir_name = strfmt(fmt, &vars).wrap_err("格式化目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
-            if !dir_name.is_empty() {
-                dir_names.push(dir_name);
-            }
-        }
+        let vars = fmt_template::to_vars(fmt_params)?;
+        validate_chapter_dir_fmt(chapter_dir_fmt)?;
+
+        let dir_names =
+            fmt_template::render_path(chapter_dir_fmt, &vars).wrap_err("格式化目录名失败")?;
         // 将格式化后的目录名拼接成完整的目录路径
         let mut chapter_download_dir = comic_download_dir.to_path_buf();
         for dir_name in dir_names {
@@ -221,13 +211,109 @@ pub enum ComicStatus {
 
 #[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ChapterDirFmtParams {
-    pub comic_uuid: String,
-    pub comic_path_word: String,
-    pub comic_title: String,
-    pub author: String,
+    #[serde(flatten)]
+    pub comic: ComicDirFmtParams,
     pub group_path_word: String,
     pub group_title: String,
+    /// 分组的章节数
+    pub group_size: i64,
     pub chapter_uuid: String,
     pub chapter_title: String,
     pub order: f64,
+    /// 章节在分组中的序号，由`ComicDetail::get_chapter_indexes`计算
+    ///
+    /// 分组插入新章节后可能变化，用在`chapter_dir_fmt`中时已下载的目录不会跟着改名
+    pub chapter_index: String,
+    /// 章节的页数
+    pub page_count: i64,
+    /// 章节的创建日期，比如`2023-05-12`，解析失败时为空
+    pub chapter_date: String,
+}
+
+impl ChapterDirFmtParams {
+    /// 文字字段会用`text_conversion`转换，`comic_fmt_params`应该已经用同一个`text_conversion`转换过
+    pub fn new(
+        comic_fmt_params: ComicDirFmtParams,
+        chapter_info: &ChapterInfo,
+        chapter_index: String,
+        text_conversion: TextConversion,
+    ) -> Self {
+        let chapter_date = chapter_info
+            .created_date()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        Self {
+            comic: comic_fmt_params,
+            group_path_word: chapter_info.group_path_word.clone(),
+            group_title: text_conversion.convert(&chapter_info.group_name),
+            group_size: chapter_info.group_size,
+            chapter_uuid: chapter_info.chapter_uuid.clone(),
+            chapter_title: text_conversion.convert(&chapter_info.chapter_title),
+            order: chapter_info.order,
+            chapter_index,
+            page_count: chapter_info.chapter_size,
+            chapter_date,
+        }
+    }
+}
+
+/// `paths[i]`是`chapter_infos[i]`的路径，多个章节的路径相同时，除了最早创建的章节，其他章节路径的最后一层都会加上章节ID的前8位
+///
+/// 最早创建的章节保持原来的路径，这样以后出现同名的新章节时，已下载或已导出的章节的路径不会变
+pub fn disambiguate_chapter_paths(
+    chapter_infos: &[&ChapterInfo],
+    paths: &mut [PathBuf],
+    ext: &str,
+    max_bytes: usize,
+) {
+    for mut indexes in utils::find_path_collisions(paths.iter().map(PathBuf::as_path)) {
+        indexes.sort_by_key(|&i| {
+            let chapter_info = chapter_infos[i];
+            (&chapter_info.datetime_created, &chapter_info.chapter_uuid)
+        });
+
+        for &i in &indexes[1..] {
+            let chapter_uuid = &chapter_infos[i].chapter_uuid;
+            let suffix = chapter_uuid.get(..8).unwrap_or(chapter_uuid);
+            paths[i] = utils::add_filename_suffix(&paths[i], suffix, ext, max_bytes);
+        }
+    }
+}
+
+/// 章节目录格式的最后一层必须包含章节字段，否则同一分组的章节会下载到同一个目录
+fn validate_chapter_dir_fmt(chapter_dir_fmt: &str) -> eyre::Result<()> {
+    let mut fmt_parts: Vec<&str> = chapter_dir_fmt.split('/').collect();
+
+    let Some(last_fmt_part) = fmt_parts.pop() else {
+        return Err(eyre!("`章节目录格式`不能为空"));
+    };
+
+    if !fmt_template::contains_any_field(last_fmt_part, &fmt_template::CHAPTER_FIELDS)? {
+        return Err(eyre!(
+            "`章节目录格式`不合法，最后一层必须至少包含一个章节字段 {:?}",
+            fmt_template::CHAPTER_FIELDS
+        ));
+    }
+
+    for fmt_part in fmt_parts {
+        if let Some(field) = fmt_template::find_used_field(fmt_part, &fmt_template::CHAPTER_FIELDS)?
+        {
+            return Err(eyre!(
+                "`章节目录格式`不合法，章节字段只能出现在最后一层，你当前在非最后一层使用了`{field}`。非最后一层只允许使用 {:?} 和 {:?}",
+                fmt_template::COMIC_FIELDS,
+                fmt_template::GROUP_FIELDS,
+            ));
+        }
+    }
+
+    let fields = [
+        fmt_template::COMIC_FIELDS.as_slice(),
+        &fmt_template::GROUP_FIELDS,
+        &fmt_template::CHAPTER_FIELDS,
+    ]
+    .concat();
+    fmt_template::validate_fields(chapter_dir_fmt, &fields).wrap_err("`章节目录格式`不合法")?;
+
+    Ok(())
 }
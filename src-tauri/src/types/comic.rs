@@ -3,7 +3,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use chrono::Datelike;
 use eyre::{eyre, OptionExt, WrapErr};
+use float_ord::FloatOrd;
+use image::ImageFormat;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::AppHandle;
@@ -11,15 +14,22 @@ use tracing::instrument;
 use walkdir::WalkDir;
 
 use crate::{
+    config::TextConversion,
     extensions::{AppHandleExt, WalkDirEntryExt},
+    fmt_template,
     responses::{
         AuthorRespData, ChapterInGetChaptersRespData, GetComicRespData, GroupRespData,
         LabeledValueRespData, LastChapterRespData, ThemeRespData,
     },
-    types::{ChapterDirFmtParams, ChapterInfo, ComicStatus},
+    types::{disambiguate_chapter_paths, ChapterDirFmtParams, ChapterInfo, ComicStatus},
     utils,
 };
 
+/// 封面文件名(不含扩展名)，封面与`元数据.json`放在同一目录下
+const COVER_FILE_STEM: &str = "封面";
+/// 封面可能的扩展名
+const COVER_EXTENSIONS: [&str; 2] = ["webp", "jpg"];
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 #[allow(clippy::struct_excessive_bools)]
@@ -83,9 +93,13 @@ impl Comic {
 
         // TODO: 这是为了兼容v0.10.2及之前的版本，后续需要移除，计划在v0.12.0之后移除
         if let Some(comic_download_dir) = path_word_to_dir_map.get(&comic.comic.path_word) {
-            comic
+            let created_chapter_dirs = comic
                 .create_chapter_metadata_for_old_version(comic_download_dir)
                 .wrap_err("为旧版本创建章节元数据失败")?;
+            let library_index = app.get_library_index();
+            for chapter_dir in created_chapter_dirs {
+                library_index.update_chapter_metadata(&chapter_dir)?;
+            }
         }
 
         comic.update_fields(&path_word_to_dir_map)?;
@@ -117,6 +131,28 @@ impl Comic {
         Ok(comic)
     }
 
+    /// 用索引中保存的元数据创建Comic，与`from_metadata`不同，不会读取任何文件
+    ///
+    /// `chapter_metadata`为漫画目录下所有章节元数据的路径和内容
+    #[instrument(level = "error", skip_all, fields(comic_download_dir = ?comic_download_dir))]
+    pub fn from_indexed_metadata(
+        comic_download_dir: PathBuf,
+        metadata_str: &str,
+        chapter_metadata: &[(PathBuf, String)],
+    ) -> eyre::Result<Comic> {
+        let mut comic =
+            serde_json::from_str::<Comic>(metadata_str).wrap_err("将元数据反序列化为Comic失败")?;
+
+        comic.comic_download_dir = Some(comic_download_dir);
+        comic.is_downloaded = Some(true);
+
+        for (metadata_path, metadata_str) in chapter_metadata {
+            comic.update_chapter_info_fields(metadata_path, metadata_str)?;
+        }
+
+        Ok(comic)
+    }
+
     #[instrument(level = "error", skip_all, fields(comic_uuid = self.comic.uuid, comic_title = self.comic.name))]
     pub fn update_fields(
         &mut self,
@@ -154,52 +190,78 @@ impl Comic {
             let metadata_str = std::fs::read_to_string(metadata_path)
                 .wrap_err(format!("读取`{}`失败", metadata_path.display()))?;
 
-            let chapter_json: serde_json::Value =
-                serde_json::from_str(&metadata_str).wrap_err(format!(
-                    "将`{}`反序列化为serde_json::Value失败",
-                    metadata_path.display()
-                ))?;
-
-            let chapter_uuid = chapter_json
-                .get("chapterUuid")
-                .and_then(|uuid| uuid.as_str())
-                .ok_or_eyre(format!(
-                    "`{}`没有`chapterUuid`字段",
-                    metadata_path.display()
-                ))?
-                .to_string();
-
-            let group_path_word = chapter_json
-                .get("groupPathWord")
-                .and_then(|word| word.as_str())
-                .ok_or_eyre(format!(
-                    "`{}`没有`groupPathWord`字段",
-                    metadata_path.display()
-                ))?
-                .to_string();
-
-            let Some(group) = self.comic.groups.get_mut(&group_path_word) else {
-                continue;
-            };
+            self.update_chapter_info_fields(metadata_path, &metadata_str)?;
+        }
 
-            if let Some(chapter_info) = group
-                .iter_mut()
-                .find(|chapter| chapter.chapter_uuid == chapter_uuid)
-            {
-                let parent = metadata_path
-                    .parent()
-                    .ok_or_eyre(format!("`{}`没有父目录", metadata_path.display()))?;
-                chapter_info.chapter_download_dir = Some(parent.to_path_buf());
-                chapter_info.is_downloaded = Some(true);
-                chapter_info.is_pdf_exported = chapter_json
-                    .get("isPdfExported")
-                    .and_then(serde_json::Value::as_bool)
-                    .unwrap_or(false);
-                chapter_info.is_cbz_exported = chapter_json
-                    .get("isCbzExported")
-                    .and_then(serde_json::Value::as_bool)
-                    .unwrap_or(false);
-            }
+        Ok(())
+    }
+
+    /// 用`metadata_path`处的章节元数据(内容为`metadata_str`)更新对应章节的字段
+    fn update_chapter_info_fields(
+        &mut self,
+        metadata_path: &Path,
+        metadata_str: &str,
+    ) -> eyre::Result<()> {
+        let chapter_json: serde_json::Value =
+            serde_json::from_str(metadata_str).wrap_err(format!(
+                "将`{}`反序列化为serde_json::Value失败",
+                metadata_path.display()
+            ))?;
+
+        let chapter_uuid = chapter_json
+            .get("chapterUuid")
+            .and_then(|uuid| uuid.as_str())
+            .ok_or_eyre(format!(
+                "`{}`没有`chapterUuid`字段",
+                metadata_path.display()
+            ))?
+            .to_string();
+
+        let group_path_word = chapter_json
+            .get("groupPathWord")
+            .and_then(|word| word.as_str())
+            .ok_or_eyre(format!(
+                "`{}`没有`groupPathWord`字段",
+                metadata_path.display()
+            ))?
+            .to_string();
+
+        let Some(group) = self.comic.groups.get_mut(&group_path_word) else {
+            return Ok(());
+        };
+
+        if let Some(chapter_info) = group
+            .iter_mut()
+            .find(|chapter| chapter.chapter_uuid == chapter_uuid)
+        {
+            let parent = metadata_path
+                .parent()
+                .ok_or_eyre(format!("`{}`没有父目录", metadata_path.display()))?;
+            chapter_info.chapter_download_dir = Some(parent.to_path_buf());
+            chapter_info.is_downloaded = Some(true);
+            chapter_info.is_pdf_exported = chapter_json
+                .get("isPdfExported")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            chapter_info.is_cbz_exported = chapter_json
+                .get("isCbzExported")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            chapter_info.is_long_img_exported = chapter_json
+                .get("isLongImgExported")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            chapter_info.is_epub_exported = chapter_json
+                .get("isEpubExported")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            chapter_info.is_long = chapter_json
+                .get("isLong")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+            chapter_info.download_img_process = chapter_json
+                .get("downloadImgProcess")
+                .and_then(|value| serde_json::from_value(value.clone()).ok());
         }
 
         Ok(())
@@ -234,6 +296,63 @@ impl Comic {
         Ok(())
     }
 
+    /// 下载封面并保存到漫画下载目录，与`元数据.json`放在一起
+    ///
+    /// 已存在的封面会被覆盖，扩展名与当前格式不一致的旧封面会被删除
+    #[instrument(level = "error", skip_all, fields(comic_uuid = self.comic.uuid, comic_title = self.comic.name, cover = self.comic.cover))]
+    pub async fn save_cover(&self, app: &AppHandle) -> eyre::Result<()> {
+        let comic_download_dir = self
+            .comic_download_dir
+            .as_ref()
+            .ok_or_eyre("`comic_download_dir`字段为`None`")?;
+
+        if self.comic.cover.is_empty() {
+            return Err(eyre!("漫画没有封面URL"));
+        }
+
+        let copy_client = app.get_copy_client();
+        let (cover_data, cover_format) = copy_client
+            .get_img_data_and_format(&self.comic.cover)
+            .await
+            .wrap_err("下载封面失败")?;
+
+        let extension = match cover_format {
+            ImageFormat::WebP => "webp",
+            ImageFormat::Jpeg => "jpg",
+            _ => return Err(eyre!("封面出现了意料之外的格式: {cover_format:?}")),
+        };
+
+        std::fs::create_dir_all(comic_download_dir)
+            .wrap_err(format!("创建目录`{}`失败", comic_download_dir.display()))?;
+
+        // 删除扩展名不一致的旧封面，保证目录里只有一个封面
+        for ext in COVER_EXTENSIONS {
+            let old_cover_path = comic_download_dir.join(format!("{COVER_FILE_STEM}.{ext}"));
+            if ext != extension && old_cover_path.exists() {
+                std::fs::remove_file(&old_cover_path)
+                    .wrap_err(format!("删除`{}`失败", old_cover_path.display()))?;
+            }
+        }
+
+        let cover_path = comic_download_dir.join(format!("{COVER_FILE_STEM}.{extension}"));
+        std::fs::write(&cover_path, &cover_data)
+            .wrap_err(format!("写入文件`{}`失败", cover_path.display()))?;
+
+        tracing::trace!("封面保存成功");
+
+        Ok(())
+    }
+
+    /// 获取漫画下载目录中的封面路径，如果封面不存在则返回`None`
+    pub fn get_cover_path(&self) -> Option<PathBuf> {
+        let comic_download_dir = self.comic_download_dir.as_ref()?;
+
+        COVER_EXTENSIONS
+            .iter()
+            .map(|ext| comic_download_dir.join(format!("{COVER_FILE_STEM}.{ext}")))
+            .find(|path| path.is_file())
+    }
+
     #[instrument(level = "error", skip_all, fields(comic_uuid = self.comic.uuid, comic_title = self.comic.name))]
     pub fn get_comic_export_dir(&self, app: &AppHandle) -> eyre::Result<PathBuf> {
         let (download_dir, export_dir) = {
@@ -281,44 +400,61 @@ impl Comic {
 
     /// 根据fmt更新`comic_download_dir`和`chapter_infos.chapter_download_dir`字段
     #[instrument(level = "error", skip_all, fields(comic_uuid = self.comic.uuid, comic_title = self.comic.name))]
-    fn update_download_dir_fields_by_fmt(&mut self, app: &AppHandle) -> eyre::Result<()> {
-        let comic_uuid = self.comic.uuid.clone();
-        let comic_title = self.comic.name.clone();
-        let comic_path_word = self.comic.path_word.clone();
-        let author = self
-            .comic
-            .author
-            .iter()
-            .map(|a| a.name.clone())
-            .collect::<Vec<_>>()
-            .join(", ");
-
-        let comic_dir_fmt_params = ComicDirFmtParams {
-            comic_uuid: comic_uuid.clone(),
-            comic_path_word: comic_path_word.clone(),
-            comic_title: comic_title.clone(),
-            author: author.clone(),
+    pub fn update_download_dir_fields_by_fmt(&mut self, app: &AppHandle) -> eyre::Result<()> {
+        let (download_dir, comic_dir_fmt, chapter_dir_fmt, text_conversion) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.download_dir.clone(),
+                config.comic_dir_fmt.clone(),
+                config.chapter_dir_fmt.clone(),
+                config.fmt_text_conversion,
+            )
         };
-        let comic_download_dir = Comic::get_comic_download_dir_by_fmt(app, &comic_dir_fmt_params)?;
+
+        let comic_dir_fmt_params = ComicDirFmtParams::new(&self.comic, text_conversion);
+        let comic_download_dir = Comic::get_comic_download_dir_by_fmt(
+            &download_dir,
+            &comic_dir_fmt,
+            &comic_dir_fmt_params,
+        )?;
         self.comic_download_dir = Some(comic_download_dir.clone());
 
-        for chapter_info in &mut self.comic.groups.values_mut().flatten() {
-            let chapter_dir_fmt_params = ChapterDirFmtParams {
-                comic_uuid: comic_uuid.clone(),
-                comic_path_word: comic_path_word.clone(),
-                comic_title: comic_title.clone(),
-                author: author.clone(),
-                group_path_word: chapter_info.group_path_word.clone(),
-                group_title: chapter_info.group_name.clone(),
-                chapter_uuid: chapter_info.chapter_uuid.clone(),
-                chapter_title: chapter_info.chapter_title.clone(),
-                order: chapter_info.order,
-            };
+        let chapter_indexes = self.comic.get_chapter_indexes();
+        let mut chapter_infos: Vec<&mut ChapterInfo> =
+            self.comic.groups.values_mut().flatten().collect();
+
+        let mut chapter_download_dirs = Vec::with_capacity(chapter_infos.len());
+        for chapter_info in &chapter_infos {
+            let chapter_index = chapter_indexes
+                .get(&chapter_info.chapter_uuid)
+                .cloned()
+                .unwrap_or_default();
+            let chapter_dir_fmt_params = ChapterDirFmtParams::new(
+                comic_dir_fmt_params.clone(),
+                chapter_info,
+                chapter_index,
+                text_conversion,
+            );
             let chapter_download_dir = ChapterInfo::get_chapter_download_dir_by_fmt(
-                app,
                 &comic_download_dir,
+                &chapter_dir_fmt,
                 &chapter_dir_fmt_params,
             )?;
+            chapter_download_dirs.push(chapter_download_dir);
+        }
+
+        let chapter_info_refs: Vec<&ChapterInfo> = chapter_infos.iter().map(|c| &**c).collect();
+        disambiguate_chapter_paths(
+            &chapter_info_refs,
+            &mut chapter_download_dirs,
+            "",
+            utils::MAX_FILENAME_BYTES,
+        );
+
+        for (chapter_info, chapter_download_dir) in
+            chapter_infos.iter_mut().zip(chapter_download_dirs)
+        {
             chapter_info.chapter_download_dir = Some(chapter_download_dir);
         }
 
@@ -335,49 +471,19 @@ impl Comic {
             author = fmt_params.author,
         )
     )]
-    fn get_comic_download_dir_by_fmt(
-        app: &AppHandle,
+    pub fn get_comic_download_dir_by_fmt(
+        download_dir: &Path,
+        comic_dir_fmt: &str,
         fmt_params: &ComicDirFmtParams,
     ) -> eyre::Result<PathBuf> {
-        use strfmt::strfmt;
-
-        let json_value = serde_json::to_value(fmt_params)
-            .wrap_err("将ComicDirFmtParams转为serde_json::Value失败")?;
-
-        let json_map = json_value
-            .as_object()
-            .ok_or_eyre("ComicDirFmtParams不是JSON对象")?;
-
-        let vars: HashMap<String, String> = json_map
-            .into_iter()
-            .map(|(k, v)| {
-                let key = k.clone();
-                let value = match v {
-                    serde_json::Value::String(s) => s.clone(),
-                    _ => v.to_string(),
-                };
-                (key, value)
-            })
-            .collect();
-
-        let (download_dir, comic_dir_fmt) = {
-            let config = app.get_config();
-            let config = config.read();
-            (config.download_dir.clone(), config.comic_dir_fmt.clone())
-        };
+        let vars = fmt_template::to_vars(fmt_params)?;
 
-        let dir_fmt_parts: Vec<&str> = comic_dir_fmt.split('/').collect();
+        validate_comic_dir_fmt(comic_dir_fmt)?;
 
-        let mut dir_names = Vec::new();
-        for fmt in dir_fmt_parts {
-            let dir_name = strfmt(fmt, &vars).wrap_err("格式化目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
-            if !dir_name.is_empty() {
-                dir_names.push(dir_name);
-            }
-        }
+        let dir_names =
+            fmt_template::render_path(comic_dir_fmt, &vars).wrap_err("格式化目录名失败")?;
         // 将格式化后的目录名拼接成完整的目录路径
-        let mut comic_download_dir = download_dir;
+        let mut comic_download_dir = download_dir.to_path_buf();
         for dir_name in dir_names {
             comic_download_dir = comic_download_dir.join(dir_name);
         }
@@ -385,10 +491,11 @@ impl Comic {
         Ok(comic_download_dir)
     }
 
-    fn create_chapter_metadata_for_old_version(
+    /// 返回创建了章节元数据的章节目录
+    pub fn create_chapter_metadata_for_old_version(
         &self,
         comic_download_dir: &Path,
-    ) -> eyre::Result<()> {
+    ) -> eyre::Result<Vec<PathBuf>> {
         let mut chapter_dirs = HashSet::new();
         for group_entry in std::fs::read_dir(comic_download_dir)?.filter_map(Result::ok) {
             let Ok(file_type) = group_entry.file_type() else {
@@ -409,6 +516,7 @@ impl Comic {
             }
         }
 
+        let mut created_chapter_dirs = Vec::new();
         for chapter_info in self.comic.groups.values().flatten() {
             let group_title = utils::filename_filter(&chapter_info.group_name);
             let chapter_title = utils::filename_filter(&chapter_info.chapter_title);
@@ -425,13 +533,14 @@ impl Comic {
             if old_chapter_dir_exists && !old_chapter_metadata_exists {
                 // 如果旧版本的章节目录存在，但没有元数据文件，就创建一个
                 let mut info = chapter_info.clone();
-                info.chapter_download_dir = Some(old_chapter_dir);
+                info.chapter_download_dir = Some(old_chapter_dir.clone());
                 info.is_downloaded = Some(true);
                 info.save_metadata()?;
+                created_chapter_dirs.push(old_chapter_dir);
             }
         }
 
-        Ok(())
+        Ok(created_chapter_dirs)
     }
 }
 
@@ -520,11 +629,16 @@ impl ComicDetail {
                     group_name: group_resp_data.name.clone(),
                     group_size: chapter.count,
                     order: chapter.ordered as f64 / 10.0,
+                    datetime_created: chapter.datetime_created,
                     comic_status,
+                    is_long: false,
                     is_downloaded: None,
                     chapter_download_dir: None,
                     is_pdf_exported: false,
                     is_cbz_exported: false,
+                    is_long_img_exported: false,
+                    is_epub_exported: false,
+                    download_img_process: None,
                 })
                 .collect();
 
@@ -558,6 +672,31 @@ impl ComicDetail {
             groups,
         }
     }
+
+    /// `chapter_uuid` -> 章节在分组中按`order`排序后的序号
+    ///
+    /// 序号从1开始，按分组的章节数补零，比如分组有120话时第7话的序号为`007`
+    pub fn get_chapter_indexes(&self) -> HashMap<String, String> {
+        let mut chapter_indexes = HashMap::new();
+        for chapter_infos in self.groups.values() {
+            let mut chapter_infos: Vec<&ChapterInfo> = chapter_infos.iter().collect();
+            chapter_infos.sort_by_key(|chapter_info| FloatOrd(chapter_info.order));
+
+            let group_size = chapter_infos
+                .iter()
+                .map(|chapter_info| chapter_info.group_size)
+                .max()
+                .unwrap_or_default()
+                .max(i64::try_from(chapter_infos.len()).unwrap_or(i64::MAX));
+            let width = group_size.to_string().len();
+
+            for (i, chapter_info) in chapter_infos.iter().enumerate() {
+                let chapter_index = format!("{:0width$}", i + 1);
+                chapter_indexes.insert(chapter_info.chapter_uuid.clone(), chapter_index);
+            }
+        }
+        chapter_indexes
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
@@ -654,10 +793,82 @@ impl Group {
     }
 }
 
+/// `comic_dir_fmt`的模板参数，其他模板的参数也包含这些漫画字段
 #[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ComicDirFmtParams {
     pub comic_uuid: String,
     pub comic_path_word: String,
     pub comic_title: String,
+    /// 漫画的别名，没有别名时为空
+    pub alias: String,
     pub author: String,
+    /// 最早的章节的创建年份，没有章节时为空
+    ///
+    /// 与`status`一样可能在下载后变化，用在目录格式中时已下载的目录不会跟着改名
+    pub publish_year: String,
+    /// 地区，即`region.display`
+    pub region: String,
+    /// 连载状态，即`status.display`，连载完结后会变化
+    pub status: String,
+    /// 第一个题材，没有题材时为空
+    pub theme: String,
+}
+
+impl ComicDirFmtParams {
+    /// 文字字段会用`text_conversion`转换
+    pub fn new(comic: &ComicDetail, text_conversion: TextConversion) -> Self {
+        let author = comic
+            .author
+            .iter()
+            .map(|a| a.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let publish_year = comic
+            .groups
+            .values()
+            .flatten()
+            .filter_map(ChapterInfo::created_date)
+            .min()
+            .map(|date| date.year().to_string())
+            .unwrap_or_default();
+
+        let theme = comic
+            .theme
+            .first()
+            .map(|theme| theme.name.clone())
+            .unwrap_or_default();
+
+        Self {
+            comic_uuid: comic.uuid.clone(),
+            comic_path_word: comic.path_word.clone(),
+            comic_title: text_conversion.convert(&comic.name),
+            alias: text_conversion.convert(comic.alias.as_deref().unwrap_or_default()),
+            author: text_conversion.convert(&author),
+            publish_year,
+            region: text_conversion.convert(&comic.region.display),
+            status: text_conversion.convert(&comic.status.display),
+            theme: text_conversion.convert(&theme),
+        }
+    }
+}
+
+/// 漫画目录格式只能使用漫画字段，因为同一漫画的所有章节都在这个目录下
+fn validate_comic_dir_fmt(comic_dir_fmt: &str) -> eyre::Result<()> {
+    let forbidden_fields = [
+        fmt_template::GROUP_FIELDS.as_slice(),
+        &fmt_template::CHAPTER_FIELDS,
+    ]
+    .concat();
+    if let Some(field) = fmt_template::find_used_field(comic_dir_fmt, &forbidden_fields)? {
+        return Err(eyre!(
+            "`漫画目录格式`不合法，不能使用分组字段和章节字段，你使用了`{field}`，只能使用 {:?}",
+            fmt_template::COMIC_FIELDS
+        ));
+    }
+
+    fmt_template::validate_fields(comic_dir_fmt, &fmt_template::COMIC_FIELDS)
+        .wrap_err("`漫画目录格式`不合法")?;
+
+    Ok(())
 }
@@ -1,55 +1,127 @@
+use chrono::Datelike;
+use eyre::eyre;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use yaserde::{YaDeserialize, YaSerialize};
 
-use crate::types::Comic;
+use crate::types::{Comic, LabeledValue};
 
 use super::{ChapterInfo, ComicStatus};
 
+/// 拷贝漫画网页版的漫画详情页
+const COMIC_WEB_URL_PREFIX: &str = "https://www.2025copy.com/comic";
+
 /// <https://wiki.kavitareader.com/guides/metadata/comics>/
+///
+/// 字段顺序与ComicInfo v2.1的schema保持一致，有些阅读器会按顺序解析
 #[derive(
     Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
 )]
 #[serde(rename_all = "camelCase")]
 pub struct ComicInfo {
-    #[yaserde(rename = "Manga")]
-    pub manga: String,
-    /// 漫画名
-    #[yaserde(rename = "Series")]
-    pub series: String,
-    /// 出版社
-    #[yaserde(rename = "Publisher")]
-    pub publisher: String,
-    /// 作者
-    #[yaserde(rename = "Writer")]
-    pub writer: String,
-    /// 漫画类型
-    #[yaserde(rename = "Genre")]
-    pub genre: String,
-    #[yaserde(rename = "Summary")]
-    pub summary: String,
     /// 章节名
     #[yaserde(rename = "Title")]
     pub title: String,
+    /// 漫画名
+    #[yaserde(rename = "Series")]
+    pub series: String,
     /// 普通章节序号
     #[yaserde(rename = "Number")]
     pub number: Option<String>,
+    /// 章节总数，Kavita以此判断漫画的连载状态
+    /// - `0` => Ongoing  
+    /// - `非零`且与`Number`或`Volume`一致 => Completed  
+    /// - `其他非零值` => Ended
+    #[yaserde(rename = "Count")]
+    pub count: i64,
     /// 卷序号
     #[yaserde(rename = "Volume")]
     pub volume: Option<String>,
-    /// 如果值为Special，则该章节会被Kavita视为特刊
-    #[yaserde(rename = "Format")]
-    pub format: Option<String>,
+    /// 漫画的别名
+    #[yaserde(rename = "AlternateSeries")]
+    pub alternate_series: Option<String>,
+    #[yaserde(rename = "Summary")]
+    pub summary: String,
+    /// 章节创建时间的年份
+    #[yaserde(rename = "Year")]
+    pub year: Option<i32>,
+    #[yaserde(rename = "Month")]
+    pub month: Option<u32>,
+    #[yaserde(rename = "Day")]
+    pub day: Option<u32>,
+    /// 作者
+    #[yaserde(rename = "Writer")]
+    pub writer: String,
+    /// 作画，拷贝漫画不区分作者的分工，无法知道谁负责作画，所以不设置
+    #[yaserde(rename = "Penciller")]
+    pub penciller: Option<String>,
+    /// 出版社
+    #[yaserde(rename = "Publisher")]
+    pub publisher: String,
+    /// 漫画类型
+    #[yaserde(rename = "Genre")]
+    pub genre: String,
+    /// 漫画的地区、受众和连载状态，比如`日本, 少年漫画, 連載中`
+    #[yaserde(rename = "Tags")]
+    pub tags: String,
+    /// 漫画详情页的链接
+    #[yaserde(rename = "Web")]
+    pub web: String,
     /// 该章节的有多少页
     #[yaserde(rename = "PageCount")]
     pub page_count: i64,
-    /// 章节总数
-    /// - `0` => Ongoing  
-    /// - `非零`且与`Number`或`Volume`一致 => Completed  
-    /// - `其他非零值` => Ended
-    #[yaserde(rename = "Count")]
-    pub count: i64,
+    #[yaserde(rename = "LanguageISO")]
+    pub language_iso: String,
+    /// 如果值为Special，则该章节会被Kavita视为特刊
+    #[yaserde(rename = "Format")]
+    pub format: Option<String>,
+    #[yaserde(rename = "Manga")]
+    pub manga: String,
+    /// 由漫画的`restrict`转换而来
+    #[yaserde(rename = "AgeRating")]
+    pub age_rating: String,
+    /// 每一页的信息，导出时才知道
+    #[yaserde(rename = "Pages")]
+    pub pages: ComicPages,
+}
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicPages {
+    #[yaserde(rename = "Page")]
+    pub pages: Vec<ComicPageInfo>,
+}
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicPageInfo {
+    /// 页的序号，从0开始
+    #[yaserde(attribute = true, rename = "Image")]
+    pub image: u32,
+    /// 封面为`FrontCover`，其他页不设置，阅读器会视为`Story`
+    #[yaserde(attribute = true, rename = "Type")]
+    pub page_type: Option<String>,
+    #[yaserde(attribute = true, rename = "ImageWidth")]
+    pub image_width: u32,
+    #[yaserde(attribute = true, rename = "ImageHeight")]
+    pub image_height: u32,
+}
+
+impl ComicPageInfo {
+    pub fn new(image: u32, (image_width, image_height): (u32, u32), is_cover: bool) -> Self {
+        Self {
+            image,
+            page_type: is_cover.then(|| "FrontCover".to_string()),
+            image_width,
+            image_height,
+        }
+    }
 }
+
 impl ComicInfo {
     #[allow(clippy::cast_possible_wrap)]
     pub fn from(comic: &Comic, chapter_info: &ChapterInfo) -> ComicInfo {
@@ -65,17 +137,41 @@ impl ComicInfo {
             ComicStatus::Completed => chapter_info.group_size,
         };
 
+        let authors = comic
+            .comic
+            .author
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let tags = [
+            &comic.comic.region.display,
+            &comic.comic.reclass.display,
+            &comic.comic.status.display,
+        ]
+        .into_iter()
+        .filter(|tag| !tag.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+        let created_date = chapter_info.created_date();
+
         ComicInfo {
-            manga: "Yes".to_string(),
+            title: chapter_info.chapter_title.clone(),
             series: chapter_info.comic_title.clone(),
+            number,
+            count,
+            volume,
+            alternate_series: comic.comic.alias.clone().filter(|alias| !alias.is_empty()),
+            summary: comic.comic.brief.clone(),
+            year: created_date.map(|date| date.year()),
+            month: created_date.map(|date| date.month()),
+            day: created_date.map(|date| date.day()),
+            writer: authors,
+            penciller: None,
             publisher: "拷贝漫画".to_string(),
-            writer: comic
-                .comic
-                .author
-                .iter()
-                .map(|a| a.name.as_str())
-                .collect::<Vec<_>>()
-                .join(", "),
             genre: comic
                 .comic
                 .theme
@@ -83,13 +179,74 @@ impl ComicInfo {
                 .map(|t| t.name.as_str())
                 .collect::<Vec<_>>()
                 .join(", "),
-            summary: comic.comic.brief.clone(),
-            title: chapter_info.chapter_title.clone(),
-            number,
-            volume,
-            format,
+            tags,
+            web: format!("{COMIC_WEB_URL_PREFIX}/{}", comic.comic.path_word),
             page_count: chapter_info.chapter_size,
-            count,
+            language_iso: "zh".to_string(),
+            format,
+            manga: "Yes".to_string(),
+            age_rating: get_age_rating(&comic.comic.restrict).to_string(),
+            pages: ComicPages::default(),
+        }
+    }
+
+    pub fn to_xml(&self) -> eyre::Result<String> {
+        let cfg = yaserde::ser::Config {
+            perform_indent: true,
+            ..Default::default()
+        };
+        yaserde::ser::to_string_with_config(self, &cfg)
+            .map_err(|err_msg| eyre!("序列化`ComicInfo.xml`失败: {err_msg}"))
+    }
+
+    pub fn from_xml(xml: &str) -> eyre::Result<ComicInfo> {
+        yaserde::de::from_str(xml)
+            .map_err(|err_msg| eyre!("反序列化`ComicInfo.xml`失败: {err_msg}"))
+    }
+
+    /// 设置每一页的信息，同时以实际导出的页数更新`PageCount`
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn set_pages(&mut self, pages: Vec<ComicPageInfo>) {
+        self.page_count = pages.len() as i64;
+        self.pages = ComicPages { pages };
+    }
+
+    /// 合并CBZ时，用第一个和最后一个章节描述整个CBZ
+    pub fn from_merged(
+        comic: &Comic,
+        first_chapter_info: &ChapterInfo,
+        last_chapter_info: &ChapterInfo,
+    ) -> ComicInfo {
+        let mut comic_info = ComicInfo::from(comic, first_chapter_info);
+        if first_chapter_info.chapter_uuid == last_chapter_info.chapter_uuid {
+            return comic_info;
         }
+
+        comic_info.title = format!(
+            "{} - {}",
+            first_chapter_info.chapter_title, last_chapter_info.chapter_title
+        );
+        // 序号改为范围，比如`1-10`
+        let order_range = format!("{}-{}", first_chapter_info.order, last_chapter_info.order);
+        if comic_info.volume.is_some() {
+            comic_info.volume = Some(order_range);
+        } else {
+            comic_info.number = Some(order_range);
+        }
+
+        comic_info
+    }
+}
+
+/// 把拷贝漫画的`restrict`转换为ComicInfo的`AgeRating`
+fn get_age_rating(restrict: &LabeledValue) -> &'static str {
+    if restrict.display.contains("18") {
+        "R18+"
+    } else if restrict.display.contains("15") {
+        "MA15+"
+    } else if restrict.value == 0 {
+        "Everyone"
+    } else {
+        "Unknown"
     }
 }
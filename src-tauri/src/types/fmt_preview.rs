@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 要预览的是配置中的哪个模板，决定了可用的字段和生成的路径
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum FmtKind {
+    ComicDir,
+    ChapterDir,
+    PdfExportDir,
+    CbzExportDir,
+    EpubExportDir,
+    LongImgExportDir,
+    MergePdf,
+    MergeComicPdf,
+    MergeCbz,
+    MergeEpub,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FmtPreview {
+    /// 抽样的路径，章节模板按分组和`order`排序，每个分组最多`sample_count`个
+    pub samples: Vec<FmtPreviewPath>,
+    /// 模板一共会生成多少个路径
+    pub total_count: u32,
+    /// 多个章节或分组生成了同一个路径，所有路径都会检查，不只是抽样的路径
+    ///
+    /// 冲突的章节路径会自动加上章节ID的前8位来区分，`samples`中是区分后的路径，冲突的合并文件则会互相覆盖
+    pub collisions: Vec<FmtPathCollision>,
+    /// 不合法或过长的路径层级，所有路径都会检查
+    pub problems: Vec<FmtPathProblem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FmtPreviewPath {
+    /// 生成这个路径的对象，比如`默認 - 第13话`
+    pub source: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FmtPathCollision {
+    pub path: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FmtPathProblem {
+    pub source: String,
+    pub path: String,
+    /// 有问题的那一层目录名或文件名
+    pub component: String,
+    pub reason: String,
+}
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateComicsReport {
+    /// 试运行时为需要合并的漫画，否则为已成功合并的漫画
+    pub merges: Vec<DuplicateComicMerge>,
+    /// 因为冲突等原因没有合并的漫画
+    pub skipped: Vec<DuplicateComicSkip>,
+    /// 合并失败的漫画，试运行时总是为空
+    pub failed: Vec<DuplicateComicFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateComicMerge {
+    pub comic_uuid: String,
+    pub comic_path_word: String,
+    pub comic_title: String,
+    /// 合并后保留的漫画目录
+    pub kept_dir: PathBuf,
+    /// 漫画的所有版本，包括保留的版本
+    pub copies: Vec<DuplicateComicCopy>,
+    /// 至少在一个版本里下载过的章节
+    pub chapters: Vec<DuplicateChapter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateComicCopy {
+    pub comic_dir: PathBuf,
+    /// 这个版本下载过的章节数
+    pub downloaded_chapter_count: i64,
+    /// 合并时保留这个版本的章节数
+    pub kept_chapter_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateChapter {
+    pub chapter_uuid: String,
+    /// 比如`默認 - 第13话`
+    pub source: String,
+    /// 此章节有多少页
+    pub chapter_size: i64,
+    /// 这个章节在各个版本里的情况，没有下载这个章节的版本不在其中
+    pub copies: Vec<DuplicateChapterCopy>,
+    /// 合并后章节所在的目录
+    pub new_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateChapterCopy {
+    pub chapter_dir: PathBuf,
+    pub img_count: i64,
+    /// 所有图片的大小之和，单位为字节
+    pub img_size: u64,
+    /// 合并时是否保留这个版本的章节
+    pub is_kept: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateComicSkip {
+    pub comic_path_word: String,
+    pub comic_title: String,
+    pub comic_dirs: Vec<PathBuf>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateComicFailure {
+    pub merge: DuplicateComicMerge,
+    pub error: String,
+    /// 回滚失败的原因，为`None`时移动过的目录和元数据都已恢复原样，否则需要手动处理
+    pub rollback_error: Option<String>,
+}
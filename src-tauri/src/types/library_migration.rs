@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryMigrationReport {
+    /// 试运行时为需要迁移的漫画，否则为已成功迁移的漫画
+    pub migrations: Vec<ComicMigration>,
+    /// 因为冲突等原因没有迁移的漫画
+    pub skipped: Vec<ComicMigrationSkip>,
+    /// 迁移失败的漫画，试运行时总是为空
+    pub failed: Vec<ComicMigrationFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicMigration {
+    pub comic_uuid: String,
+    pub comic_path_word: String,
+    pub comic_title: String,
+    pub old_dir: PathBuf,
+    pub new_dir: PathBuf,
+    /// 路径有变化的已下载章节
+    pub chapters: Vec<ChapterMigration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterMigration {
+    pub chapter_uuid: String,
+    /// 比如`默認 - 第13话`
+    pub source: String,
+    pub old_dir: PathBuf,
+    pub new_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicMigrationSkip {
+    pub comic_path_word: String,
+    pub comic_title: String,
+    pub comic_download_dir: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicMigrationFailure {
+    pub migration: ComicMigration,
+    pub error: String,
+    /// 回滚失败的原因，为`None`时移动过的目录和元数据都已恢复原样，否则需要手动处理
+    pub rollback_error: Option<String>,
+}
@@ -0,0 +1,40 @@
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 导出长图的格式
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub enum LongImgFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl LongImgFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LongImgFormat::Png => "png",
+            LongImgFormat::Jpeg => "jpg",
+            LongImgFormat::Webp => "webp",
+        }
+    }
+
+    pub fn to_image_format(self) -> ImageFormat {
+        match self {
+            LongImgFormat::Png => ImageFormat::Png,
+            LongImgFormat::Jpeg => ImageFormat::Jpeg,
+            LongImgFormat::Webp => ImageFormat::WebP,
+        }
+    }
+
+    /// 该格式允许的最大宽高
+    pub fn max_dimension(self) -> u32 {
+        match self {
+            // PNG标准允许的最大宽高为2^31-1
+            LongImgFormat::Png => i32::MAX.unsigned_abs(),
+            LongImgFormat::Jpeg => 65535,
+            LongImgFormat::Webp => 16383,
+        }
+    }
+}
@@ -1,16 +1,23 @@
-use std::{collections::HashMap, io::Cursor, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
-use eyre::{OptionExt, WrapErr};
+use eyre::WrapErr;
 use image::ImageReader;
-use regex_lite::{Captures, Regex};
 use tauri::AppHandle;
 use tracing::instrument;
-use walkdir::WalkDir;
 
-use crate::{
-    extensions::{AppHandleExt, WalkDirEntryExt},
-    types::Comic,
-};
+use crate::{extensions::AppHandleExt, types::Comic};
+
+/// 大部分文件系统限制每一层目录名或文件名最多255字节，一个汉字在UTF-8中占3字节
+pub const MAX_FILENAME_BYTES: usize = 255;
+/// Windows的保留名，不区分大小写，带扩展名也不行，比如`con.pdf`
+pub const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
 
 pub fn filename_filter(s: &str) -> String {
     s.chars()
@@ -30,6 +37,110 @@ pub fn filename_filter(s: &str) -> String {
         .to_string()
 }
 
+/// 在`filename_filter`的基础上去掉控制字符和末尾的`.`，避开Windows保留名，并截断到`max_bytes`字节以内
+///
+/// 旧版本的目录名是用`filename_filter`生成的，为了兼容不能改它，所以新生成的目录名和文件名都用这个函数
+pub fn sanitize_filename(s: &str, max_bytes: usize) -> String {
+    let name: String = filename_filter(s)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    // Windows会自动去掉末尾的`.`和空格，导致实际的目录名与记录的不一致
+    let mut name = name.trim_end_matches(['.', ' ']).to_string();
+
+    if is_windows_reserved_name(&name) {
+        let stem_len = name.find('.').unwrap_or(name.len());
+        name.insert(stem_len, '_');
+    }
+
+    truncate_filename(&name, max_bytes)
+}
+
+pub fn is_windows_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved_name| reserved_name.eq_ignore_ascii_case(stem))
+}
+
+/// 超过`max_bytes`字节时按字符边界截断`name`，并在末尾加上原名的哈希，让前缀相同的不同长名字截断后仍然不同
+///
+/// 哈希只由原名决定，所以同一个名字每次截断的结果都一样
+pub fn truncate_filename(name: &str, max_bytes: usize) -> String {
+    if name.len() <= max_bytes {
+        return name.to_string();
+    }
+
+    let hash_suffix = format!("~{}", stable_hash(name));
+    let mut end = max_bytes.saturating_sub(hash_suffix.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}{hash_suffix}", name[..end].trim_end())
+}
+
+/// 32位的FNV-1a哈希，与`DefaultHasher`不同，结果不会随Rust版本变化，可以用在文件名里
+pub fn stable_hash(s: &str) -> String {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in s.bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    format!("{hash:08x}")
+}
+
+/// 给路径最后一层的名字加上` [suffix]`，`ext`不为空时加在`.{ext}`前面
+///
+/// 加上后超过`max_bytes`字节的话，会先用`truncate_filename`截断原来的名字
+pub fn add_filename_suffix(path: &Path, suffix: &str, ext: &str, max_bytes: usize) -> PathBuf {
+    let filename = path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let ext_with_dot = if ext.is_empty() {
+        String::new()
+    } else {
+        format!(".{ext}")
+    };
+    let stem = filename
+        .strip_suffix(&ext_with_dot)
+        .unwrap_or(&filename)
+        .to_string();
+
+    let suffix = format!(" [{suffix}]");
+    let max_stem_bytes = max_bytes.saturating_sub(suffix.len() + ext_with_dot.len());
+    let stem = truncate_filename(&stem, max_stem_bytes);
+
+    path.with_file_name(format!("{stem}{suffix}{ext_with_dot}"))
+}
+
+/// 找出相同的路径，返回每组相同路径在`paths`中的下标，只有一个路径的组不会返回
+///
+/// Windows和macOS默认不区分大小写，所以只是大小写不同也算相同
+pub fn find_path_collisions<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<Vec<usize>> {
+    // 用BTreeMap让结果的顺序固定
+    let mut path_indexes: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, path) in paths.into_iter().enumerate() {
+        let key = path.to_string_lossy().to_lowercase();
+        path_indexes.entry(key).or_default().push(i);
+    }
+
+    path_indexes
+        .into_values()
+        .filter(|indexes| indexes.len() > 1)
+        .collect()
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 #[instrument(level = "error", skip_all)]
 pub fn get_dimensions(img_data: &[u8]) -> eyre::Result<(u32, u32)> {
     let reader = ImageReader::new(Cursor::new(&img_data)).with_guessed_format()?;
@@ -37,41 +148,19 @@ pub fn get_dimensions(img_data: &[u8]) -> eyre::Result<(u32, u32)> {
     Ok(dimensions)
 }
 
+/// 查询已下载漫画的索引，不会遍历下载目录
 #[instrument(level = "error", skip_all)]
 pub fn create_path_word_to_dir_map(app: &AppHandle) -> eyre::Result<HashMap<String, PathBuf>> {
     let mut path_word_to_dir_map: HashMap<String, PathBuf> = HashMap::new();
     let download_dir = app.get_config().read().download_dir.clone();
-    if !download_dir.exists() {
-        return Ok(path_word_to_dir_map);
-    }
 
-    for entry in WalkDir::new(&download_dir)
-        .into_iter()
-        .filter_map(Result::ok)
-    {
-        let path = entry.path();
-        if !entry.is_comic_metadata() {
-            continue;
-        }
-
-        let metadata_str =
-            std::fs::read_to_string(path).wrap_err(format!("读取`{}`失败", path.display()))?;
-        let comic_json: serde_json::Value = serde_json::from_str(&metadata_str).wrap_err(
-            format!("将`{}`反序列化为serde_json::Value失败", path.display()),
-        )?;
-        let path_word = comic_json
-            .pointer("/comic/path_word")
-            .and_then(|path_word| path_word.as_str())
-            .ok_or_eyre(format!("`{}`没有`comic.path_word`字段", path.display()))?
-            .to_string();
-
-        let parent = path
-            .parent()
-            .ok_or_eyre(format!("`{}`没有父目录", path.display()))?;
-
-        path_word_to_dir_map
-            .entry(path_word)
-            .or_insert(parent.to_path_buf());
+    let library_index = app.get_library_index();
+    library_index
+        .ensure_built(&download_dir)
+        .wrap_err("建立已下载漫画索引失败")?;
+
+    for (path_word, comic_dir) in library_index.get_comic_dirs()? {
+        path_word_to_dir_map.entry(path_word).or_insert(comic_dir);
     }
 
     Ok(path_word_to_dir_map)
@@ -94,81 +183,3 @@ pub async fn get_comic(app: AppHandle, comic_path_word: &str) -> eyre::Result<Co
 
     Ok(comic)
 }
-
-/// 预处理`fmt`中的`order`占位符
-///
-/// ### 功能描述
-/// 标准的格式化(如`{order:0>4}`)会将宽度补齐应用于整个数字字符串
-/// 当`order`为`5.1`时，标准输出为`05.1`(总长度4)
-///
-/// 本函数旨在实现**仅对整数部分补齐，小数部分追加在后**的效果
-/// 当`order`为`5.1`时，本函数会将其转换为`0005.1`(整数补齐至4位，小数保留)
-///
-/// ### 处理流程
-/// 1. **解析数值**：从`vars`中提取`order`，将其拆分为整数部分和小数部分
-/// 2. **正则扫描**：使用正则查找模板中的`{order}`或`{order:xxx}`占位符，同时兼容`{{` 和 `}}`转义
-/// 3. **自定义格式化**：
-///    - 提取占位符中的格式参数(如`0>4`)
-///    - 仅将该参数应用于整数部分
-///    - 若存在非零小数部分，将其追加到格式化后的整数后面
-/// 4. **原地替换**：将计算出的最终字符串(如 `0005.1`)直接替换掉原模板中的占位符
-///
-/// ### 示例
-/// - 输入 fmt: `"{order:0>3} {chapter_title}"`, order: `"1.5"`
-/// - 处理后 fmt: `"001.5 {chapter_title}"`
-#[instrument(level = "error", skip_all, fields(fmt = fmt))]
-pub fn preprocess_order_placeholder(
-    fmt: &mut String,
-    vars: &HashMap<String, String>,
-) -> eyre::Result<()> {
-    use strfmt::strfmt;
-
-    let Some(order_str) = vars.get("order") else {
-        return Ok(());
-    };
-
-    // 分离整数和小数
-    let (int_part, frac_part) = match order_str.split_once('.') {
-        Some((i, f)) => (i, f),
-        None => (order_str.as_str(), ""),
-    };
-    let should_append_frac = !frac_part.is_empty() && frac_part != "0";
-
-    // group 1: "{{" (转义左括号)
-    // group 2: "}}" (转义右括号)
-    // group 3: "{order...}" (真正的目标)
-    // group 4: 冒号后的格式参数 (仅当 group 3 匹配时有效)
-    let re = Regex::new(r"(\{\{)|(\}\})|(\{order(?::(.*?))?\})")?;
-
-    // 执行替换
-    let new_fmt = re.replace_all(fmt, |caps: &Captures| {
-        // 遇到 {{，原样返回，消耗掉字符避免后续匹配误伤
-        if caps.get(1).is_some() {
-            return "{{".to_string();
-        }
-        // 遇到 }}，同理
-        if caps.get(2).is_some() {
-            return "}}".to_string();
-        }
-        // 匹配到了 {order...}
-        // 此时 Group 4 是格式参数 (例如 "0>4")
-        let fmt_spec = caps.get(4).map_or("", |m| m.as_str());
-
-        // 构造临时模板 "{v:xxx}" 来格式化整数部分
-        let int_fmt = format!("{{v:{fmt_spec}}}");
-        let mut temp_vars = HashMap::new();
-        temp_vars.insert("v".to_string(), int_part.to_string());
-
-        let formatted_int = strfmt(&int_fmt, &temp_vars).unwrap_or(int_part.to_string());
-
-        if should_append_frac {
-            format!("{formatted_int}.{frac_part}")
-        } else {
-            formatted_int
-        }
-    });
-
-    *fmt = new_fmt.to_string();
-
-    Ok(())
-}
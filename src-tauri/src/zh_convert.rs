@@ -0,0 +1,103 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+/// 简繁对照表，格式与OpenCC的`STCharacters.txt`相同
+///
+/// 只收录了约990个简繁写法不同的常用字，不是完整的OpenCC字表，表中没有的字转换时原样保留
+///
+/// 每行是一个简体字和它对应的繁体字，用`\t`分隔，有多个繁体字时用空格分隔，第一个是简转繁的结果
+const ST_CHARACTERS: &str = include_str!("zh_convert/st_characters.txt");
+
+struct ConversionTables {
+    s2t: HashMap<char, char>,
+    t2s: HashMap<char, char>,
+}
+
+fn get_tables() -> &'static ConversionTables {
+    static TABLES: OnceLock<ConversionTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut s2t = HashMap::new();
+        let mut t2s = HashMap::new();
+        for line in ST_CHARACTERS.lines() {
+            let Some((simplified, traditionals)) = line.split_once('\t') else {
+                continue;
+            };
+            let Some(simplified) = simplified.chars().next() else {
+                continue;
+            };
+            for (i, traditional) in traditionals
+                .split(' ')
+                .filter_map(|t| t.chars().next())
+                .enumerate()
+            {
+                if i == 0 {
+                    s2t.insert(simplified, traditional);
+                }
+                // 多个简体字对应同一个繁体字时，以表中先出现的为准
+                t2s.entry(traditional).or_insert(simplified);
+            }
+        }
+        ConversionTables { s2t, t2s }
+    })
+}
+
+/// 简体转繁体，只按字转换，不处理词语，比如`头发`会转为`頭發`而不是`頭髮`
+///
+/// 对照表之外的字原样保留
+pub fn to_traditional(s: &str) -> String {
+    let s2t = &get_tables().s2t;
+    s.chars().map(|c| *s2t.get(&c).unwrap_or(&c)).collect()
+}
+
+/// 繁体转简体，只按字转换，对照表之外的字原样保留
+pub fn to_simplified(s: &str) -> String {
+    let t2s = &get_tables().t2s;
+    s.chars().map(|c| *t2s.get(&c).unwrap_or(&c)).collect()
+}
+
+/// 转为不带声调的拼音，汉字之间用空格分隔，比如`第13话`会转为`Di 13 Hua`
+///
+/// 汉字以外的非ASCII字符(比如假名)也会尽量音译，结果只包含ASCII字符
+pub fn to_pinyin(s: &str) -> String {
+    let mut output = String::new();
+    // 上一个字符是否为转换后的汉字，用于在汉字与后面的字母、数字之间加空格
+    let mut after_han = false;
+    for c in s.chars() {
+        if c.is_ascii() {
+            if after_han && c.is_ascii_alphanumeric() {
+                output.push(' ');
+            }
+            output.push(c);
+            after_han = false;
+            continue;
+        }
+
+        let Some(transliterated) = deunicode::deunicode_char(c) else {
+            continue;
+        };
+
+        if is_han(c) {
+            let needs_space = output
+                .chars()
+                .last()
+                .is_some_and(|last| last.is_ascii_alphanumeric());
+            if needs_space {
+                output.push(' ');
+            }
+            output.push_str(transliterated.trim());
+            after_han = true;
+        } else {
+            output.push_str(transliterated);
+            after_han = false;
+        }
+    }
+    output
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c,
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}'
+    )
+}
@@ -145,6 +145,14 @@ async exportCbzChapters(comic: Comic, chapterUuids: string[]) : Promise<Result<n
     else return { status: "error", error: e  as any };
 }
 },
+async updateCbzComicInfo(comic: Comic) : Promise<Result<CbzComicInfoUpdateReport, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("update_cbz_comic_info", { comic }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async exportPdfChapters(comic: Comic, chapterUuids: string[]) : Promise<Result<null, CommandError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("export_pdf_chapters", { comic, chapterUuids }) };
@@ -153,6 +161,73 @@ async exportPdfChapters(comic: Comic, chapterUuids: string[]) : Promise<Result<n
     else return { status: "error", error: e  as any };
 }
 },
+async estimatePdfSize(comic: Comic, chapterUuids: string[]) : Promise<Result<number, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("estimate_pdf_size", { comic, chapterUuids }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportLongImg(comic: Comic) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_long_img", { comic }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportLongImgChapters(comic: Comic, chapterUuids: string[]) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_long_img_chapters", { comic, chapterUuids }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportEpub(comic: Comic) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_epub", { comic }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportEpubChapters(comic: Comic, chapterUuids: string[]) : Promise<Result<null, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_epub_chapters", { comic, chapterUuids }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async previewFmt(comic: Comic, fmtKind: FmtKind, fmt: string, sampleCount: number) : Promise<Result<FmtPreview, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("preview_fmt", { comic, fmtKind, fmt, sampleCount }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async migrateLibrary(dryRun: boolean) : Promise<Result<LibraryMigrationReport, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("migrate_library", { dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async mergeDuplicateComics(dryRun: boolean) : Promise<Result<DuplicateComicsReport, CommandError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("merge_duplicate_comics", { dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async cancelExport(comicPathWord: string) : Promise<number> {
+    return await TAURI_INVOKE("cancel_export", { comicPathWord });
+},
 async updateDownloadedComics() : Promise<Result<null, CommandError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("update_downloaded_comics") };
@@ -217,13 +292,19 @@ async openLogFile(path: string) : Promise<Result<LogMetadata[], CommandError>> {
 export const events = __makeEvents__<{
 downloadEvent: DownloadEvent,
 exportCbzEvent: ExportCbzEvent,
+exportEpubEvent: ExportEpubEvent,
+exportLongImgEvent: ExportLongImgEvent,
 exportPdfEvent: ExportPdfEvent,
+libraryChangedEvent: LibraryChangedEvent,
 logEvent: LogEvent,
 updateDownloadedComicsEvent: UpdateDownloadedComicsEvent
 }>({
 downloadEvent: "download-event",
 exportCbzEvent: "export-cbz-event",
+exportEpubEvent: "export-epub-event",
+exportLongImgEvent: "export-long-img-event",
 exportPdfEvent: "export-pdf-event",
+libraryChangedEvent: "library-changed-event",
 logEvent: "log-event",
 updateDownloadedComicsEvent: "update-downloaded-comics-event"
 })
@@ -237,6 +318,39 @@ updateDownloadedComicsEvent: "update-downloaded-comics-event"
 export type ApiDomainMode = "Default" | "Custom"
 export type Author = { name: string; alias: string | null; path_word: string }
 export type AuthorRespData = { name: string; alias: string | null; path_word: string }
+export type CbzComicInfoUpdateReport = { 
+/**
+ * `ComicInfo.xml`有变化并已重写的CBZ
+ */
+updatedCbzPaths: string[]; 
+/**
+ * 标记为已导出CBZ，但按当前的章节导出格式找不到CBZ的章节，通常是修改过导出格式或手动移动过CBZ
+ */
+missingChapters: MissingCbzChapter[] }
+/**
+ * CBZ中文件的压缩方式
+ */
+export type CbzCompression = 
+/**
+ * 不压缩
+ */
+"Stored" | "Deflate" | 
+/**
+ * 压缩率更高，但有些阅读器不支持
+ */
+"Zstd"
+/**
+ * CBZ配置
+ */
+export type CbzConfig = { compression: CbzCompression; 
+/**
+ * 压缩等级，`Deflate`为0~9，`Zstd`为1~22，`Stored`时忽略
+ */
+compressionLevel: number; 
+/**
+ * JPEG和WebP本身已经压缩过，再压缩几乎没有收益，为`true`时这些图片始终不压缩
+ */
+storeCompressedImgs: boolean }
 export type ChapterInGetChapterRespData = { index: number; uuid: string; count: number; ordered: number; size: number; name: string; comic_id: string; comic_path_word: string; group_id: string | null; group_path_word: string; type: number; news: string; datetime_created: string; prev: string | null; next: string | null; contents: ContentRespData[]; words: number[]; is_long: boolean }
 export type ChapterInGetChaptersRespData = { index: number; uuid: string; count: number; ordered: number; size: number; name: string; comic_id: string; comic_path_word: string; group_id: string | null; group_path_word: string; type: number; news: string; datetime_created: string; prev: string | null; next: string | null }
 export type ChapterInfo = { chapterUuid: string; chapterTitle: string; 
@@ -252,10 +366,18 @@ groupSize: number;
  * 此章节在group中的顺序
  */
 order: number; 
+/**
+ * 章节的创建时间，比如`2023-05-12`
+ */
+datetimeCreated: string; 
 /**
  * 漫画的连载状态
  */
 comicStatus: ComicStatus; 
+/**
+ * 是否为条漫，下载章节时才能知道
+ */
+isLong: boolean; 
 /**
  * 是否曾导出过PDF
  */
@@ -263,7 +385,24 @@ isPdfExported: boolean;
 /**
  * 是否曾导出过CBZ
  */
-isCbzExported: boolean; isDownloaded?: boolean | null; chapterDownloadDir?: string | null }
+isCbzExported: boolean; 
+/**
+ * 是否曾导出过长图
+ */
+isLongImgExported: boolean; 
+/**
+ * 是否曾导出过EPUB
+ */
+isEpubExported: boolean; 
+/**
+ * 下载时对图片做过的处理，为`None`表示下载时没有处理，导出时会跳过已经做过的处理
+ */
+downloadImgProcess: ImgProcessConfig | null; isDownloaded?: boolean | null; chapterDownloadDir?: string | null }
+export type ChapterMigration = { chapterUuid: string; 
+/**
+ * 比如`默認 - 第13话`
+ */
+source: string; oldDir: string; newDir: string }
 export type Comic = { is_banned: boolean; is_lock: boolean; is_login: boolean; is_mobile_bind: boolean; is_vip: boolean; comic: ComicDetail; popular: number; groups: { [key in string]: Group }; isDownloaded?: boolean | null; comicDownloadDir?: string | null }
 export type ComicDetail = { uuid: string; b_404: boolean; b_hidden: boolean; ban: number; ban_ip: boolean | null; name: string; alias: string | null; path_word: string; close_comment: boolean; close_roast: boolean; free_type: LabeledValue; restrict: LabeledValue; reclass: LabeledValue; seo_baidu: string | null; region: LabeledValue; status: LabeledValue; author: Author[]; theme: Theme[]; brief: string; datetime_updated: string; cover: string; last_chapter: LastChapter; popular: number; 
 /**
@@ -273,19 +412,155 @@ groups: { [key in string]: ChapterInfo[] } }
 export type ComicInFavorite = { uuid: string; bDisplay: boolean; name: string; pathWord: string; author: AuthorRespData[]; cover: string; status: number; popular: number; datetimeUpdated: string; lastChapterId: string; lastChapterName: string; isDownloaded: boolean; comicDownloadDir: string }
 export type ComicInGetChapterRespData = { name: string; uuid: string; path_word: string; restrict: RestrictRespData }
 export type ComicInSearch = { name: string; alias: string | null; pathWord: string; cover: string; ban: number; author: AuthorRespData[]; popular: number; isDownloaded: boolean; comicDownloadDir: string }
+export type ComicMigration = { comicUuid: string; comicPathWord: string; comicTitle: string; oldDir: string; newDir: string; 
+/**
+ * 路径有变化的已下载章节
+ */
+chapters: ChapterMigration[] }
+export type ComicMigrationFailure = { migration: ComicMigration; error: string; 
+/**
+ * 回滚失败的原因，为`None`时移动过的目录和元数据都已恢复原样，否则需要手动处理
+ */
+rollbackError: string | null }
+export type ComicMigrationSkip = { comicPathWord: string; comicTitle: string; comicDownloadDir: string; reason: string }
 export type ComicStatus = "ongoing" | "completed"
 export type CommandError = { err_title: string; message: string }
 export type Config = { token: string; downloadDir: string; exportDir: string; apiDomainMode: ApiDomainMode; customApiDomain: string; downloadFormat: DownloadFormat; enableFileLogger: boolean; chapterConcurrency: number; chapterDownloadIntervalSec: number; imgConcurrency: number; imgDownloadIntervalSec: number; updateDownloadedComicsIntervalSec: number; comicDirFmt: string; chapterDirFmt: string; exportDirFmt: string; mergePdfFmt: string; createPdfConcurrency: number; enableMergePdf: boolean; 
+/**
+ * 合并PDF时每多少个章节拆分为一个PDF，0表示不按章节数拆分
+ */
+mergePdfChapterCount: number; 
+/**
+ * 合并PDF的最大大小，单位为MB，超过则拆分，0表示不按大小拆分
+ */
+mergePdfMaxSizeMb: number; 
+/**
+ * 合并PDF时把所有分组合并为一个PDF，书签按分组嵌套，此时使用`merge_comic_pdf_fmt`
+ */
+mergePdfAllGroups: boolean; 
+/**
+ * 所有分组合并为一个PDF时的路径模板，只能使用漫画字段
+ */
+mergeComicPdfFmt: string; 
+/**
+ * 所有目录名和文件名模板中的文字字段统一进行的转换，单个字段还可以用`s2t`、`t2s`、`pinyin`过滤器单独转换
+ */
+fmtTextConversion: TextConversion; 
 /**
  * 导出跳过模式
  */
-exportSkipMode: ExportSkipMode }
+exportSkipMode: ExportSkipMode; 
+/**
+ * 导出时是否把封面作为第一页
+ */
+exportWithCover: boolean; 
+/**
+ * 图片处理
+ */
+imgProcess: ImgProcessConfig; 
+/**
+ * 条漫(长条漫画)处理
+ */
+webtoon: WebtoonConfig; 
+/**
+ * 导出长图的格式
+ */
+longImgFormat: LongImgFormat; 
+/**
+ * 同时创建长图的章节数，拼接长图很吃内存，不宜太大
+ */
+createLongImgConcurrency: number; mergeEpubFmt: string; enableMergeEpub: boolean; 
+/**
+ * EPUB是否从右往左翻页
+ */
+epubRtl: boolean; mergeCbzFmt: string; enableMergeCbz: boolean; 
+/**
+ * 合并CBZ时每多少个章节拆分为一个CBZ，0表示不拆分
+ */
+mergeCbzChapterCount: number; 
+/**
+ * CBZ的压缩方式
+ */
+cbz: CbzConfig; 
+/**
+ * PDF的元数据和阅读方式
+ */
+pdf: PdfConfig }
 export type ContentRespData = { url: string }
 export type DownloadEvent = { event: "Speed"; data: { speed: string } } | { event: "RiskControl"; data: { chapterUuid: string; retryAfter: number } } | { event: "Sleeping"; data: { chapterUuid: string; remainingSec: number } } | { event: "TaskCreate"; data: { state: DownloadTaskState; comic: Comic; chapterInfo: ChapterInfo; downloadedImgCount: number; totalImgCount: number } } | { event: "TaskDelete"; data: { chapterUuid: string } } | { event: "TaskUpdate"; data: { chapterUuid: string; state: DownloadTaskState; downloadedImgCount: number; totalImgCount: number } }
 export type DownloadFormat = "Webp" | "Jpeg"
 export type DownloadTaskState = "Pending" | "Downloading" | "Paused" | "Completed" | "Failed"
-export type ExportCbzEvent = { event: "Start"; data: { uuid: string; comicTitle: string; groupTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string; comicPathWord: string; exportDir: string } }
-export type ExportPdfEvent = { event: "CreateStart"; data: { uuid: string; comicTitle: string; groupTitle: string; total: number } } | { event: "CreateProgress"; data: { uuid: string; current: number } } | { event: "CreateError"; data: { uuid: string } } | { event: "CreateEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "MergeStart"; data: { uuid: string; comicTitle: string; groupTitle: string; total: number } } | { event: "MergeError"; data: { uuid: string } } | { event: "MergeEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } }
+export type DuplicateChapter = { chapterUuid: string; 
+/**
+ * 比如`默認 - 第13话`
+ */
+source: string; 
+/**
+ * 此章节有多少页
+ */
+chapterSize: number; 
+/**
+ * 这个章节在各个版本里的情况，没有下载这个章节的版本不在其中
+ */
+copies: DuplicateChapterCopy[]; 
+/**
+ * 合并后章节所在的目录
+ */
+newDir: string }
+export type DuplicateChapterCopy = { chapterDir: string; imgCount: number; 
+/**
+ * 所有图片的大小之和，单位为字节
+ */
+imgSize: number; 
+/**
+ * 合并时是否保留这个版本的章节
+ */
+isKept: boolean }
+export type DuplicateComicCopy = { comicDir: string; 
+/**
+ * 这个版本下载过的章节数
+ */
+downloadedChapterCount: number; 
+/**
+ * 合并时保留这个版本的章节数
+ */
+keptChapterCount: number }
+export type DuplicateComicFailure = { merge: DuplicateComicMerge; error: string; 
+/**
+ * 回滚失败的原因，为`None`时移动过的目录和元数据都已恢复原样，否则需要手动处理
+ */
+rollbackError: string | null }
+export type DuplicateComicMerge = { comicUuid: string; comicPathWord: string; comicTitle: string; 
+/**
+ * 合并后保留的漫画目录
+ */
+keptDir: string; 
+/**
+ * 漫画的所有版本，包括保留的版本
+ */
+copies: DuplicateComicCopy[]; 
+/**
+ * 至少在一个版本里下载过的章节
+ */
+chapters: DuplicateChapter[] }
+export type DuplicateComicSkip = { comicPathWord: string; comicTitle: string; comicDirs: string[]; reason: string }
+export type DuplicateComicsReport = { 
+/**
+ * 试运行时为需要合并的漫画，否则为已成功合并的漫画
+ */
+merges: DuplicateComicMerge[]; 
+/**
+ * 因为冲突等原因没有合并的漫画
+ */
+skipped: DuplicateComicSkip[]; 
+/**
+ * 合并失败的漫画，试运行时总是为空
+ */
+failed: DuplicateComicFailure[] }
+export type ExportCbzEvent = { event: "Start"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "ValidationError"; data: { uuid: string; chapterUuid: string; chapterTitle: string; errMsg: string } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "MergeStart"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "MergeProgress"; data: { uuid: string; current: number } } | { event: "MergeError"; data: { uuid: string } } | { event: "MergeEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "Cancelled"; data: { uuid: string } }
+export type ExportEpubEvent = { event: "CreateStart"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "CreateProgress"; data: { uuid: string; current: number } } | { event: "CreateError"; data: { uuid: string } } | { event: "CreateEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "MergeStart"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "MergeError"; data: { uuid: string } } | { event: "MergeEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "Cancelled"; data: { uuid: string } }
+export type ExportLongImgEvent = { event: "Start"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "Cancelled"; data: { uuid: string } }
+export type ExportPdfEvent = { event: "CreateStart"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "CreateProgress"; data: { uuid: string; current: number } } | { event: "CreateValidationError"; data: { uuid: string; chapterUuid: string; chapterTitle: string; errMsg: string } } | { event: "CreateError"; data: { uuid: string } } | { event: "CreateEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "MergeStart"; data: { uuid: string; comicPathWord: string; comicTitle: string; groupTitle: string; total: number } } | { event: "MergeProgress"; data: { uuid: string; current: number } } | { event: "MergeError"; data: { uuid: string } } | { event: "MergeEnd"; data: { uuid: string; comicPathWord: string; exportDir: string } } | { event: "Cancelled"; data: { uuid: string } }
 /**
  * 导出跳过模式
  */
@@ -303,6 +578,40 @@ export type ExportSkipMode =
  */
 "SkipExported"
 export type FavoriteItem = { uuid: number; bFolder: boolean; comic: ComicInFavorite }
+/**
+ * 要预览的是配置中的哪个模板，决定了可用的字段和生成的路径
+ */
+export type FmtKind = "ComicDir" | "ChapterDir" | "PdfExportDir" | "CbzExportDir" | "EpubExportDir" | "LongImgExportDir" | "MergePdf" | "MergeComicPdf" | "MergeCbz" | "MergeEpub"
+export type FmtPathCollision = { path: string; sources: string[] }
+export type FmtPathProblem = { source: string; path: string; 
+/**
+ * 有问题的那一层目录名或文件名
+ */
+component: string; reason: string }
+export type FmtPreview = { 
+/**
+ * 抽样的路径，章节模板按分组和`order`排序，每个分组最多`sample_count`个
+ */
+samples: FmtPreviewPath[]; 
+/**
+ * 模板一共会生成多少个路径
+ */
+totalCount: number; 
+/**
+ * 多个章节或分组生成了同一个路径，所有路径都会检查，不只是抽样的路径
+ * 
+ * 冲突的章节路径会自动加上章节ID的前8位来区分，`samples`中是区分后的路径，冲突的合并文件则会互相覆盖
+ */
+collisions: FmtPathCollision[]; 
+/**
+ * 不合法或过长的路径层级，所有路径都会检查
+ */
+problems: FmtPathProblem[] }
+export type FmtPreviewPath = { 
+/**
+ * 生成这个路径的对象，比如`默認 - 第13话`
+ */
+source: string; path: string }
 export type GetChapterRespData = { is_banned: boolean; show_app: boolean; is_lock: boolean; is_login: boolean; is_mobile_bind: boolean; is_vip: boolean; comic: ComicInGetChapterRespData; chapter: ChapterInGetChapterRespData }
 export type GetFavoriteOrdering = 
 /**
@@ -319,20 +628,197 @@ export type GetFavoriteOrdering =
 "Read"
 export type GetFavoriteResult = Pagination<FavoriteItem>
 export type Group = { path_word: string; count: number; name: string }
+/**
+ * 图片处理配置，用于电子书阅读器等场景
+ */
+export type ImgProcessConfig = { 
+/**
+ * 导出时处理图片
+ */
+enableOnExport: boolean; 
+/**
+ * 下载时处理图片，处理后的图片直接保存到下载目录
+ */
+enableOnDownload: boolean; 
+/**
+ * 自动裁剪四周的纯色边框
+ */
+autoCrop: boolean; 
+/**
+ * 自动裁剪时，亮度与边框颜色的差值不超过这个值的像素视为边框
+ */
+autoCropTolerance: number; 
+/**
+ * 将横向的跨页拆分为两页，按从右到左的顺序排列
+ */
+splitSpread: boolean; 
+/**
+ * 转为灰度图
+ */
+grayscale: boolean; 
+/**
+ * 对比度调整，0表示不调整，正数增加对比度，负数降低对比度
+ */
+contrast: number; 
+/**
+ * gamma校正，1.0表示不调整
+ */
+gamma: number }
 export type JsonValue = null | boolean | number | string | JsonValue[] | { [key in string]: JsonValue }
 export type LabeledValue = { value: number; display: string }
 export type LastChapter = { uuid: string; name: string }
+export type LibraryChangedEvent = { 
+/**
+ * 下载目录里有变化的漫画的`path_word`
+ */
+comicPathWords: string[] }
+export type LibraryMigrationReport = { 
+/**
+ * 试运行时为需要迁移的漫画，否则为已成功迁移的漫画
+ */
+migrations: ComicMigration[]; 
+/**
+ * 因为冲突等原因没有迁移的漫画
+ */
+skipped: ComicMigrationSkip[]; 
+/**
+ * 迁移失败的漫画，试运行时总是为空
+ */
+failed: ComicMigrationFailure[] }
 export type LogEvent = { jsonRaw: string }
 export type LogLevel = "TRACE" | "DEBUG" | "INFO" | "WARN" | "ERROR"
 export type LogMetadata = { timestamp: string; level: LogLevel; fields: { [key in string]: JsonValue }; target: string; filename: string; line_number: number; span?: JsonValue; spans?: LogSpan[] }
 export type LogSpan = ({ [key in string]: null | boolean | number | string | JsonValue[] | { [key in string]: JsonValue } }) & { name: string }
 export type LoginRespData = { token: string; user_id: string; username: string; nickname: string; avatar: string; datetime_created: string; ticket: number; reward_ticket: number; downloads: number; vip_downloads: number; reward_downloads: number; scy_answer: boolean }
+/**
+ * 导出长图的格式
+ */
+export type LongImgFormat = "Png" | "Jpeg" | "Webp"
+export type MissingCbzChapter = { chapterUuid: string; chapterTitle: string; 
+/**
+ * 按当前的章节导出格式，CBZ应该在的路径
+ */
+expectedCbzPath: string }
 export type Pagination<T> = { list: T[]; total: number; limit: number; offset: number }
+/**
+ * PDF配置
+ */
+export type PdfConfig = { 
+/**
+ * 是否从右往左翻页
+ */
+rtl: boolean; 
+/**
+ * 是否以双页显示，封面单独占一页
+ */
+twoPageLayout: boolean; 
+/**
+ * 是否嵌入XMP元数据，有些文献管理软件只读取XMP
+ */
+embedXmp: boolean; 
+/**
+ * 页面尺寸
+ */
+pageSize: PdfPageSize; 
+/**
+ * 自定义页面的宽度，单位为毫米
+ */
+customPageWidthMm: number; 
+/**
+ * 自定义页面的高度，单位为毫米
+ */
+customPageHeightMm: number; 
+/**
+ * 页面尺寸为`Original`时，按这个DPI把图片的像素换算为页面尺寸，72表示1像素对应1点
+ */
+dpi: number; 
+/**
+ * 页边距，单位为毫米
+ */
+marginMm: number; 
+/**
+ * 是否把图片重新压缩为JPEG
+ */
+recompressImg: boolean; 
+/**
+ * 重新压缩时的JPEG质量，1~100
+ */
+jpegQuality: number; 
+/**
+ * 重新压缩时图片的最大宽度，超过则等比缩小，0表示不限制
+ */
+maxImgWidth: number; 
+/**
+ * 合并PDF时是否生成封面页，包含漫画封面、标题和章节范围
+ */
+mergedCoverPage: boolean; 
+/**
+ * 合并PDF时是否生成目录页，点击章节标题可以跳转
+ */
+mergedTocPage: boolean; 
+/**
+ * 合并PDF时是否设置页码标签，阅读器会把页码显示为`Ch.12 p.3`
+ */
+mergedPageLabels: boolean }
+/**
+ * PDF的页面尺寸
+ */
+export type PdfPageSize = 
+/**
+ * 按图片尺寸和DPI决定页面尺寸
+ */
+"Original" | "A4" | "A5" | "B5" | "Custom"
 export type RestrictRespData = { value: number; display: string }
 export type SearchResult = Pagination<ComicInSearch>
+/**
+ * 模板中文字字段的转换方式，只转换标题、作者这类文字字段，不转换ID、`path_word`和数字
+ */
+export type TextConversion = "None" | 
+/**
+ * 繁体转简体，只转换内置对照表中的约990个常用字
+ */
+"Simplified" | 
+/**
+ * 简体转繁体，只转换内置对照表中的约990个常用字
+ */
+"Traditional" | 
+/**
+ * 转为拼音，结果只包含ASCII字符
+ */
+"Pinyin"
 export type Theme = { name: string; path_word: string }
 export type UpdateDownloadedComicsEvent = { event: "GetComicStart"; data: { total: number } } | { event: "GetComicProgress"; data: { current: number; total: number } } | { event: "CreateDownloadTasksStart"; data: { comicPathWord: string; comicTitle: string } } | { event: "CreateDownloadTasksEnd"; data: { comicPathWord: string } } | { event: "GetComicEnd" }
 export type UserProfileRespData = { user_id: string; username: string; nickname: string; avatar: string; datetime_created: string; ticket: number; reward_ticket: number; downloads: number; vip_downloads: number; reward_downloads: number; scy_answer: boolean; day_downloads_refresh: string; day_downloads: number }
+/**
+ * 条漫配置
+ * 
+ * 条漫的图片是一条条竖长的切片，导出时会把切片拼接起来，再按`page_aspect_ratio`重新切分成页
+ */
+export type WebtoonConfig = { mode: WebtoonMode; 
+/**
+ * 每页的高宽比，比如A4纸为1.414
+ */
+pageAspectRatio: number; 
+/**
+ * 一行像素的最大亮度与最小亮度之差不超过这个值时，视为可以切分的空白行
+ */
+whitespaceTolerance: number }
+/**
+ * 条漫模式
+ */
+export type WebtoonMode = 
+/**
+ * 不处理，每张图片都是单独的一页
+ */
+"Off" | 
+/**
+ * 只处理`is_long`为`true`的章节
+ */
+"Auto" | 
+/**
+ * 处理所有章节
+ */
+"Always"
 
 /** tauri-specta globals **/
 